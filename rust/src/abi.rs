@@ -0,0 +1,1193 @@
+use std::fmt;
+
+use primitive_types::U256;
+use serde::Deserialize;
+
+use crate::{errors::AbiError, logs::Log, utility::sha3_hash};
+
+/// The type of an ABI value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamType {
+    Address,
+    Bool,
+    /// `uintN`, with its size in bits.
+    Uint(usize),
+    /// `intN`, with its size in bits.
+    Int(usize),
+    /// `bytesN`, with its size in bytes.
+    FixedBytes(usize),
+    Bytes,
+    String,
+    /// `T[]`.
+    Array(Box<ParamType>),
+    /// `T[N]`.
+    FixedArray(Box<ParamType>, usize),
+    /// `(T1,T2,...)`.
+    Tuple(Vec<ParamType>),
+}
+
+impl ParamType {
+    /// Parses a type like `uint256`, `bytes32[]` or `(address,string)[2]`.
+    /// Tuple components may be named, as in human-readable signatures.
+    pub fn parse(kind: &str) -> Result<ParamType, AbiError> {
+        let invalid = || AbiError::InvalidType(kind.to_string());
+        let kind = kind.trim();
+        if let Some(rest) = kind.strip_suffix(']') {
+            let open = rest.rfind('[').ok_or_else(invalid)?;
+            let inner = Box::new(ParamType::parse(&rest[..open])?);
+            return match &rest[open + 1..] {
+                "" => Ok(ParamType::Array(inner)),
+                size => size
+                    .parse()
+                    .map(|size| ParamType::FixedArray(inner, size))
+                    .map_err(|_| invalid()),
+            };
+        }
+        let tuple = kind.strip_prefix("tuple").unwrap_or(kind);
+        if let Some(components) = tuple
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'))
+        {
+            return split_list(components)
+                .into_iter()
+                .map(|component| Param::parse(component).map(|param| param.kind))
+                .collect::<Result<_, _>>()
+                .map(ParamType::Tuple);
+        }
+
+        let size = |prefix: &str, default: usize| -> Option<usize> {
+            match kind.strip_prefix(prefix)? {
+                "" => Some(default),
+                size => size.parse().ok(),
+            }
+        };
+        match kind {
+            "address" => Ok(ParamType::Address),
+            "bool" => Ok(ParamType::Bool),
+            "string" => Ok(ParamType::String),
+            "bytes" => Ok(ParamType::Bytes),
+            _ if kind.starts_with("uint") => match size("uint", 256) {
+                Some(bits) if bits % 8 == 0 && (8..=256).contains(&bits) => {
+                    Ok(ParamType::Uint(bits))
+                }
+                _ => Err(invalid()),
+            },
+            _ if kind.starts_with("int") => match size("int", 256) {
+                Some(bits) if bits % 8 == 0 && (8..=256).contains(&bits) => {
+                    Ok(ParamType::Int(bits))
+                }
+                _ => Err(invalid()),
+            },
+            _ if kind.starts_with("bytes") => match size("bytes", 0) {
+                Some(size @ 1..=32) => Ok(ParamType::FixedBytes(size)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Returns true if the values of the type are encoded in the tail, after an offset.
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            ParamType::Bytes | ParamType::String | ParamType::Array(_) => true,
+            ParamType::FixedArray(inner, size) => *size > 0 && inner.is_dynamic(),
+            ParamType::Tuple(types) => types.iter().any(ParamType::is_dynamic),
+            _ => false,
+        }
+    }
+
+    /// Returns true for the elementary types encoded in a single word, the only ones stored
+    /// as is in an event topic.
+    pub fn is_value_type(&self) -> bool {
+        matches!(
+            self,
+            ParamType::Address
+                | ParamType::Bool
+                | ParamType::Uint(_)
+                | ParamType::Int(_)
+                | ParamType::FixedBytes(_)
+        )
+    }
+
+    /// Returns the size of the encoding of a static type, or of the offset of a dynamic one.
+    fn head_size(&self) -> usize {
+        match self {
+            _ if self.is_dynamic() => 32,
+            ParamType::FixedArray(inner, size) => inner.head_size() * size,
+            ParamType::Tuple(types) => types.iter().map(ParamType::head_size).sum(),
+            _ => 32,
+        }
+    }
+}
+
+impl fmt::Display for ParamType {
+    /// Formats the canonical type, as used in signatures.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamType::Address => write!(f, "address"),
+            ParamType::Bool => write!(f, "bool"),
+            ParamType::Uint(bits) => write!(f, "uint{}", bits),
+            ParamType::Int(bits) => write!(f, "int{}", bits),
+            ParamType::FixedBytes(size) => write!(f, "bytes{}", size),
+            ParamType::Bytes => write!(f, "bytes"),
+            ParamType::String => write!(f, "string"),
+            ParamType::Array(inner) => write!(f, "{}[]", inner),
+            ParamType::FixedArray(inner, size) => write!(f, "{}[{}]", inner, size),
+            ParamType::Tuple(types) => write!(f, "({})", join(types, ",")),
+        }
+    }
+}
+
+/// An ABI value. Integers are stored as 256 bits words, two's complement for `Int`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Address(U256),
+    Bool(bool),
+    Uint(U256),
+    Int(U256),
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+    Array(Vec<Token>),
+    FixedArray(Vec<Token>),
+    Tuple(Vec<Token>),
+}
+
+impl Token {
+    /// Returns true if the value can be encoded as `kind`.
+    pub fn matches(&self, kind: &ParamType) -> bool {
+        match (self, kind) {
+            (Token::Address(value), ParamType::Address) => value.bits() <= 160,
+            (Token::Bool(_), ParamType::Bool) => true,
+            (Token::Uint(value), ParamType::Uint(bits)) => value.bits() <= *bits,
+            // two's complement: the bits above the sign bit of `intN` are copies of it.
+            (Token::Int(value), ParamType::Int(bits)) if value.bit(255) => (!*value).bits() < *bits,
+            (Token::Int(value), ParamType::Int(bits)) => value.bits() < *bits,
+            (Token::FixedBytes(bytes), ParamType::FixedBytes(size)) => bytes.len() == *size,
+            (Token::Bytes(_), ParamType::Bytes) | (Token::String(_), ParamType::String) => true,
+            (Token::Array(tokens), ParamType::Array(inner)) => {
+                tokens.iter().all(|token| token.matches(inner))
+            }
+            (Token::FixedArray(tokens), ParamType::FixedArray(inner, size)) => {
+                tokens.len() == *size && tokens.iter().all(|token| token.matches(inner))
+            }
+            (Token::Tuple(tokens), ParamType::Tuple(types)) => {
+                tokens.len() == types.len()
+                    && tokens
+                        .iter()
+                        .zip(types)
+                        .all(|(token, kind)| token.matches(kind))
+            }
+            _ => false,
+        }
+    }
+
+    fn is_dynamic(&self) -> bool {
+        match self {
+            Token::Bytes(_) | Token::String(_) | Token::Array(_) => true,
+            Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+                tokens.iter().any(Token::is_dynamic)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Address(value) => write!(f, "0x{:040x}", value),
+            Token::Bool(value) => write!(f, "{}", value),
+            Token::Uint(value) => write!(f, "{}", value),
+            Token::Int(value) if value.bit(255) => write!(f, "-{}", value.overflowing_neg().0),
+            Token::Int(value) => write!(f, "{}", value),
+            Token::FixedBytes(bytes) | Token::Bytes(bytes) => write!(f, "0x{}", hex::encode(bytes)),
+            Token::String(value) => write!(f, "{:?}", value),
+            Token::Array(tokens) | Token::FixedArray(tokens) => {
+                write!(f, "[{}]", join(tokens, ", "))
+            }
+            Token::Tuple(tokens) => write!(f, "({})", join(tokens, ", ")),
+        }
+    }
+}
+
+/// A named parameter of a function, event or error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    pub kind: ParamType,
+    /// True for the event parameters stored in the topics.
+    pub indexed: bool,
+}
+
+impl Param {
+    /// Parses a human-readable parameter like `address indexed from` or `string memory name`.
+    pub fn parse(param: &str) -> Result<Param, AbiError> {
+        let param = param.trim();
+        // the type ends at the first space outside of a tuple.
+        let mut depth = 0;
+        let end = param
+            .char_indices()
+            .find(|(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0 && c.is_whitespace()
+            })
+            .map(|(index, _)| index)
+            .unwrap_or(param.len());
+        let mut parsed = Param {
+            name: String::new(),
+            kind: ParamType::parse(&param[..end])?,
+            indexed: false,
+        };
+        for word in param[end..].split_whitespace() {
+            match word {
+                "indexed" => parsed.indexed = true,
+                "memory" | "calldata" | "storage" | "payable" => {}
+                name => parsed.name = name.to_string(),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// A function of a contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<Param>,
+    pub outputs: Vec<Param>,
+}
+
+impl Function {
+    /// Parses a signature like `transfer(address,uint256)` or
+    /// `function balanceOf(address owner) view returns (uint256)`.
+    pub fn parse(signature: &str) -> Result<Function, AbiError> {
+        let parsed = Signature::parse(signature, "function")?;
+        Ok(Function {
+            name: parsed.name,
+            inputs: parsed.inputs,
+            outputs: parsed.outputs,
+        })
+    }
+
+    /// Returns the canonical signature, e.g. `transfer(address,uint256)`.
+    pub fn signature(&self) -> String {
+        signature(&self.name, &self.inputs)
+    }
+
+    pub fn selector(&self) -> [u8; 4] {
+        selector(&self.signature())
+    }
+
+    /// Encodes a call to the function, to be used as the tx data.
+    pub fn encode_input(&self, args: &[Token]) -> Result<Vec<u8>, AbiError> {
+        check_tokens(&self.inputs, args)?;
+        let mut data = self.selector().to_vec();
+        data.extend(encode_tokens(args));
+        Ok(data)
+    }
+
+    /// Decodes the arguments of a call to the function.
+    pub fn decode_input(&self, data: &[u8]) -> Result<Vec<Token>, AbiError> {
+        match data.split_at_checked(4) {
+            Some((selector, args)) if selector == self.selector() => {
+                decode_tokens(&types(&self.inputs), args)
+            }
+            _ => Err(AbiError::SelectorMismatch(self.signature())),
+        }
+    }
+
+    /// Decodes the data returned by the function.
+    pub fn decode_output(&self, data: &[u8]) -> Result<Vec<Token>, AbiError> {
+        decode_tokens(&types(&self.outputs), data)
+    }
+}
+
+/// An event emitted by a contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub name: String,
+    pub inputs: Vec<Param>,
+    /// True if the event signature is not stored as first topic.
+    pub anonymous: bool,
+}
+
+impl Event {
+    /// Parses a signature like `event Transfer(address indexed from, address indexed to, uint256)`.
+    pub fn parse(signature: &str) -> Result<Event, AbiError> {
+        let parsed = Signature::parse(signature, "event")?;
+        Ok(Event {
+            name: parsed.name,
+            inputs: parsed.inputs,
+            anonymous: parsed.anonymous,
+        })
+    }
+
+    /// Returns the canonical signature, e.g. `Transfer(address,address,uint256)`.
+    pub fn signature(&self) -> String {
+        signature(&self.name, &self.inputs)
+    }
+
+    /// Returns the first topic of the event, the hash of its signature.
+    pub fn topic(&self) -> U256 {
+        U256::from_big_endian(&sha3_hash(self.signature().as_bytes()))
+    }
+
+    /// Decodes the parameters of the event from the topics and the data of `log`.
+    /// Indexed parameters other than value types (strings, bytes, arrays and tuples) are only
+    /// stored as their hash, so they're decoded as `bytes32`.
+    pub fn decode_log(&self, log: &Log) -> Result<Vec<Token>, AbiError> {
+        let mut topics = log.topics.iter();
+        if !self.anonymous && topics.next() != Some(&self.topic()) {
+            return Err(AbiError::SelectorMismatch(self.signature()));
+        }
+        let data_inputs: Vec<ParamType> = self
+            .inputs
+            .iter()
+            .filter(|input| !input.indexed)
+            .map(|input| input.kind.clone())
+            .collect();
+        let mut data = decode_tokens(&data_inputs, &log.data)?.into_iter();
+
+        let mut tokens = vec![];
+        for input in &self.inputs {
+            if !input.indexed {
+                tokens.push(data.next().ok_or(AbiError::InvalidData)?);
+                continue;
+            }
+            let mut topic = [0u8; 32];
+            topics
+                .next()
+                .ok_or(AbiError::InvalidData)?
+                .to_big_endian(&mut topic);
+            tokens.push(match input.kind.is_value_type() {
+                true => decode_token(&input.kind, &topic, 0)?,
+                false => Token::FixedBytes(topic.to_vec()),
+            });
+        }
+        // e.g. the `Transfer` of ERC-721 has the signature of the ERC-20 one, but one more topic.
+        if topics.next().is_some() {
+            return Err(AbiError::InvalidData);
+        }
+        Ok(tokens)
+    }
+
+    /// Like `decode_log`, but names the parameters.
+    pub fn decode(&self, log: &Log) -> Result<DecodedLog, AbiError> {
+        let tokens = self.decode_log(log)?;
+        Ok(DecodedLog {
+            address: log.address,
+            name: self.name.clone(),
+            params: self
+                .inputs
+                .iter()
+                .map(|input| input.name.clone())
+                .zip(tokens)
+                .collect(),
+        })
+    }
+}
+
+/// A custom error that a contract can revert with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomError {
+    pub name: String,
+    pub inputs: Vec<Param>,
+}
+
+impl CustomError {
+    /// Parses a signature like `error InsufficientBalance(uint256 available, uint256 required)`.
+    pub fn parse(signature: &str) -> Result<CustomError, AbiError> {
+        let parsed = Signature::parse(signature, "error")?;
+        Ok(CustomError {
+            name: parsed.name,
+            inputs: parsed.inputs,
+        })
+    }
+
+    pub fn signature(&self) -> String {
+        signature(&self.name, &self.inputs)
+    }
+
+    pub fn selector(&self) -> [u8; 4] {
+        selector(&self.signature())
+    }
+
+    /// Decodes the arguments of the error from the revert data.
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<Token>, AbiError> {
+        match data.split_at_checked(4) {
+            Some((selector, args)) if selector == self.selector() => {
+                decode_tokens(&types(&self.inputs), args)
+            }
+            _ => Err(AbiError::SelectorMismatch(self.signature())),
+        }
+    }
+}
+
+/// The interface of a contract.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Abi {
+    pub functions: Vec<Function>,
+    pub events: Vec<Event>,
+    pub errors: Vec<CustomError>,
+}
+
+impl Abi {
+    /// Parses a JSON ABI, as produced by solc.
+    pub fn from_json(json: &str) -> Result<Abi, AbiError> {
+        let items: Vec<JsonItem> =
+            serde_json::from_str(json).map_err(|error| AbiError::InvalidJson(error.to_string()))?;
+        let mut abi = Abi::default();
+        for item in items {
+            let inputs = params(&item.inputs)?;
+            match item.kind.as_str() {
+                "function" => abi.functions.push(Function {
+                    name: item.name,
+                    inputs,
+                    outputs: params(&item.outputs)?,
+                }),
+                "event" => abi.events.push(Event {
+                    name: item.name,
+                    inputs,
+                    anonymous: item.anonymous,
+                }),
+                "error" => abi.errors.push(CustomError {
+                    name: item.name,
+                    inputs,
+                }),
+                // constructor, fallback and receive have no selector.
+                _ => {}
+            }
+        }
+        Ok(abi)
+    }
+
+    /// Parses human-readable signatures, one item per line, like `function name() returns (string)`,
+    /// `event Approval(address indexed, address indexed, uint256)` or `error Unauthorized()`.
+    pub fn parse(signatures: &str) -> Result<Abi, AbiError> {
+        let mut abi = Abi::default();
+        for line in signatures
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            match line.split_whitespace().next() {
+                Some("event") => abi.events.push(Event::parse(line)?),
+                Some("error") => abi.errors.push(CustomError::parse(line)?),
+                _ => abi.functions.push(Function::parse(line)?),
+            }
+        }
+        Ok(abi)
+    }
+
+    /// Returns the first function named `name`.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    /// Returns the first event named `name`.
+    pub fn event(&self, name: &str) -> Option<&Event> {
+        self.events.iter().find(|event| event.name == name)
+    }
+
+    /// Returns the first error named `name`.
+    pub fn error(&self, name: &str) -> Option<&CustomError> {
+        self.errors.iter().find(|error| error.name == name)
+    }
+
+    /// Returns the function called by `data`, the tx data.
+    pub fn function_by_selector(&self, data: &[u8]) -> Option<&Function> {
+        let selector = data.get(..4)?;
+        self.functions
+            .iter()
+            .find(|function| function.selector() == selector)
+    }
+
+    /// Returns the event that emitted `log`, matching its first topic with the event signatures.
+    /// Anonymous events have no signature topic, so the first one whose parameters fit the log
+    /// is returned.
+    pub fn event_of(&self, log: &Log) -> Option<&Event> {
+        let topic = log.topics.first();
+        self.events
+            .iter()
+            .find(|event| !event.anonymous && Some(&event.topic()) == topic)
+            .or_else(|| {
+                self.events
+                    .iter()
+                    .filter(|event| event.anonymous)
+                    .find(|event| event.decode_log(log).is_ok())
+            })
+    }
+
+    /// Decodes `log` with the event that emitted it.
+    pub fn decode_log(&self, log: &Log) -> Option<DecodedLog> {
+        self.event_of(log)?.decode(log).ok()
+    }
+
+    /// Decodes the logs emitted by the events of the ABI, skipping the others.
+    pub fn decode_logs(&self, logs: &[Log]) -> Vec<DecodedLog> {
+        logs.iter().filter_map(|log| self.decode_log(log)).collect()
+    }
+}
+
+/// A log decoded with the event that emitted it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedLog {
+    pub address: U256,
+    /// The name of the event.
+    pub name: String,
+    /// The parameters of the event with their names, in declaration order.
+    pub params: Vec<(String, Token)>,
+}
+
+impl DecodedLog {
+    /// Returns the parameter named `name`.
+    pub fn param(&self, name: &str) -> Option<&Token> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, token)| token)
+    }
+}
+
+impl fmt::Display for DecodedLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(name, token)| match name.is_empty() {
+                true => token.to_string(),
+                false => format!("{}: {}", name, token),
+            })
+            .collect();
+        write!(f, "{}({})", self.name, params.join(", "))
+    }
+}
+
+/// Encodes `tokens` like the arguments of a function, without the selector.
+pub fn encode_tokens(tokens: &[Token]) -> Vec<u8> {
+    let head_size: usize = tokens
+        .iter()
+        .map(|token| match token.is_dynamic() {
+            true => 32,
+            false => static_size(token),
+        })
+        .sum();
+    let mut head = vec![];
+    let mut tail = vec![];
+    for token in tokens {
+        if token.is_dynamic() {
+            head.extend(word(U256::from(head_size + tail.len())));
+            tail.extend(encode_token(token));
+        } else {
+            head.extend(encode_token(token));
+        }
+    }
+    head.extend(tail);
+    head
+}
+
+/// Decodes values of `types` encoded like the arguments of a function.
+pub fn decode_tokens(types: &[ParamType], data: &[u8]) -> Result<Vec<Token>, AbiError> {
+    decode_sequence(types, data, 0)
+}
+
+/// The parts of a human-readable signature.
+struct Signature {
+    name: String,
+    inputs: Vec<Param>,
+    outputs: Vec<Param>,
+    anonymous: bool,
+}
+
+impl Signature {
+    /// Parses `[kind] name(inputs) [modifiers] [returns (outputs)] [anonymous]`.
+    fn parse(signature: &str, kind: &str) -> Result<Signature, AbiError> {
+        let invalid = || AbiError::InvalidSignature(signature.to_string());
+        let text = signature.trim();
+        let text = text.strip_prefix(kind).map(str::trim_start).unwrap_or(text);
+        let open = text.find('(').ok_or_else(invalid)?;
+        let name = text[..open].trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+        let close = matching_paren(text, open).ok_or_else(invalid)?;
+        let inputs = parse_params(&text[open + 1..close])?;
+
+        let rest = text[close + 1..].trim();
+        let mut outputs = vec![];
+        if let Some(returns) = rest.find("returns") {
+            let list = rest[returns + "returns".len()..].trim();
+            let close = list
+                .starts_with('(')
+                .then(|| matching_paren(list, 0))
+                .flatten()
+                .ok_or_else(invalid)?;
+            outputs = parse_params(&list[1..close])?;
+        }
+        Ok(Signature {
+            name: name.to_string(),
+            inputs,
+            outputs,
+            anonymous: rest.split_whitespace().any(|word| word == "anonymous"),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonItem {
+    #[serde(rename = "type", default = "function_kind")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    inputs: Vec<JsonParam>,
+    #[serde(default)]
+    outputs: Vec<JsonParam>,
+    #[serde(default)]
+    anonymous: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonParam {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    components: Vec<JsonParam>,
+    #[serde(default)]
+    indexed: bool,
+}
+
+impl JsonParam {
+    /// Returns the type, with the components of tuples spelled out.
+    fn canonical_kind(&self) -> String {
+        match self.kind.strip_prefix("tuple") {
+            Some(suffix) => {
+                let components: Vec<String> = self
+                    .components
+                    .iter()
+                    .map(JsonParam::canonical_kind)
+                    .collect();
+                format!("({}){}", components.join(","), suffix)
+            }
+            None => self.kind.clone(),
+        }
+    }
+}
+
+fn function_kind() -> String {
+    "function".to_string()
+}
+
+fn params(params: &[JsonParam]) -> Result<Vec<Param>, AbiError> {
+    params
+        .iter()
+        .map(|param| {
+            Ok(Param {
+                name: param.name.clone(),
+                kind: ParamType::parse(&param.canonical_kind())?,
+                indexed: param.indexed,
+            })
+        })
+        .collect()
+}
+
+fn parse_params(list: &str) -> Result<Vec<Param>, AbiError> {
+    split_list(list).into_iter().map(Param::parse).collect()
+}
+
+/// Splits a comma separated list, ignoring the commas inside parentheses.
+fn split_list(list: &str) -> Vec<&str> {
+    if list.trim().is_empty() {
+        return vec![];
+    }
+    let mut items = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in list.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&list[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&list[start..]);
+    items
+}
+
+/// Returns the index of the parenthesis closing the one at `open`.
+fn matching_paren(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in text[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn signature(name: &str, inputs: &[Param]) -> String {
+    format!("{}({})", name, join(&types(inputs), ","))
+}
+
+fn selector(signature: &str) -> [u8; 4] {
+    let hash = sha3_hash(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn types(params: &[Param]) -> Vec<ParamType> {
+    params.iter().map(|param| param.kind.clone()).collect()
+}
+
+fn join<T: fmt::Display>(items: &[T], separator: &str) -> String {
+    items
+        .iter()
+        .map(T::to_string)
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+fn check_tokens(params: &[Param], tokens: &[Token]) -> Result<(), AbiError> {
+    if params.len() != tokens.len() {
+        return Err(AbiError::WrongValueCount {
+            expected: params.len(),
+            actual: tokens.len(),
+        });
+    }
+    for (index, (param, token)) in params.iter().zip(tokens).enumerate() {
+        if !token.matches(&param.kind) {
+            return Err(AbiError::TypeMismatch {
+                index,
+                kind: param.kind.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn word(value: U256) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    value.to_big_endian(&mut word);
+    word
+}
+
+/// Returns `bytes` right padded to a multiple of 32 bytes.
+fn padded(bytes: &[u8]) -> Vec<u8> {
+    let mut padded = bytes.to_vec();
+    padded.resize(bytes.len().div_ceil(32) * 32, 0);
+    padded
+}
+
+/// Returns the size of the encoding of a static token.
+fn static_size(token: &Token) -> usize {
+    match token {
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => tokens.iter().map(static_size).sum(),
+        _ => 32,
+    }
+}
+
+fn encode_token(token: &Token) -> Vec<u8> {
+    match token {
+        Token::Address(value) | Token::Uint(value) | Token::Int(value) => word(*value).to_vec(),
+        Token::Bool(value) => word(U256::from(*value as u8)).to_vec(),
+        Token::FixedBytes(bytes) => padded(bytes),
+        Token::Bytes(bytes) => [word(U256::from(bytes.len())).to_vec(), padded(bytes)].concat(),
+        Token::String(value) => {
+            let bytes = value.as_bytes();
+            [word(U256::from(bytes.len())).to_vec(), padded(bytes)].concat()
+        }
+        Token::Array(tokens) => [
+            word(U256::from(tokens.len())).to_vec(),
+            encode_tokens(tokens),
+        ]
+        .concat(),
+        Token::FixedArray(tokens) | Token::Tuple(tokens) => encode_tokens(tokens),
+    }
+}
+
+/// Reads the word at `offset` as an offset or a length, which can't be beyond the data.
+fn read_size(data: &[u8], offset: usize) -> Result<usize, AbiError> {
+    let value = read_word(data, offset)?;
+    if value > U256::from(data.len()) {
+        return Err(AbiError::InvalidData);
+    }
+    Ok(value.as_usize())
+}
+
+fn read_word(data: &[u8], offset: usize) -> Result<U256, AbiError> {
+    read_bytes(data, offset, 32).map(U256::from_big_endian)
+}
+
+fn read_bytes(data: &[u8], offset: usize, size: usize) -> Result<&[u8], AbiError> {
+    offset
+        .checked_add(size)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(AbiError::InvalidData)
+}
+
+/// Decodes a sequence of values whose heads start at `base`, the position the offsets of
+/// the dynamic values are relative to.
+fn decode_sequence(types: &[ParamType], data: &[u8], base: usize) -> Result<Vec<Token>, AbiError> {
+    let mut offset = base;
+    let mut tokens = vec![];
+    for kind in types {
+        let position = match kind.is_dynamic() {
+            true => base
+                .checked_add(read_size(data, offset)?)
+                .ok_or(AbiError::InvalidData)?,
+            false => offset,
+        };
+        tokens.push(decode_token(kind, data, position)?);
+        offset += kind.head_size();
+    }
+    Ok(tokens)
+}
+
+fn decode_token(kind: &ParamType, data: &[u8], offset: usize) -> Result<Token, AbiError> {
+    match kind {
+        ParamType::Address => read_word(data, offset).map(Token::Address),
+        ParamType::Bool => read_word(data, offset).map(|value| Token::Bool(!value.is_zero())),
+        ParamType::Uint(_) => read_word(data, offset).map(Token::Uint),
+        ParamType::Int(_) => read_word(data, offset).map(Token::Int),
+        ParamType::FixedBytes(size) => {
+            read_bytes(data, offset, *size).map(|bytes| Token::FixedBytes(bytes.to_vec()))
+        }
+        ParamType::Bytes | ParamType::String => {
+            let size = read_size(data, offset)?;
+            let bytes = read_bytes(data, offset + 32, size)?;
+            Ok(match kind {
+                ParamType::Bytes => Token::Bytes(bytes.to_vec()),
+                _ => Token::String(String::from_utf8_lossy(bytes).into_owned()),
+            })
+        }
+        ParamType::Array(inner) => {
+            // every element takes at least a word, which bounds the length.
+            let size = read_size(data, offset)?;
+            if size.saturating_mul(32) > data.len() {
+                return Err(AbiError::InvalidData);
+            }
+            let types = vec![(**inner).clone(); size];
+            decode_sequence(&types, data, offset + 32).map(Token::Array)
+        }
+        ParamType::FixedArray(inner, size) => {
+            if size.saturating_mul(32) > data.len() {
+                return Err(AbiError::InvalidData);
+            }
+            let types = vec![(**inner).clone(); *size];
+            decode_sequence(&types, data, offset).map(Token::FixedArray)
+        }
+        ParamType::Tuple(types) => decode_sequence(types, data, offset).map(Token::Tuple),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<u8> {
+        words
+            .iter()
+            .flat_map(|word| hex::decode(format!("{:0>64}", word)).unwrap())
+            .collect()
+    }
+
+    /// Right pads `text` to a word, as `bytesN`, `bytes` and `string` are encoded.
+    fn padded(text: &str) -> String {
+        format!("{:0<64}", hex::encode(text))
+    }
+
+    fn uint(value: u64) -> Token {
+        Token::Uint(value.into())
+    }
+
+    /// Checks that `args` encode to `expected` (selector included) and decode back.
+    fn round_trip(signature: &str, args: Vec<Token>, expected: Vec<u8>) {
+        let function = Function::parse(signature).unwrap();
+        let data = function.encode_input(&args).unwrap();
+        assert_eq!(hex::encode(&data), hex::encode(&expected));
+        assert_eq!(function.decode_input(&data).unwrap(), args);
+    }
+
+    // The examples of the Solidity ABI specification.
+
+    #[test]
+    fn static_values() {
+        let expected = [hex::decode("cdcd77c0").unwrap(), words(&["45", "1"])].concat();
+        round_trip(
+            "baz(uint32,bool)",
+            vec![uint(69), Token::Bool(true)],
+            expected,
+        );
+    }
+
+    #[test]
+    fn fixed_array_of_fixed_bytes() {
+        let expected = [
+            hex::decode("fce353f6").unwrap(),
+            words(&[&padded("abc"), &padded("def")]),
+        ]
+        .concat();
+        let args = vec![Token::FixedArray(vec![
+            Token::FixedBytes(b"abc".to_vec()),
+            Token::FixedBytes(b"def".to_vec()),
+        ])];
+        round_trip("bar(bytes3[2])", args, expected);
+    }
+
+    #[test]
+    fn dynamic_bytes_and_array() {
+        let expected = [
+            hex::decode("a5643bf2").unwrap(),
+            words(&["60", "1", "a0", "4", &padded("dave"), "3", "1", "2", "3"]),
+        ]
+        .concat();
+        let args = vec![
+            Token::Bytes(b"dave".to_vec()),
+            Token::Bool(true),
+            Token::Array(vec![uint(1), uint(2), uint(3)]),
+        ];
+        round_trip("sam(bytes,bool,uint256[])", args, expected);
+    }
+
+    #[test]
+    fn mixed_static_and_dynamic() {
+        let expected = [
+            hex::decode("8be65246").unwrap(),
+            words(&[
+                "123",
+                "80",
+                &padded("1234567890"),
+                "e0",
+                "2",
+                "456",
+                "789",
+                "d",
+                &padded("Hello, world!"),
+            ]),
+        ]
+        .concat();
+        let args = vec![
+            uint(0x123),
+            Token::Array(vec![uint(0x456), uint(0x789)]),
+            Token::FixedBytes(b"1234567890".to_vec()),
+            Token::Bytes(b"Hello, world!".to_vec()),
+        ];
+        round_trip("f(uint256,uint32[],bytes10,bytes)", args, expected);
+    }
+
+    #[test]
+    fn nested_dynamic_arrays() {
+        let expected = [
+            hex::decode("2289b18c").unwrap(),
+            words(&[
+                "40",
+                "140",
+                "2",
+                "40",
+                "a0",
+                "2",
+                "1",
+                "2",
+                "1",
+                "3",
+                "3",
+                "60",
+                "a0",
+                "e0",
+                "3",
+                &padded("one"),
+                "3",
+                &padded("two"),
+                "5",
+                &padded("three"),
+            ]),
+        ]
+        .concat();
+        let args = vec![
+            Token::Array(vec![
+                Token::Array(vec![uint(1), uint(2)]),
+                Token::Array(vec![uint(3)]),
+            ]),
+            Token::Array(vec![
+                Token::String("one".to_string()),
+                Token::String("two".to_string()),
+                Token::String("three".to_string()),
+            ]),
+        ];
+        round_trip("g(uint256[][],string[])", args, expected);
+    }
+
+    #[test]
+    fn dynamic_tuple() {
+        // the tuple is dynamic, so it's encoded in the tail with its own offsets.
+        let function = Function::parse("f((uint256,bytes),int8)").unwrap();
+        let expected = [
+            function.selector().to_vec(),
+            words(&["40", &"f".repeat(64), "1", "40", "2", &padded("ab")]),
+        ]
+        .concat();
+        let args = vec![
+            Token::Tuple(vec![uint(1), Token::Bytes(b"ab".to_vec())]),
+            Token::Int(U256::MAX),
+        ];
+        round_trip("f((uint256,bytes),int8)", args, expected);
+    }
+
+    #[test]
+    fn integers_must_fit_their_width() {
+        let function = Function::parse("f(uint8,int8)").unwrap();
+        let minus = |value: u64| Token::Int(U256::from(value).overflowing_neg().0);
+        for (args, valid) in [
+            (vec![uint(255), Token::Int(127.into())], true),
+            (vec![uint(0), minus(128)], true),
+            (vec![uint(0), minus(1)], true),
+            (vec![uint(256), Token::Int(0.into())], false),
+            (vec![uint(0), Token::Int(128.into())], false),
+            (vec![uint(0), Token::Int(1000.into())], false),
+            (vec![uint(0), minus(129)], false),
+        ] {
+            let result = function.encode_input(&args);
+            if valid {
+                assert!(result.is_ok(), "{:?}", args);
+            } else {
+                assert!(
+                    matches!(result, Err(AbiError::TypeMismatch { .. })),
+                    "{:?}",
+                    args
+                );
+            }
+        }
+        assert!(Token::Int(U256::MAX).matches(&ParamType::Int(8)));
+        assert!(Token::Int(U256::MAX >> 1).matches(&ParamType::Int(256)));
+    }
+
+    #[test]
+    fn decode_output() {
+        let function = Function::parse("function name() view returns (string)").unwrap();
+        let data = words(&["20", "4", &padded("Test")]);
+        assert_eq!(
+            function.decode_output(&data).unwrap(),
+            vec![Token::String("Test".to_string())]
+        );
+        assert_eq!(
+            function.decode_output(&data[..64]),
+            Err(AbiError::InvalidData)
+        );
+    }
+
+    #[test]
+    fn indexed_event_topics() {
+        let event =
+            Event::parse("event Transfer(address indexed from, address indexed to, uint256 value)")
+                .unwrap();
+        let topic = "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+        assert_eq!(event.topic(), U256::from_str_radix(topic, 16).unwrap());
+
+        let log = Log::new(
+            U256::from(0xcafe),
+            words(&["3e8"]),
+            vec![event.topic(), U256::from(0xaa), U256::from(0xbb)],
+        );
+        assert_eq!(
+            event.decode_log(&log).unwrap(),
+            vec![
+                Token::Address(0xaa.into()),
+                Token::Address(0xbb.into()),
+                uint(1000)
+            ]
+        );
+
+        // indexed dynamic values are only stored as their hash.
+        let event = Event::parse("event Named(string indexed name, bytes data)").unwrap();
+        let hash = sha3_hash(b"alice");
+        let log = Log::new(
+            U256::zero(),
+            words(&["20", "1", &format!("{:0<64}", "ff")]),
+            vec![event.topic(), U256::from_big_endian(&hash)],
+        );
+        assert_eq!(
+            event.decode_log(&log).unwrap(),
+            vec![Token::FixedBytes(hash.to_vec()), Token::Bytes(vec![0xff])]
+        );
+
+        // so are indexed static arrays and tuples.
+        let event =
+            Event::parse("event Pair(uint256[2] indexed pair, (bool,address) indexed t)").unwrap();
+        let pair = sha3_hash(&words(&["1", "2"]));
+        let tuple = sha3_hash(&words(&["1", "aa"]));
+        let log = Log::new(
+            U256::zero(),
+            vec![],
+            vec![
+                event.topic(),
+                U256::from_big_endian(&pair),
+                U256::from_big_endian(&tuple),
+            ],
+        );
+        assert_eq!(
+            event.decode_log(&log).unwrap(),
+            vec![
+                Token::FixedBytes(pair.to_vec()),
+                Token::FixedBytes(tuple.to_vec())
+            ]
+        );
+
+        // a missing topic.
+        let event = Event::parse("event Named(string indexed name, bytes data)").unwrap();
+        let log = Log::new(U256::zero(), vec![], vec![event.topic()]);
+        assert_eq!(event.decode_log(&log), Err(AbiError::InvalidData));
+    }
+
+    #[test]
+    fn logs_are_matched_with_their_event() {
+        let abi = Abi::parse(
+            "
+            event Transfer(address indexed from, address indexed to, uint256 value)
+            event Moved(uint256 indexed id, uint256 amount) anonymous
+            ",
+        )
+        .unwrap();
+        let transfer = abi.event("Transfer").unwrap();
+        let log = Log::new(
+            U256::zero(),
+            words(&["1"]),
+            vec![transfer.topic(), U256::from(0xaa), U256::from(0xbb)],
+        );
+        assert_eq!(abi.event_of(&log), Some(transfer));
+
+        // anonymous events have no signature topic: any log that decodes with them matches.
+        let log = Log::new(U256::zero(), words(&["1"]), vec![U256::from(7)]);
+        assert_eq!(
+            abi.event_of(&log).map(|event| &event.name[..]),
+            Some("Moved")
+        );
+        assert_eq!(abi.decode_log(&log).unwrap().param("id"), Some(&uint(7)));
+
+        // neither the signature of `Transfer` nor the topics of `Moved`.
+        let log = Log::new(
+            U256::zero(),
+            words(&["1"]),
+            vec![U256::from(7), U256::one()],
+        );
+        assert_eq!(abi.event_of(&log), None);
+        let log = Log::new(U256::zero(), vec![], vec![U256::from(7)]);
+        assert_eq!(abi.event_of(&log), None);
+    }
+}
@@ -0,0 +1,71 @@
+use std::{fmt, str::FromStr};
+
+use primitive_types::U256;
+
+/// The 20 bytes address of an account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address(pub [u8; 20]);
+
+impl Address {
+    pub const ZERO: Address = Address([0; 20]);
+}
+
+impl From<[u8; 20]> for Address {
+    fn from(bytes: [u8; 20]) -> Address {
+        Address(bytes)
+    }
+}
+
+/// Keeps the lowest 20 bytes.
+impl From<U256> for Address {
+    fn from(value: U256) -> Address {
+        let mut bytes = [0u8; 32];
+        value.to_big_endian(&mut bytes);
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&bytes[12..]);
+        Address(address)
+    }
+}
+
+impl From<Address> for U256 {
+    fn from(address: Address) -> U256 {
+        U256::from_big_endian(&address.0)
+    }
+}
+
+/// Parses 40 hex digits, with or without the `0x` (or `0X`) prefix.
+impl FromStr for Address {
+    type Err = hex::FromHexError;
+
+    fn from_str(text: &str) -> Result<Address, hex::FromHexError> {
+        let mut address = [0u8; 20];
+        let digits = text
+            .strip_prefix("0x")
+            .or_else(|| text.strip_prefix("0X"))
+            .unwrap_or(text);
+        hex::decode_to_slice(digits, &mut address)?;
+        Ok(Address(address))
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Address;
+
+    #[test]
+    fn parses_both_hex_prefixes() {
+        let text = "00000000000000000000000000000000000000AB";
+        let address = Address::from(primitive_types::U256::from(0xab));
+        assert_eq!(text.parse(), Ok(address));
+        assert_eq!(format!("0x{}", text).parse(), Ok(address));
+        assert_eq!(format!("0X{}", text).parse(), Ok(address));
+        assert!("0xab".parse::<Address>().is_err());
+        assert!(format!("0x0{}", text).parse::<Address>().is_err());
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use primitive_types::U256;
+
+use crate::{errors::AssemblerError, opcode::OpCode};
+
+/// Mnemonics accepted besides the ones printed by the disassembler.
+const ALIASES: [(&str, u8); 3] = [("SHA3", 0x20), ("PREVRANDAO", 0x44), ("INVALID", 0xfe)];
+
+const PUSH0: u8 = 0x5f;
+const PUSH1: u8 = 0x60;
+
+enum Operand {
+    Value(U256),
+    /// `@label`, the position of a label.
+    Label(String),
+}
+
+enum Statement {
+    /// `label:`, marks a position in the code without emitting any byte.
+    Label(String),
+    Opcode(u8),
+    /// `PUSHn operand`, or `PUSH operand` (`size` is `None`) to use the smallest `PUSH`.
+    Push {
+        size: Option<usize>,
+        operand: Operand,
+    },
+}
+
+/// Assembles the asm syntax of `evm.json`: one mnemonic per line with the `PUSH` operand in
+/// decimal or hex. On top of that, `name:` defines a label, `@name` pushes its position, `PUSH`
+/// without size uses the smallest `PUSH` for its operand, and comments start with `//` or `;`.
+/// Labels don't emit a `JUMPDEST`, it has to be written explicitly.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    let statements = parse(source)?;
+
+    // the size of a `PUSH @label` depends on the position of the label, which depends on the
+    // size of the previous pushes: grow the pushes until every label fits.
+    let mut sizes: Vec<usize> = statements
+        .iter()
+        .map(|(_, statement)| match statement {
+            Statement::Label(_) => 0,
+            Statement::Opcode(_) => 1,
+            Statement::Push {
+                size: Some(size), ..
+            } => 1 + size,
+            Statement::Push {
+                size: None,
+                operand: Operand::Value(value),
+            } => 1 + byte_size(*value),
+            Statement::Push {
+                size: None,
+                operand: Operand::Label(_),
+            } => 1,
+        })
+        .collect();
+    let labels = loop {
+        let labels = label_positions(&statements, &sizes)?;
+        let mut changed = false;
+        for (index, (line, statement)) in statements.iter().enumerate() {
+            if let Statement::Push {
+                size: None,
+                operand: Operand::Label(label),
+            } = statement
+            {
+                let size = 1 + byte_size(resolve(&labels, label, *line)?);
+                if size > sizes[index] {
+                    sizes[index] = size;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break labels;
+        }
+    };
+
+    let mut code = vec![];
+    for ((line, statement), size) in statements.iter().zip(sizes) {
+        match statement {
+            Statement::Label(_) => {}
+            Statement::Opcode(opcode) => code.push(*opcode),
+            Statement::Push { operand, .. } => {
+                let (value, text) = match operand {
+                    Operand::Value(value) => (*value, value.to_string()),
+                    Operand::Label(label) => {
+                        (resolve(&labels, label, *line)?, format!("@{}", label))
+                    }
+                };
+                let data_size = size - 1;
+                if byte_size(value) > data_size {
+                    return Err(AssemblerError::OperandTooLarge {
+                        line: *line,
+                        operand: text,
+                        size: data_size,
+                    });
+                }
+                let mut bytes = [0u8; 32];
+                value.to_big_endian(&mut bytes);
+                code.push(push_opcode(data_size));
+                code.extend_from_slice(&bytes[32 - data_size..]);
+            }
+        }
+    }
+    Ok(code)
+}
+
+fn parse(source: &str) -> Result<Vec<(usize, Statement)>, AssemblerError> {
+    let mnemonics = mnemonics();
+    let mut statements = vec![];
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split("//").next().unwrap_or_default();
+        let line = line.split(';').next().unwrap_or_default();
+
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            if let Some(label) = token.strip_suffix(':') {
+                statements.push((line_number, Statement::Label(label.to_string())));
+                continue;
+            }
+
+            let mnemonic = token.to_uppercase();
+            let size = match mnemonic.strip_prefix("PUSH") {
+                Some("") => Some(None),
+                Some(size) => match size.parse::<usize>() {
+                    Ok(size @ 1..=32) => Some(Some(size)),
+                    _ => None,
+                },
+                None => None,
+            };
+            let Some(size) = size else {
+                let opcode = mnemonics.get(mnemonic.as_str()).copied().ok_or_else(|| {
+                    AssemblerError::UnknownMnemonic {
+                        line: line_number,
+                        mnemonic: token.to_string(),
+                    }
+                })?;
+                statements.push((line_number, Statement::Opcode(opcode)));
+                continue;
+            };
+
+            let operand = tokens
+                .next()
+                .ok_or_else(|| AssemblerError::MissingOperand {
+                    line: line_number,
+                    mnemonic: token.to_string(),
+                })?;
+            let operand = parse_operand(operand).ok_or_else(|| AssemblerError::InvalidOperand {
+                line: line_number,
+                operand: operand.to_string(),
+            })?;
+            statements.push((line_number, Statement::Push { size, operand }));
+        }
+    }
+    Ok(statements)
+}
+
+fn parse_operand(operand: &str) -> Option<Operand> {
+    if let Some(label) = operand.strip_prefix('@') {
+        return (!label.is_empty()).then(|| Operand::Label(label.to_string()));
+    }
+    let value = match operand
+        .strip_prefix("0x")
+        .or_else(|| operand.strip_prefix("0X"))
+    {
+        Some(hex) => U256::from_str_radix(hex, 16).ok()?,
+        None => U256::from_dec_str(operand).ok()?,
+    };
+    Some(Operand::Value(value))
+}
+
+/// Maps every mnemonic to its opcode.
+fn mnemonics() -> HashMap<String, u8> {
+    let mut mnemonics: HashMap<String, u8> = (0..=u8::MAX)
+        .filter_map(|byte| OpCode::new(byte).map(|opcode| (opcode.name(), byte)))
+        .collect();
+    for (alias, opcode) in ALIASES {
+        mnemonics.insert(alias.to_string(), opcode);
+    }
+    mnemonics
+}
+
+fn label_positions(
+    statements: &[(usize, Statement)],
+    sizes: &[usize],
+) -> Result<HashMap<String, usize>, AssemblerError> {
+    let mut labels = HashMap::new();
+    let mut position = 0;
+    for ((line, statement), size) in statements.iter().zip(sizes) {
+        if let Statement::Label(label) = statement {
+            if labels.insert(label.clone(), position).is_some() {
+                return Err(AssemblerError::DuplicateLabel {
+                    line: *line,
+                    label: label.clone(),
+                });
+            }
+        }
+        position += size;
+    }
+    Ok(labels)
+}
+
+fn resolve(
+    labels: &HashMap<String, usize>,
+    label: &str,
+    line: usize,
+) -> Result<U256, AssemblerError> {
+    labels
+        .get(label)
+        .map(|position| U256::from(*position))
+        .ok_or_else(|| AssemblerError::UnknownLabel {
+            line,
+            label: label.to_string(),
+        })
+}
+
+/// Returns the number of bytes needed to push `value`, 0 for `PUSH0`.
+fn byte_size(value: U256) -> usize {
+    value.bits().div_ceil(8)
+}
+
+fn push_opcode(data_size: usize) -> u8 {
+    match data_size {
+        0 => PUSH0,
+        size => PUSH1 + size as u8 - 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::instructions;
+
+    #[test]
+    fn labels_resolve_forwards_and_backwards() {
+        let source = "
+            start:
+            JUMPDEST
+            PUSH @end   // forward
+            JUMPI
+            PUSH @start ; backward
+            JUMP
+            end: JUMPDEST STOP
+        ";
+        assert_eq!(
+            assemble(source).unwrap(),
+            hex::decode("5b6006575f565b00").unwrap()
+        );
+    }
+
+    #[test]
+    fn push_operand_sizing() {
+        assert_eq!(assemble("PUSH 0").unwrap(), vec![0x5f]);
+        assert_eq!(assemble("PUSH 255").unwrap(), vec![0x60, 0xff]);
+        assert_eq!(assemble("PUSH 0x0100").unwrap(), vec![0x61, 0x01, 0x00]);
+        assert_eq!(assemble("PUSH4 1").unwrap(), vec![0x63, 0, 0, 0, 1]);
+        assert_eq!(
+            assemble("push32 0").unwrap(),
+            [vec![0x7f], vec![0; 32]].concat()
+        );
+    }
+
+    #[test]
+    fn label_pushes_grow_until_labels_fit() {
+        // 300 bytes of code before the label: its position needs a PUSH2.
+        let source = format!("PUSH @end\n{}end: JUMPDEST", "STOP\n".repeat(300));
+        let code = assemble(&source).unwrap();
+        assert_eq!(&code[..3], &[0x61, 0x01, 0x2f]);
+        assert_eq!(code[0x12f], 0x5b);
+    }
+
+    #[test]
+    fn assemble_disassemble_round_trip() {
+        let source = "
+            PUSH1 0x80 PUSH1 0x40 MSTORE
+            CALLVALUE DUP1 ISZERO PUSH @ok JUMPI
+            PUSH0 DUP1 REVERT
+            ok: JUMPDEST POP
+            PUSH32 0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff
+            SHA3 INVALID
+        ";
+        let code = assemble(source).unwrap();
+        let listing: Vec<String> = instructions(&code)
+            .iter()
+            .map(|instruction| match instruction.immediate.is_empty() {
+                true => instruction.name.clone(),
+                false => format!(
+                    "{} 0x{}",
+                    instruction.name,
+                    hex::encode(&instruction.immediate)
+                ),
+            })
+            .collect();
+        assert_eq!(assemble(&listing.join("\n")).unwrap(), code);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source: &str| assemble(source).unwrap_err();
+        assert_eq!(
+            error("STOP\nFOO"),
+            AssemblerError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "FOO".to_string()
+            }
+        );
+        assert_eq!(
+            error("PUSH1"),
+            AssemblerError::MissingOperand {
+                line: 1,
+                mnemonic: "PUSH1".to_string()
+            }
+        );
+        assert_eq!(
+            error("PUSH1 0xzz"),
+            AssemblerError::InvalidOperand {
+                line: 1,
+                operand: "0xzz".to_string()
+            }
+        );
+        assert_eq!(
+            error("PUSH1 256"),
+            AssemblerError::OperandTooLarge {
+                line: 1,
+                operand: "256".to_string(),
+                size: 1
+            }
+        );
+        assert_eq!(
+            error("PUSH @nowhere"),
+            AssemblerError::UnknownLabel {
+                line: 1,
+                label: "nowhere".to_string()
+            }
+        );
+        assert_eq!(
+            error("a:\na:"),
+            AssemblerError::DuplicateLabel {
+                line: 2,
+                label: "a".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,640 @@
+//! An interactive debugger stepping through the execution of some bytecode.
+//!
+//! Usage: `evm-debug <bytecode> [--tx <file>] [--block <file>] [--state <file>]`
+//!
+//! The bytecode is hex encoded, inline or in a file. The tx, block and state files use the same
+//! JSON format as `evm.json`. Type `help` at the prompt for the list of commands.
+//!
+//! Without a `gas` in the tx file, the gas limit is the block gas limit of the dev node, so that
+//! an infinite loop runs out of gas. Only the first `MAX_STEPS` steps are recorded.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+use evm::{evm_with_inspector, EvmResult, Inspector, TraceStep, DEFAULT_BLOCK_GAS_LIMIT};
+use primitive_types::U256;
+use serde::Deserialize;
+
+/// The number of steps that can be debugged, the execution goes on without recording the rest.
+const MAX_STEPS: usize = 1_000_000;
+
+#[derive(Debug, Deserialize, Default)]
+struct TxDataRaw {
+    to: Option<String>,
+    from: Option<String>,
+    origin: Option<String>,
+    gasprice: Option<String>,
+    value: Option<String>,
+    data: Option<String>,
+    gas: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct BlockDataRaw {
+    basefee: Option<String>,
+    coinbase: Option<String>,
+    timestamp: Option<String>,
+    number: Option<String>,
+    difficulty: Option<String>,
+    gaslimit: Option<String>,
+    chainid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddressData {
+    nonce: Option<String>,
+    balance: Option<String>,
+    code: Option<Code>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Code {
+    bin: String,
+}
+
+/// Everything needed to execute the program from the start.
+#[derive(Clone)]
+struct Program {
+    code: Vec<u8>,
+    tx: Vec<Vec<u8>>,
+    block: Vec<Vec<u8>>,
+    state: HashMap<Vec<u8>, (usize, Vec<u8>, Vec<u8>)>,
+}
+
+impl Program {
+    fn run(&self, inspector: Rc<RefCell<dyn Inspector>>) -> EvmResult {
+        evm_with_inspector(
+            &self.code,
+            self.tx.clone(),
+            self.block.clone(),
+            self.state.clone(),
+            inspector,
+        )
+    }
+}
+
+/// An executed opcode.
+struct Step {
+    pc: usize,
+    op_name: String,
+    depth: usize,
+    gas: u64,
+}
+
+/// Records the first `MAX_STEPS` opcodes executed by the program.
+#[derive(Default)]
+struct Recorder {
+    steps: Vec<Step>,
+    truncated: bool,
+}
+
+impl Inspector for Recorder {
+    fn step(&mut self, step: &TraceStep) {
+        if self.steps.len() == MAX_STEPS {
+            self.truncated = true;
+            return;
+        }
+        self.steps.push(Step {
+            pc: step.pc,
+            op_name: step.op_name.clone(),
+            depth: step.depth,
+            gas: step.gas,
+        });
+    }
+}
+
+/// The state of the executing frame right before a step.
+#[derive(Clone)]
+struct Snapshot {
+    address: U256,
+    gas_cost: u64,
+    refund: u64,
+    stack: Vec<U256>,
+    memory: Vec<u8>,
+    storage: Vec<(U256, U256)>,
+    return_data: Vec<u8>,
+}
+
+/// Captures the state of the frame at the `target`-th step. The execution can't be paused,
+/// so every inspection re-executes the program from the start.
+struct Capture {
+    target: usize,
+    count: usize,
+    snapshot: Option<Snapshot>,
+}
+
+impl Inspector for Capture {
+    fn step(&mut self, step: &TraceStep) {
+        if self.count == self.target {
+            let mut storage: Vec<(U256, U256)> = step.storage.slots(step.address).collect();
+            storage.sort();
+            self.snapshot = Some(Snapshot {
+                address: step.address,
+                gas_cost: step.gas_cost,
+                refund: step.refund,
+                stack: step.stack.to_vec(),
+                memory: step.memory.to_vec(),
+                storage,
+                return_data: step.return_data.to_vec(),
+            });
+        }
+        self.count += 1;
+    }
+}
+
+enum Breakpoint {
+    Pc(usize),
+    Opcode(String),
+}
+
+impl Breakpoint {
+    fn matches(&self, step: &Step) -> bool {
+        match self {
+            Breakpoint::Pc(pc) => step.pc == *pc,
+            Breakpoint::Opcode(name) => step.op_name.eq_ignore_ascii_case(name),
+        }
+    }
+}
+
+struct Debugger {
+    program: Program,
+    steps: Vec<Step>,
+    result: EvmResult,
+    /// Whether the execution went on after the last recorded step.
+    truncated: bool,
+    /// The index of the next step to execute, `steps.len()` once the execution is over.
+    position: usize,
+    breakpoints: Vec<Breakpoint>,
+    /// The last captured snapshot and its position, to inspect a step without re-executing.
+    snapshot: RefCell<Option<(usize, Snapshot)>>,
+}
+
+impl Debugger {
+    fn new(program: Program) -> Debugger {
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        let result = program.run(recorder.clone());
+        let Recorder { steps, truncated } = recorder.take();
+        Debugger {
+            program,
+            steps,
+            result,
+            truncated,
+            position: 0,
+            breakpoints: vec![],
+            snapshot: RefCell::new(None),
+        }
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        if self.position >= self.steps.len() {
+            return None;
+        }
+        if let Some((position, snapshot)) = &*self.snapshot.borrow() {
+            if *position == self.position {
+                return Some(snapshot.clone());
+            }
+        }
+        let capture = Rc::new(RefCell::new(Capture {
+            target: self.position,
+            count: 0,
+            snapshot: None,
+        }));
+        self.program.run(capture.clone());
+        let snapshot = capture.borrow_mut().snapshot.take();
+        if let Some(snapshot) = &snapshot {
+            *self.snapshot.borrow_mut() = Some((self.position, snapshot.clone()));
+        }
+        snapshot
+    }
+
+    fn step(&mut self, count: usize) {
+        self.position = (self.position + count).min(self.steps.len());
+    }
+
+    fn step_back(&mut self, count: usize) {
+        self.position = self.position.saturating_sub(count);
+    }
+
+    /// Steps over sub calls, stopping at the next step of the current frame or of its callers.
+    fn step_over(&mut self) {
+        let Some(current) = self.steps.get(self.position) else {
+            return;
+        };
+        let depth = current.depth;
+        self.position = (self.position + 1..self.steps.len())
+            .find(|&index| self.steps[index].depth <= depth)
+            .unwrap_or(self.steps.len());
+    }
+
+    fn resume(&mut self) {
+        self.position = (self.position + 1..self.steps.len())
+            .find(|&index| self.is_breakpoint(index))
+            .unwrap_or(self.steps.len());
+    }
+
+    fn resume_back(&mut self) {
+        self.position = (0..self.position)
+            .rev()
+            .find(|&index| self.is_breakpoint(index))
+            .unwrap_or(0);
+    }
+
+    fn is_breakpoint(&self, index: usize) -> bool {
+        self.breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.matches(&self.steps[index]))
+    }
+
+    fn print_position(&self) {
+        match self.steps.get(self.position) {
+            Some(step) => println!(
+                "[{}/{}] depth {} pc {:#06x} {} (gas {})",
+                self.position,
+                self.steps.len(),
+                step.depth,
+                step.pc,
+                step.op_name,
+                step.gas
+            ),
+            None => {
+                if self.truncated {
+                    println!("only the first {} steps were recorded", MAX_STEPS);
+                }
+                println!("execution finished: {:?}", self.result.result);
+                println!("return data: 0x{}", hex::encode(&self.result.ret));
+                println!("gas used: {}", self.result.gas_used);
+                if let Some(reason) = &self.result.revert_reason {
+                    println!("revert reason: {}", reason);
+                }
+            }
+        }
+    }
+
+    fn print_info(&self) {
+        self.print_position();
+        if let Some(snapshot) = self.snapshot() {
+            println!("address: 0x{:040x}", snapshot.address);
+            println!("gas cost: {}", snapshot.gas_cost);
+            println!("refund: {}", snapshot.refund);
+            println!("stack items: {}", snapshot.stack.len());
+            println!("memory size: {}", snapshot.memory.len());
+        }
+    }
+
+    fn print_stack(&self) {
+        let Some(snapshot) = self.snapshot() else {
+            return;
+        };
+        if snapshot.stack.is_empty() {
+            println!("(empty)");
+        }
+        // the top of the stack first.
+        for (index, item) in snapshot.stack.iter().rev().enumerate() {
+            println!("{:>4}: {:#066x}", index, item);
+        }
+    }
+
+    fn print_memory(&self) {
+        let Some(snapshot) = self.snapshot() else {
+            return;
+        };
+        if snapshot.memory.is_empty() {
+            println!("(empty)");
+        }
+        for (index, line) in snapshot.memory.chunks(32).enumerate() {
+            println!("{:#06x}: {}", index * 32, hex::encode(line));
+        }
+    }
+
+    fn print_storage(&self) {
+        let Some(snapshot) = self.snapshot() else {
+            return;
+        };
+        if snapshot.storage.is_empty() {
+            println!("(empty)");
+        }
+        for (slot, value) in snapshot.storage {
+            println!("{:#066x}: {:#066x}", slot, value);
+        }
+    }
+
+    fn print_return_data(&self) {
+        let Some(snapshot) = self.snapshot() else {
+            return;
+        };
+        println!("0x{}", hex::encode(snapshot.return_data));
+    }
+
+    fn print_breakpoints(&self) {
+        if self.breakpoints.is_empty() {
+            println!("no breakpoints");
+        }
+        for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+            match breakpoint {
+                Breakpoint::Pc(pc) => println!("{}: pc {:#06x}", index, pc),
+                Breakpoint::Opcode(name) => println!("{}: opcode {}", index, name),
+            }
+        }
+    }
+
+    /// Runs a command, returns `false` to quit.
+    fn command(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return true;
+        };
+        let argument = words.next();
+        let count = argument.and_then(parse_number).unwrap_or(1);
+
+        match command {
+            "s" | "step" => {
+                self.step(count);
+                self.print_position();
+            }
+            "n" | "next" => {
+                self.step_over();
+                self.print_position();
+            }
+            "c" | "continue" => {
+                self.resume();
+                self.print_position();
+            }
+            "bs" | "back" => {
+                self.step_back(count);
+                self.print_position();
+            }
+            "rc" | "reverse-continue" => {
+                self.resume_back();
+                self.print_position();
+            }
+            "b" | "break" => match argument {
+                None => self.print_breakpoints(),
+                Some(target) => {
+                    let breakpoint = match parse_number(target) {
+                        Some(pc) => Breakpoint::Pc(pc),
+                        None => Breakpoint::Opcode(target.to_uppercase()),
+                    };
+                    self.breakpoints.push(breakpoint);
+                    self.print_breakpoints();
+                }
+            },
+            "d" | "delete" => match argument.and_then(parse_number) {
+                Some(index) if index < self.breakpoints.len() => {
+                    self.breakpoints.remove(index);
+                    self.print_breakpoints();
+                }
+                _ => println!("usage: delete <breakpoint index>"),
+            },
+            "i" | "info" => self.print_info(),
+            "st" | "stack" => self.print_stack(),
+            "m" | "memory" => self.print_memory(),
+            "sto" | "storage" => self.print_storage(),
+            "r" | "returndata" => self.print_return_data(),
+            "h" | "help" => print_help(),
+            "q" | "quit" => return false,
+            _ => println!("unknown command `{}`, type `help` for the list", command),
+        }
+        true
+    }
+}
+
+fn print_help() {
+    println!("s, step [n]             execute the next n opcodes (default 1)");
+    println!("n, next                 step over sub calls");
+    println!("c, continue             run to the next breakpoint");
+    println!("bs, back [n]            step back n opcodes (default 1)");
+    println!("rc, reverse-continue    run back to the previous breakpoint");
+    println!("b, break [pc|OPCODE]    add a breakpoint, or list them");
+    println!("d, delete <index>       remove a breakpoint");
+    println!("i, info                 show the current position and frame");
+    println!("st, stack               show the stack, top first");
+    println!("m, memory               show a hex dump of the memory");
+    println!("sto, storage            show the storage of the executing account");
+    println!("r, returndata           show the return data of the last sub call");
+    println!("q, quit                 exit");
+}
+
+/// Parses a decimal or `0x` prefixed hex number.
+fn parse_number(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn decode_hex(text: &str) -> Vec<u8> {
+    let text = text.trim();
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    hex::decode(text).unwrap_or_else(|error| panic!("invalid hex `{}`: {}", text, error))
+}
+
+/// Decodes a hex value as a big endian 32 bytes word.
+fn decode_word(text: &Option<String>) -> Vec<u8> {
+    let text = text.as_deref().unwrap_or("0x");
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    let digits = text.trim_start_matches('0');
+    if digits.len() > 64 {
+        panic!("`0x{}` doesn't fit in 32 bytes", text);
+    }
+    decode_hex(&format!("{:0>64}", digits))
+}
+
+fn read_json<T: for<'a> Deserialize<'a>>(path: &str) -> T {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|error| panic!("{}: {}", path, error));
+    serde_json::from_str(&text).unwrap_or_else(|error| panic!("{}: {}", path, error))
+}
+
+fn load_program(args: &[String]) -> Program {
+    let mut code = None;
+    let mut tx = TxDataRaw::default();
+    let mut block = BlockDataRaw::default();
+    let mut state: HashMap<String, AddressData> = HashMap::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tx" => tx = read_json(args.next().expect("missing tx file")),
+            "--block" => block = read_json(args.next().expect("missing block file")),
+            "--state" => state = read_json(args.next().expect("missing state file")),
+            bytecode => {
+                let bytecode = std::fs::read_to_string(bytecode).unwrap_or(bytecode.to_string());
+                code = Some(decode_hex(&bytecode));
+            }
+        }
+    }
+
+    let mut tx_data = vec![
+        decode_word(&tx.to),
+        decode_word(&tx.from),
+        decode_word(&tx.origin),
+        decode_word(&tx.gasprice),
+        decode_word(&tx.value),
+        tx.data.as_deref().map(decode_hex).unwrap_or_default(),
+    ];
+    let gas = tx
+        .gas
+        .unwrap_or_else(|| format!("{:x}", DEFAULT_BLOCK_GAS_LIMIT));
+    tx_data.push(decode_word(&Some(gas)));
+
+    let block_data = vec![
+        decode_word(&block.basefee),
+        decode_word(&block.coinbase),
+        decode_word(&block.timestamp),
+        decode_word(&block.number),
+        decode_word(&block.difficulty),
+        decode_word(&block.gaslimit),
+        decode_word(&block.chainid),
+    ];
+
+    let state = state
+        .into_iter()
+        .map(|(address, data)| {
+            let nonce = data
+                .nonce
+                .as_deref()
+                .and_then(parse_number)
+                .unwrap_or_default();
+            let code = data
+                .code
+                .map(|code| decode_hex(&code.bin))
+                .unwrap_or_default();
+            (
+                decode_word(&Some(address)),
+                (nonce, decode_word(&data.balance), code),
+            )
+        })
+        .collect();
+
+    Program {
+        code: code
+            .expect("usage: evm-debug <bytecode> [--tx <file>] [--block <file>] [--state <file>]"),
+        tx: tx_data,
+        block: block_data,
+        state,
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut debugger = Debugger::new(load_program(&args));
+    debugger.print_position();
+
+    let stdin = io::stdin();
+    loop {
+        print!("(evm-debug) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        if !debugger.command(&line) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evm::assemble;
+
+    use super::*;
+
+    /// The caller runs 6 PUSH1, GAS, CALL, POP and STOP at the pcs 0 to 15, and 0xbb runs
+    /// 2 PUSH1, ADD, POP and STOP at the pcs 0 to 6: 15 steps.
+    fn debugger() -> Debugger {
+        let caller = "PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP STOP";
+        let callee = "PUSH1 1 PUSH1 2 ADD POP STOP";
+        let program = Program {
+            code: assemble(caller).unwrap(),
+            tx: vec![],
+            block: vec![],
+            state: HashMap::from([(vec![0xbb], (0, vec![], assemble(callee).unwrap()))]),
+        };
+        Debugger::new(program)
+    }
+
+    #[test]
+    fn step_over_skips_sub_calls() {
+        let mut debugger = debugger();
+        assert_eq!(debugger.steps.len(), 15);
+        // from the CALL to the POP after it.
+        debugger.position = 7;
+        debugger.step_over();
+        assert_eq!(debugger.position, 13);
+        assert_eq!(debugger.steps[13].op_name, "POP");
+
+        // inside the call, to the next step of the callee, then back to the caller.
+        debugger.position = 8;
+        debugger.step_over();
+        assert_eq!(debugger.position, 9);
+        debugger.position = 12;
+        debugger.step_over();
+        assert_eq!(debugger.position, 13);
+
+        // past the last step, the execution is over.
+        debugger.position = 14;
+        debugger.step_over();
+        assert_eq!(debugger.position, 15);
+        debugger.step_over();
+        assert_eq!(debugger.position, 15);
+    }
+
+    #[test]
+    fn breakpoints_match_pcs_and_opcodes() {
+        let step = |pc, op_name: &str| Step {
+            pc,
+            op_name: op_name.to_string(),
+            depth: 1,
+            gas: 0,
+        };
+        assert!(Breakpoint::Pc(4).matches(&step(4, "ADD")));
+        assert!(!Breakpoint::Pc(4).matches(&step(5, "ADD")));
+        assert!(Breakpoint::Opcode("ADD".to_string()).matches(&step(5, "ADD")));
+        assert!(Breakpoint::Opcode("add".to_string()).matches(&step(5, "ADD")));
+        assert!(!Breakpoint::Opcode("ADD".to_string()).matches(&step(5, "ADDMOD")));
+    }
+
+    #[test]
+    fn continue_and_reverse_continue_stop_at_breakpoints() {
+        let mut debugger = debugger();
+        assert!(debugger.command("break add"));
+        debugger.resume();
+        assert_eq!(debugger.position, 10);
+        debugger.resume();
+        assert_eq!(debugger.position, 15);
+        debugger.resume_back();
+        assert_eq!(debugger.position, 10);
+        // without a breakpoint before, back to the start.
+        debugger.resume_back();
+        assert_eq!(debugger.position, 0);
+
+        // pc 4 is a PUSH1 of the caller and the ADD of the callee.
+        assert!(debugger.command("delete 0"));
+        assert!(debugger.command("break 0x4"));
+        debugger.resume();
+        assert_eq!(debugger.position, 2);
+        debugger.resume();
+        assert_eq!(debugger.position, 10);
+        debugger.resume_back();
+        assert_eq!(debugger.position, 2);
+    }
+
+    #[test]
+    fn words_are_padded_to_32_bytes() {
+        let word = |text: &str| decode_word(&Some(text.to_string()));
+        assert_eq!(word("0x01"), [vec![0; 31], vec![1]].concat());
+        assert_eq!(decode_word(&None), vec![0; 32]);
+        // leading zeros don't count.
+        assert_eq!(word(&format!("0x{}", "0".repeat(70))), vec![0; 32]);
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit in 32 bytes")]
+    fn oversized_words_are_rejected() {
+        decode_word(&Some(format!("0x1{}", "0".repeat(64))));
+    }
+}
@@ -0,0 +1,624 @@
+//! A lightweight JSON-RPC dev node, backed by the in-process state.
+//!
+//! Usage: `evm-rpc [--port <port>] [--chain-id <id>] [--fund <address>]... [--block-time <s>]
+//! [--no-mining] [--genesis <file>] [--dump-state <file>]`
+//!
+//! By default every transaction is mined right away in its own block. With `--block-time` a
+//! block is mined every few seconds (at least 1), and with `--no-mining` only on `evm_mine`.
+//! The funded accounts start with 10000 ether, on top of the accounts of the `genesis.json`.
+//! With `--dump-state`, the accounts are written in the `alloc` format whenever they may have
+//! changed, so that the file can be given to `--genesis` to resume.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
+
+use evm::{
+    dump_alloc, load_alloc, Block, CallRequest, DevNode, EvmResult, ExecutionResult, Log,
+    MiningMode, NodeError, Receipt, RevertReason, Transaction,
+};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use primitive_types::U256;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const DEFAULT_PORT: u16 = 8545;
+const DEFAULT_CHAIN_ID: u64 = 1337;
+
+/// The JSON-RPC error code of a reverted call, with the revert data in `data`.
+const EXECUTION_REVERTED: i64 = 3;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const SERVER_ERROR: i64 = -32000;
+
+/// The hash of the RLP encoding of an empty list of uncles.
+const EMPTY_UNCLES_HASH: &str =
+    "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347";
+
+/// The methods that can change the accounts, after which the state is dumped.
+const STATE_CHANGING_METHODS: &[&str] = &[
+    "eth_sendRawTransaction",
+    "eth_sendTransaction",
+    "evm_mine",
+    "anvil_mine",
+    "evm_revert",
+    "anvil_setBalance",
+    "anvil_setCode",
+    "anvil_setNonce",
+    "anvil_setStorageAt",
+];
+
+/// The most blocks mined by a single `anvil_mine`, so that a request can't keep the node busy
+/// for hours.
+const MAX_MINED_BLOCKS: u64 = 10_000;
+
+/// The stack of the thread executing the requests. In debug builds, the 1024 nested frames of
+/// a call at the depth limit don't fit in the 8 MiB of the main thread.
+const SERVER_STACK_SIZE: usize = 64 << 20;
+
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<String>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<NodeError> for RpcError {
+    fn from(error: NodeError) -> RpcError {
+        match &error {
+            NodeError::Reverted(data) => RpcError {
+                code: EXECUTION_REVERTED,
+                message: revert_message(data),
+                data: Some(bytes(data)),
+            },
+            _ => RpcError::new(SERVER_ERROR, error.to_string()),
+        }
+    }
+}
+
+type RpcResult = Result<Value, RpcError>;
+
+fn main() {
+    let mut port = DEFAULT_PORT;
+    let mut chain_id = DEFAULT_CHAIN_ID;
+    let mut funded = vec![];
+    let mut mining = MiningMode::Auto;
+    let mut genesis = None;
+    let mut dump = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--no-mining" {
+            mining = MiningMode::Manual;
+            continue;
+        }
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--port" => port = value.parse().unwrap_or_else(|_| usage()),
+            "--chain-id" => chain_id = value.parse().unwrap_or_else(|_| usage()),
+            "--fund" => funded.push(parse_u256(&value).unwrap_or_else(|_| usage())),
+            "--block-time" => {
+                // 0 would mine blocks back to back.
+                let seconds = match value.parse() {
+                    Ok(0) | Err(_) => usage(),
+                    Ok(seconds) => seconds,
+                };
+                mining = MiningMode::Interval(Duration::from_secs(seconds));
+            }
+            "--genesis" => genesis = Some(value),
+            "--dump-state" => dump = Some(value),
+            _ => usage(),
+        }
+    }
+
+    let ether = U256::exp10(18);
+    let mut node = DevNode::new(chain_id).with_mining(mining);
+    if let Some(path) = genesis {
+        let alloc = std::fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|json| load_alloc(&json).map_err(|error| error.to_string()));
+        match alloc {
+            Ok((state, storage)) => node = node.with_state(state, storage),
+            Err(error) => {
+                eprintln!("cannot load {}: {}", path, error);
+                std::process::exit(1);
+            }
+        }
+    }
+    for address in funded {
+        node = node.with_account(address, ether * 10_000);
+    }
+
+    let server = Server::http(("127.0.0.1", port)).unwrap_or_else(|error| {
+        eprintln!("cannot listen on port {}: {}", port, error);
+        std::process::exit(1);
+    });
+    println!(
+        "listening on http://127.0.0.1:{} (chain id {})",
+        port, chain_id
+    );
+
+    let serving = std::thread::Builder::new()
+        .stack_size(SERVER_STACK_SIZE)
+        .spawn(move || serve(server, node, dump))
+        .expect("cannot start the server thread");
+    let _ = serving.join();
+}
+
+/// Serves the requests, and mines the blocks with interval mining.
+fn serve(server: Server, mut node: DevNode, dump: Option<String>) {
+    dump_state(&node, dump.as_deref());
+    let mut last_mined = Instant::now();
+    loop {
+        // wake up in time to mine the next block with interval mining.
+        let timeout = match node.mining {
+            MiningMode::Interval(interval) => interval.saturating_sub(last_mined.elapsed()),
+            _ => Duration::from_secs(1),
+        };
+        match server.recv_timeout(timeout) {
+            Ok(Some(request)) => {
+                if respond(&mut node, request) {
+                    dump_state(&node, dump.as_deref());
+                }
+            }
+            Ok(None) => {}
+            Err(error) => {
+                eprintln!("cannot receive requests: {}", error);
+                std::process::exit(1);
+            }
+        }
+        match node.mining {
+            MiningMode::Interval(interval) if last_mined.elapsed() >= interval => {
+                node.mine();
+                last_mined = Instant::now();
+                dump_state(&node, dump.as_deref());
+            }
+            MiningMode::Interval(_) => {}
+            _ => last_mined = Instant::now(),
+        }
+    }
+}
+
+/// Writes the accounts to `path`, if any, in the `alloc` format.
+fn dump_state(node: &DevNode, path: Option<&str>) {
+    let Some(path) = path else {
+        return;
+    };
+    let alloc = dump_alloc(node.state(), node.storage());
+    let json = serde_json::to_string_pretty(&alloc).expect("valid JSON");
+    if let Err(error) = std::fs::write(path, json) {
+        eprintln!("cannot write {}: {}", path, error);
+    }
+}
+
+/// Answers `request`. Returns true if it may have changed the state.
+fn respond(node: &mut DevNode, mut request: Request) -> bool {
+    let cors = Header::from_bytes("Access-Control-Allow-Origin", "*").expect("valid header");
+    if *request.method() == Method::Options {
+        let allow = Header::from_bytes("Access-Control-Allow-Headers", "*").expect("valid header");
+        let _ = request.respond(Response::empty(200).with_header(cors).with_header(allow));
+        return false;
+    }
+    let mut body = String::new();
+    let mut changed = false;
+    let response = match request.as_reader().read_to_string(&mut body) {
+        Ok(_) => handle_body(node, &body, &mut changed),
+        Err(_) => error_response(Value::Null, RpcError::new(INVALID_REQUEST, "invalid body")),
+    };
+    let content_type =
+        Header::from_bytes("Content-Type", "application/json").expect("valid header");
+    let _ = request.respond(
+        Response::from_string(response.to_string())
+            .with_header(content_type)
+            .with_header(cors),
+    );
+    changed
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: evm-rpc [--port <port>] [--chain-id <id>] [--fund <address>]... \
+         [--block-time <seconds>] [--no-mining] [--genesis <file>] [--dump-state <file>]"
+    );
+    std::process::exit(1);
+}
+
+/// Handles a single request or a batch. `changed` is set if a request may have changed the state.
+fn handle_body(node: &mut DevNode, body: &str, changed: &mut bool) -> Value {
+    match serde_json::from_str::<Value>(body) {
+        Ok(Value::Array(requests)) => requests
+            .iter()
+            .map(|request| handle_request(node, request, changed))
+            .collect(),
+        Ok(request) => handle_request(node, &request, changed),
+        Err(_) => error_response(Value::Null, RpcError::new(-32700, "parse error")),
+    }
+}
+
+fn handle_request(node: &mut DevNode, request: &Value, changed: &mut bool) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return error_response(id, RpcError::new(INVALID_REQUEST, "missing method"));
+    };
+    let params = request
+        .get("params")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let state_changing = STATE_CHANGING_METHODS.contains(&method);
+    *changed |= state_changing;
+    // a bug in the interpreter fails the request rather than the whole node, and the changes
+    // made before the panic are rolled back.
+    let backup = state_changing.then(|| node.clone());
+    let result = panic::catch_unwind(AssertUnwindSafe(|| dispatch(node, method, &params)))
+        .unwrap_or_else(|_| {
+            if let Some(backup) = backup {
+                *node = backup;
+            }
+            Err(RpcError::new(INTERNAL_ERROR, "internal error"))
+        });
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => error_response(id, error),
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    let mut body = json!({ "code": error.code, "message": error.message });
+    if let Some(data) = error.data {
+        body["data"] = Value::String(data);
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": body })
+}
+
+fn dispatch(node: &mut DevNode, method: &str, params: &[Value]) -> RpcResult {
+    match method {
+        "web3_clientVersion" => Ok(json!(format!("evm-rpc/{}", env!("CARGO_PKG_VERSION")))),
+        "net_version" => Ok(json!(node.chain_id().to_string())),
+        "eth_chainId" => Ok(quantity(node.chain_id().into())),
+        "eth_blockNumber" => Ok(quantity(node.block_number())),
+        "eth_gasPrice" => Ok(quantity(node.block.basefee)),
+        "eth_accounts" => Ok(json!([])),
+        "eth_getBalance" => Ok(quantity(node.balance(u256_param(params, 0)?))),
+        "eth_getTransactionCount" => {
+            let address = u256_param(params, 0)?;
+            let nonce = match params.get(1).and_then(Value::as_str) {
+                Some("pending") => node.pending_nonce(address),
+                _ => node.nonce(address),
+            };
+            Ok(quantity(nonce.into()))
+        }
+        "eth_getCode" => Ok(json!(bytes(&node.code(u256_param(params, 0)?)))),
+        "eth_getStorageAt" => {
+            let address = u256_param(params, 0)?;
+            let slot = u256_param(params, 1)?;
+            Ok(word(node.storage_at(address, slot)))
+        }
+        "eth_getBlockByNumber" => {
+            let block = match params.first().and_then(Value::as_str) {
+                None | Some("latest" | "pending" | "safe" | "finalized") => {
+                    Some(node.latest_block())
+                }
+                Some("earliest") => node.mined_block(U256::zero()),
+                Some(_) => node.mined_block(u256_param(params, 0)?),
+            };
+            Ok(block.map(json_block).unwrap_or(Value::Null))
+        }
+        "eth_getBlockByHash" => Ok(node
+            .block_by_hash(u256_param(params, 0)?)
+            .map(json_block)
+            .unwrap_or(Value::Null)),
+        "eth_call" => {
+            let result = node.call(&call_request(params)?)?;
+            match result.result {
+                ExecutionResult::Success { .. } => Ok(json!(bytes(&result.ret))),
+                _ => Err(execution_error(&result)),
+            }
+        }
+        "eth_estimateGas" => Ok(quantity(node.estimate_gas(&call_request(params)?)?.into())),
+        "eth_sendRawTransaction" => {
+            let raw = parse_bytes(param(params, 0)?).map_err(invalid_params)?;
+            let tx = Transaction::decode(&raw)
+                .map_err(|error| RpcError::new(INVALID_PARAMS, error.to_string()))?;
+            let from = recover_sender(&tx)
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "invalid signature"))?;
+            Ok(word(node.send_transaction(from, &tx)?))
+        }
+        "eth_getTransactionReceipt" => {
+            let hash = u256_param(params, 0)?;
+            Ok(node.receipt(hash).map(receipt).unwrap_or(Value::Null))
+        }
+        "eth_sendTransaction" => Ok(word(node.send_unsigned(&call_request(params)?)?)),
+        "evm_mine" => {
+            if !params.is_empty() {
+                node.set_next_block_timestamp(u256_param(params, 0)?)?;
+            }
+            node.mine();
+            Ok(json!("0x0"))
+        }
+        "anvil_mine" => {
+            let blocks = match params.is_empty() {
+                true => U256::one(),
+                false => u256_param(params, 0)?,
+            };
+            if blocks > MAX_MINED_BLOCKS.into() {
+                return Err(invalid_params(format!(
+                    "at most {} blocks can be mined at once",
+                    MAX_MINED_BLOCKS
+                )));
+            }
+            let blocks = blocks.low_u64();
+            for _ in 0..blocks {
+                node.mine();
+            }
+            Ok(Value::Null)
+        }
+        "evm_setAutomine" => {
+            node.mining = match params.first().and_then(Value::as_bool) {
+                Some(true) => MiningMode::Auto,
+                Some(false) => MiningMode::Manual,
+                None => return Err(invalid_params("expected a boolean".to_string())),
+            };
+            Ok(json!(true))
+        }
+        "anvil_getAutomine" | "hardhat_getAutomine" => Ok(json!(node.mining == MiningMode::Auto)),
+        "evm_setIntervalMining" => {
+            node.mining = match u256_param(params, 0)?.low_u64() {
+                0 => MiningMode::Manual,
+                millis => MiningMode::Interval(Duration::from_millis(millis)),
+            };
+            Ok(json!(true))
+        }
+        "evm_snapshot" => Ok(quantity(node.snapshot().into())),
+        "evm_revert" => Ok(json!(
+            node.revert_to(u256_param(params, 0)?.low_u64() as usize)
+        )),
+        "evm_setNextBlockTimestamp" => {
+            node.set_next_block_timestamp(u256_param(params, 0)?)?;
+            Ok(Value::Null)
+        }
+        "evm_increaseTime" => Ok(quantity(
+            node.increase_time(u256_param(params, 0)?.low_u64()).into(),
+        )),
+        "anvil_setBalance" => {
+            node.set_balance(u256_param(params, 0)?, u256_param(params, 1)?);
+            Ok(Value::Null)
+        }
+        "anvil_setCode" => {
+            let code = parse_bytes(param(params, 1)?).map_err(invalid_params)?;
+            node.set_code(u256_param(params, 0)?, code);
+            Ok(Value::Null)
+        }
+        "anvil_setNonce" => {
+            node.set_nonce(u256_param(params, 0)?, u256_param(params, 1)?.low_u64());
+            Ok(Value::Null)
+        }
+        "anvil_setStorageAt" => {
+            let (address, slot) = (u256_param(params, 0)?, u256_param(params, 1)?);
+            node.set_storage_at(address, slot, u256_param(params, 2)?);
+            Ok(json!(true))
+        }
+        "anvil_impersonateAccount" => {
+            node.impersonate(u256_param(params, 0)?);
+            Ok(Value::Null)
+        }
+        "anvil_stopImpersonatingAccount" => {
+            node.stop_impersonating(u256_param(params, 0)?);
+            Ok(Value::Null)
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("the method {} does not exist", method),
+        )),
+    }
+}
+
+/// Recovers the sender from the signature of `tx`.
+fn recover_sender(tx: &Transaction) -> Option<U256> {
+    let mut r = [0u8; 32];
+    let mut s = [0u8; 32];
+    tx.signature.r.to_big_endian(&mut r);
+    tx.signature.s.to_big_endian(&mut s);
+    let signature = Signature::from_scalars(r, s).ok()?;
+    let recovery_id = RecoveryId::from_byte(tx.recovery_id()?)?;
+    let key =
+        VerifyingKey::recover_from_prehash(&tx.signing_hash(), &signature, recovery_id).ok()?;
+    let point = key.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    Some(U256::from_big_endian(&hash[12..]))
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    use sha3::{Digest, Keccak256};
+    Keccak256::digest(data).into()
+}
+
+fn execution_error(result: &EvmResult) -> RpcError {
+    match &result.result {
+        ExecutionResult::Revert { data } => NodeError::Reverted(data.clone()).into(),
+        ExecutionResult::Halt { reason, .. } => NodeError::Halted(*reason).into(),
+        ExecutionResult::Success { .. } => RpcError::new(SERVER_ERROR, "execution succeeded"),
+    }
+}
+
+fn revert_message(data: &[u8]) -> String {
+    match data.is_empty() {
+        true => "execution reverted".to_string(),
+        false => format!("execution reverted: {}", RevertReason::decode(data)),
+    }
+}
+
+fn call_request(params: &[Value]) -> Result<CallRequest, RpcError> {
+    let call = params
+        .first()
+        .ok_or_else(|| invalid_params("missing call object".to_string()))?;
+    let field = |name: &str| call.get(name).and_then(Value::as_str);
+    let u256 = |name: &str| -> Result<Option<U256>, RpcError> {
+        field(name)
+            .map(|value| parse_u256(value).map_err(invalid_params))
+            .transpose()
+    };
+    let data = field("data")
+        .or_else(|| field("input"))
+        .map(parse_bytes)
+        .transpose()
+        .map_err(invalid_params)?;
+    Ok(CallRequest {
+        from: u256("from")?.unwrap_or_default(),
+        to: u256("to")?,
+        gas: u256("gas")?.map(|gas| gas.low_u64()),
+        gas_price: u256("gasPrice")?.unwrap_or_default(),
+        value: u256("value")?.unwrap_or_default(),
+        data: data.unwrap_or_default(),
+    })
+}
+
+fn receipt(receipt: &Receipt) -> Value {
+    json!({
+        "transactionHash": word(receipt.transaction_hash),
+        "transactionIndex": quantity(receipt.transaction_index.into()),
+        "blockHash": word(receipt.block_hash),
+        "blockNumber": quantity(receipt.block_number),
+        "from": address(receipt.from),
+        "to": receipt.to.map(address),
+        "contractAddress": receipt.contract_address.map(address),
+        "gasUsed": quantity(receipt.gas_used.into()),
+        "cumulativeGasUsed": quantity(receipt.cumulative_gas_used.into()),
+        "effectiveGasPrice": quantity(receipt.effective_gas_price),
+        "status": if receipt.success { "0x1" } else { "0x0" },
+        "logs": receipt.logs.iter().enumerate().map(|(index, log)| json_log(log, receipt, index)).collect::<Vec<_>>(),
+        "logsBloom": bytes(&[0u8; 256]),
+        "type": quantity(receipt.tx_type.into()),
+    })
+}
+
+/// Serializes the `index`-th log of `receipt`.
+fn json_log(log: &Log, receipt: &Receipt, index: usize) -> Value {
+    json!({
+        "address": address(log.address),
+        "topics": log.topics.iter().map(|topic| word(*topic)).collect::<Vec<_>>(),
+        "data": bytes(&log.data),
+        "blockHash": word(receipt.block_hash),
+        "blockNumber": quantity(receipt.block_number),
+        "transactionHash": word(receipt.transaction_hash),
+        "transactionIndex": quantity(receipt.transaction_index.into()),
+        "logIndex": quantity((receipt.first_log_index + index as u64).into()),
+        "removed": false,
+    })
+}
+
+/// A mined block, with the hashes of its transactions. The tries aren't computed: their
+/// roots are zero.
+fn json_block(block: &Block) -> Value {
+    json!({
+        "number": quantity(block.number),
+        "hash": word(block.hash),
+        "parentHash": word(block.parent_hash),
+        "nonce": "0x0000000000000000",
+        "mixHash": word(U256::zero()),
+        "sha3Uncles": EMPTY_UNCLES_HASH,
+        "logsBloom": bytes(&[0u8; 256]),
+        "transactionsRoot": word(U256::zero()),
+        "stateRoot": word(U256::zero()),
+        "receiptsRoot": word(U256::zero()),
+        "miner": address(block.coinbase),
+        "difficulty": "0x0",
+        "totalDifficulty": "0x0",
+        "extraData": "0x",
+        "size": "0x0",
+        "gasLimit": quantity(block.gas_limit),
+        "gasUsed": quantity(block.gas_used.into()),
+        "timestamp": quantity(block.timestamp),
+        "baseFeePerGas": quantity(block.base_fee),
+        "transactions": block.transactions.iter().map(|hash| word(*hash)).collect::<Vec<_>>(),
+        "uncles": [],
+    })
+}
+
+fn param(params: &[Value], index: usize) -> Result<&str, RpcError> {
+    let param = params
+        .get(index)
+        .ok_or_else(|| invalid_params(format!("missing param {}", index)))?;
+    param
+        .as_str()
+        .ok_or_else(|| invalid_params(format!("expected a hex string, got {}", param)))
+}
+
+/// Parses a quantity, given as a hex string or as a number.
+fn u256_param(params: &[Value], index: usize) -> Result<U256, RpcError> {
+    if let Some(number) = params.get(index).and_then(Value::as_u64) {
+        return Ok(U256::from(number));
+    }
+    parse_u256(param(params, index)?).map_err(invalid_params)
+}
+
+fn invalid_params(message: String) -> RpcError {
+    RpcError::new(INVALID_PARAMS, message)
+}
+
+fn parse_u256(text: &str) -> Result<U256, String> {
+    let hex = text.strip_prefix("0x").unwrap_or(text);
+    if hex.is_empty() {
+        return Ok(U256::zero());
+    }
+    U256::from_str_radix(hex, 16).map_err(|_| format!("invalid quantity {}", text))
+}
+
+fn parse_bytes(text: &str) -> Result<Vec<u8>, String> {
+    hex::decode(text.strip_prefix("0x").unwrap_or(text))
+        .map_err(|_| format!("invalid hex data {}", text))
+}
+
+fn quantity(value: U256) -> Value {
+    json!(format!("{:#x}", value))
+}
+
+fn word(value: U256) -> Value {
+    json!(format!("0x{:064x}", value))
+}
+
+fn address(value: U256) -> String {
+    format!("0x{:040x}", value)
+}
+
+fn bytes(data: &[u8]) -> String {
+    format!("0x{}", hex::encode(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dispatch, recover_sender, MAX_MINED_BLOCKS};
+    use evm::{DevNode, Transaction};
+    use primitive_types::U256;
+    use serde_json::json;
+
+    #[test]
+    fn recovers_the_sender_of_the_eip155_example() {
+        // signed with the private key 0x4646...46.
+        let raw = hex::decode("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+        let tx = Transaction::decode(&raw).unwrap();
+        let sender = U256::from_str_radix("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f", 16).unwrap();
+        assert_eq!(recover_sender(&tx), Some(sender));
+    }
+
+    #[test]
+    fn anvil_mine_is_capped() {
+        let mut node = DevNode::new(1337);
+        assert!(dispatch(&mut node, "anvil_mine", &[json!("0x3")]).is_ok());
+        assert_eq!(node.block_number(), 3.into());
+        let blocks = json!(format!("{:#x}", MAX_MINED_BLOCKS + 1));
+        assert!(dispatch(&mut node, "anvil_mine", &[blocks]).is_err());
+        assert_eq!(node.block_number(), 3.into());
+    }
+}
@@ -1,36 +1,251 @@
+use std::collections::HashMap;
+
+use primitive_types::U256;
+
+use crate::{address::Address, utility::saturating_word};
+
+/// The number of past blocks whose hash is accessible through the `BLOCKHASH` opcode.
+pub const BLOCK_HASH_HISTORY: u64 = 256;
+
+/// The minimum price of a unit of blob gas (EIP-4844).
+pub const MIN_BLOB_BASE_FEE: u64 = 1;
+
+/// Controls the maximum rate of change of the blob base fee (EIP-4844, Cancun).
+pub const BLOB_BASE_FEE_UPDATE_FRACTION: u64 = 3_338_477;
+
+/// Controls the maximum rate of change of the blob base fee (EIP-7691, Prague).
+pub const BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE: u64 = 5_007_716;
+
 /// Block data.
+#[derive(Debug, Clone)]
 pub struct BlockData {
-    pub basefee: Vec<u8>,
-    pub coinbase: Vec<u8>,
-    pub timestamp: Vec<u8>,
-    pub number: Vec<u8>,
-    pub difficulty: Vec<u8>,
-    pub gaslimit: Vec<u8>,
-    pub chainid: Vec<u8>,
+    pub basefee: U256,
+    pub coinbase: Address,
+    pub timestamp: U256,
+    pub number: U256,
+    /// Only meaningful before the merge, see `prevrandao`.
+    pub difficulty: U256,
+    pub gaslimit: U256,
+    pub chainid: U256,
+    /// The beacon chain randomness (EIP-4399). `None` for pre-merge blocks.
+    pub prevrandao: Option<U256>,
+    /// `None` for pre-Cancun blocks.
+    pub excess_blob_gas: Option<u64>,
+    pub blob_base_fee_update_fraction: u64,
+    /// The root of the parent beacon block (EIP-4788). `None` for pre-Cancun blocks.
+    pub parent_beacon_block_root: Option<U256>,
+    pub block_hashes: BlockHashes,
+}
+
+impl Default for BlockData {
+    fn default() -> Self {
+        Self {
+            basefee: U256::zero(),
+            coinbase: Address::ZERO,
+            timestamp: U256::zero(),
+            number: U256::zero(),
+            difficulty: U256::zero(),
+            gaslimit: U256::zero(),
+            chainid: U256::zero(),
+            prevrandao: None,
+            excess_blob_gas: None,
+            blob_base_fee_update_fraction: BLOB_BASE_FEE_UPDATE_FRACTION,
+            parent_beacon_block_root: None,
+            block_hashes: BlockHashes::default(),
+        }
+    }
 }
 
 impl BlockData {
+    /// Builds the block data from the big endian encoded values in this order:
+    /// `basefee, coinbase, timestamp, number, difficulty, gaslimit, chainid`, optionally followed by
+    /// `prevrandao, excess_blob_gas, parent_beacon_block_root`. Missing values are left empty.
+    /// The coinbase keeps the lowest 20 bytes of its word.
+    /// Values longer than 32 bytes saturate to `U256::MAX`, and an excess blob gas above
+    /// `u64::MAX` saturates too, so its blob base fee reports an overflow.
     pub fn new(block_data: Vec<Vec<u8>>) -> BlockData {
-        if !block_data.is_empty() {
-            Self {
-                basefee: block_data[0].clone(),
-                coinbase: block_data[1].clone(),
-                timestamp: block_data[2].clone(),
-                number: block_data[3].clone(),
-                difficulty: block_data[4].clone(),
-                gaslimit: block_data[5].clone(),
-                chainid: block_data[6].clone(),
-            }
-        } else {
-            Self {
-                basefee: vec![],
-                coinbase: vec![],
-                timestamp: vec![],
-                number: vec![],
-                difficulty: vec![],
-                gaslimit: vec![],
-                chainid: vec![],
-            }
+        let word = |index: usize| block_data.get(index).map(|value| saturating_word(value));
+
+        Self {
+            basefee: word(0).unwrap_or_default(),
+            coinbase: word(1).map(Address::from).unwrap_or_default(),
+            timestamp: word(2).unwrap_or_default(),
+            number: word(3).unwrap_or_default(),
+            difficulty: word(4).unwrap_or_default(),
+            gaslimit: word(5).unwrap_or_default(),
+            chainid: word(6).unwrap_or_default(),
+            prevrandao: word(7),
+            excess_blob_gas: word(8).map(|value| value.min(u64::MAX.into()).as_u64()),
+            parent_beacon_block_root: word(9),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the value of the `DIFFICULTY` opcode, which became `PREVRANDAO` after the merge.
+    pub fn difficulty_or_prevrandao(&self) -> U256 {
+        self.prevrandao.unwrap_or(self.difficulty)
+    }
+
+    /// Returns the price of a unit of blob gas, derived from the excess blob gas.
+    /// `None` if the excess blob gas is so high that the price overflows.
+    pub fn blob_base_fee(&self) -> Option<U256> {
+        calc_blob_base_fee(
+            self.excess_blob_gas.unwrap_or_default(),
+            self.blob_base_fee_update_fraction,
+        )
+    }
+
+    /// Returns the hash of block `number` as seen by the `BLOCKHASH` opcode.
+    /// Only the last 256 blocks (current one excluded) are available, any other number returns 0.
+    pub fn block_hash(&self, number: U256) -> U256 {
+        if number >= self.number || self.number - number > BLOCK_HASH_HISTORY.into() {
+            return 0.into();
         }
+        self.block_hashes.get(number).unwrap_or_default()
+    }
+}
+
+/// Computes the blob base fee as defined in EIP-4844, `None` if it overflows.
+pub fn calc_blob_base_fee(excess_blob_gas: u64, update_fraction: u64) -> Option<U256> {
+    fake_exponential(
+        MIN_BLOB_BASE_FEE.into(),
+        excess_blob_gas.into(),
+        update_fraction.into(),
+    )
+}
+
+/// Approximates `factor * e ** (numerator / denominator)` using Taylor expansion (EIP-4844).
+/// The reference works on unbounded integers: `None` if an intermediate value overflows.
+pub fn fake_exponential(factor: U256, numerator: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return Some(factor);
+    }
+
+    let mut i = U256::one();
+    let mut output = U256::zero();
+    let mut numerator_accum = factor.checked_mul(denominator)?;
+    while !numerator_accum.is_zero() {
+        output = output.checked_add(numerator_accum)?;
+        numerator_accum = numerator_accum.checked_mul(numerator)? / denominator.checked_mul(i)?;
+        i += U256::one();
+    }
+    Some(output / denominator)
+}
+
+/// Provider of the hashes of the previous blocks.
+#[derive(Debug, Clone, Default)]
+pub struct BlockHashes {
+    /// Mapping between block number and block hash.
+    pub hashes: HashMap<U256, U256>,
+}
+
+impl BlockHashes {
+    pub fn new(hashes: HashMap<U256, U256>) -> BlockHashes {
+        BlockHashes { hashes }
+    }
+
+    pub fn insert(&mut self, number: U256, hash: U256) {
+        self.hashes.insert(number, hash);
+    }
+
+    pub fn get(&self, number: U256) -> Option<U256> {
+        self.hashes.get(&number).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_hashes_are_served_for_the_last_256_blocks() {
+        let hashes = (0..=1000u64).map(|number| (number.into(), (number + 0xff00).into()));
+        let block = BlockData {
+            number: 1000.into(),
+            block_hashes: BlockHashes::new(hashes.collect()),
+            ..BlockData::default()
+        };
+        assert_eq!(block.block_hash(999.into()), (999 + 0xff00).into());
+        assert_eq!(block.block_hash(744.into()), (744 + 0xff00).into());
+        // the current block, the one before the window and a future one.
+        assert_eq!(block.block_hash(1000.into()), U256::zero());
+        assert_eq!(block.block_hash(743.into()), U256::zero());
+        assert_eq!(block.block_hash(1001.into()), U256::zero());
+        assert_eq!(block.block_hash(U256::MAX), U256::zero());
+
+        // a block in the window the provider doesn't know.
+        let block = BlockData {
+            number: 1000.into(),
+            ..BlockData::default()
+        };
+        assert_eq!(block.block_hash(999.into()), U256::zero());
+    }
+
+    #[test]
+    fn fake_exponential_matches_the_reference() {
+        // (factor, numerator, denominator, expected), from the geth test vectors.
+        let vectors = [
+            (1, 0, 1, 1),
+            (38493, 0, 1000, 38493),
+            (0, 1234, 2345, 0),
+            (1, 2, 1, 6),
+            (1, 4, 2, 6),
+            (1, 3, 1, 16),
+            (1, 6, 2, 18),
+            (1, 4, 1, 49),
+            (1, 8, 2, 50),
+            (10, 8, 2, 542),
+            (11, 8, 2, 596),
+            (1, 5, 1, 136),
+            (1, 5, 2, 11),
+            (2, 5, 2, 23),
+            (1, 50000000, 2225652, 5709098764u64),
+            (1, 380928, BLOB_BASE_FEE_UPDATE_FRACTION, 1),
+        ];
+        for (factor, numerator, denominator, expected) in vectors {
+            assert_eq!(
+                fake_exponential(factor.into(), numerator.into(), denominator.into()),
+                Some(expected.into())
+            );
+        }
+    }
+
+    #[test]
+    fn blob_base_fee_overflow_is_reported() {
+        assert_eq!(
+            calc_blob_base_fee(u64::MAX, BLOB_BASE_FEE_UPDATE_FRACTION),
+            None
+        );
+    }
+
+    #[test]
+    fn oversized_excess_blob_gas_saturates() {
+        let mut fields = vec![vec![]; 8];
+        let mut excess_blob_gas = [0u8; 32];
+        (U256::from(u64::MAX) + 2).to_big_endian(&mut excess_blob_gas);
+        fields.push(excess_blob_gas.to_vec());
+
+        let block = BlockData::new(fields);
+        assert_eq!(block.excess_blob_gas, Some(u64::MAX));
+        assert_eq!(block.blob_base_fee(), None);
+    }
+
+    #[test]
+    fn oversized_words_saturate() {
+        let mut timestamp = vec![1];
+        timestamp.extend([0; 32]);
+        // leading zeros are not significant.
+        let mut number = vec![0];
+        number.extend([0xff; 32]);
+        let block = BlockData::new(vec![vec![], vec![], timestamp, number]);
+        assert_eq!(block.timestamp, U256::MAX);
+        assert_eq!(block.number, U256::MAX);
+
+        let mut number = vec![0; 40];
+        number.push(7);
+        assert_eq!(
+            BlockData::new(vec![vec![], vec![], vec![], number]).number,
+            7.into()
+        );
     }
 }
@@ -0,0 +1,279 @@
+use primitive_types::U256;
+use std::rc::Rc;
+
+use crate::{
+    block_data::BlockData,
+    env::Env,
+    evm::{Evm, ExecutionResult},
+    gas::Gas,
+    memory::Memory,
+    state_data::State,
+    storage::Storage,
+    tx_data::TxData,
+};
+
+/// The address used as caller of the system calls (EIP-4788 and EIP-2935).
+pub const SYSTEM_ADDRESS: [u8; 20] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xff, 0xff, 0xff, 0xfe,
+];
+
+/// The beacon roots contract introduced by EIP-4788.
+pub const BEACON_ROOTS_ADDRESS: [u8; 20] = [
+    0x00, 0x0f, 0x3d, 0xf6, 0xd7, 0x32, 0x80, 0x7e, 0xf1, 0x31, 0x9f, 0xb7, 0xb8, 0xbb, 0x85, 0x22,
+    0xd0, 0xbe, 0xac, 0x02,
+];
+
+/// The history storage contract introduced by EIP-2935.
+pub const HISTORY_STORAGE_ADDRESS: [u8; 20] = [
+    0x00, 0x00, 0xf9, 0x08, 0x27, 0xf1, 0xc5, 0x3a, 0x10, 0xcb, 0x7a, 0x02, 0x33, 0x5b, 0x17, 0x53,
+    0x20, 0x00, 0x29, 0x35,
+];
+
+/// The gas available to a system call.
+pub const SYSTEM_CALL_GAS_LIMIT: u64 = 30_000_000;
+
+/// Withdrawals are expressed in gwei, balances in wei.
+const GWEI_TO_WEI: u64 = 1_000_000_000;
+
+/// A validator withdrawal (EIP-4895).
+#[derive(Debug, Clone)]
+pub struct Withdrawal {
+    pub index: u64,
+    pub validator_index: u64,
+    pub address: U256,
+    /// The amount withdrawn, in gwei.
+    pub amount: u64,
+}
+
+/// Runs the system calls that must happen before the transactions of a block are executed.
+/// The parent block hash is read from the block hashes of `block_data`.
+pub fn pre_block(state: &mut State, storage: &mut Storage, block_data: &BlockData) {
+    if let Some(root) = block_data.parent_beacon_block_root {
+        process_beacon_block_root(state, storage, block_data, root);
+    }
+    if block_data.number.is_zero() {
+        return;
+    }
+    if let Some(hash) = block_data.block_hashes.get(block_data.number - 1) {
+        process_parent_block_hash(state, storage, block_data, hash);
+    }
+}
+
+/// Applies the changes that must happen after the transactions of a block are executed.
+pub fn post_block(state: &mut State, withdrawals: &[Withdrawal]) {
+    process_withdrawals(state, withdrawals);
+}
+
+/// Credits every withdrawal amount to its recipient. Withdrawals are not transactions,
+/// so they don't execute any code and can't fail.
+pub fn process_withdrawals(state: &mut State, withdrawals: &[Withdrawal]) {
+    for withdrawal in withdrawals {
+        if withdrawal.amount == 0 {
+            continue;
+        }
+        let amount = U256::from(withdrawal.amount) * U256::from(GWEI_TO_WEI);
+        state.transfer_balance(amount, withdrawal.address);
+    }
+}
+
+/// Stores the parent beacon block root in the EIP-4788 contract.
+pub fn process_beacon_block_root(
+    state: &mut State,
+    storage: &mut Storage,
+    block_data: &BlockData,
+    parent_beacon_block_root: U256,
+) -> Option<ExecutionResult> {
+    let mut calldata = [0u8; 32];
+    parent_beacon_block_root.to_big_endian(&mut calldata);
+
+    system_call(
+        state,
+        storage,
+        block_data,
+        U256::from_big_endian(&BEACON_ROOTS_ADDRESS),
+        calldata.to_vec(),
+    )
+}
+
+/// Stores the parent block hash in the EIP-2935 contract. There is no parent for the genesis block.
+pub fn process_parent_block_hash(
+    state: &mut State,
+    storage: &mut Storage,
+    block_data: &BlockData,
+    parent_hash: U256,
+) -> Option<ExecutionResult> {
+    if block_data.number.is_zero() {
+        return None;
+    }
+
+    let mut calldata = [0u8; 32];
+    parent_hash.to_big_endian(&mut calldata);
+
+    system_call(
+        state,
+        storage,
+        block_data,
+        U256::from_big_endian(&HISTORY_STORAGE_ADDRESS),
+        calldata.to_vec(),
+    )
+}
+
+/// Calls `contract` from the system address. If the contract is not deployed, nothing happens.
+/// State changes are kept only if the call succeeds.
+fn system_call(
+    state: &mut State,
+    storage: &mut Storage,
+    block_data: &BlockData,
+    contract: U256,
+    calldata: Vec<u8>,
+) -> Option<ExecutionResult> {
+    let code = state.get_code(contract);
+    if code.is_empty() {
+        return None;
+    }
+
+    let mut to = [0u8; 32];
+    contract.to_big_endian(&mut to);
+    let mut from = [0u8; 32];
+    from[12..].copy_from_slice(&SYSTEM_ADDRESS);
+    let tx_data = TxData::new(vec![
+        to.to_vec(),
+        from.to_vec(),
+        from.to_vec(),
+        vec![],
+        vec![],
+        calldata,
+    ]);
+
+    let env = Rc::new(Env::new(block_data.clone(), &tx_data));
+
+    let mut evm = Evm::new(
+        Box::from(code),
+        tx_data,
+        env,
+        state.clone(),
+        storage.clone(),
+        vec![],
+        vec![],
+        vec![],
+        vec![],
+        Memory::new(),
+        1024,
+        false,
+        Gas::new(SYSTEM_CALL_GAS_LIMIT),
+    );
+
+    let result = evm.execute();
+
+    if result.is_success() {
+        *state = evm.state();
+        *storage = evm.storage();
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assembler::assemble, block_data::BlockHashes};
+
+    fn block(number: u64) -> BlockData {
+        BlockData {
+            number: number.into(),
+            timestamp: 1000.into(),
+            ..BlockData::default()
+        }
+    }
+
+    #[test]
+    fn withdrawals_are_credited_in_wei() {
+        let mut state = State::default();
+        state.set_balance(0xaa.into(), 1.into());
+        let withdrawal = |index, address: u64, amount| Withdrawal {
+            index,
+            validator_index: index,
+            address: address.into(),
+            amount,
+        };
+        post_block(
+            &mut state,
+            &[
+                withdrawal(0, 0xaa, 2),
+                withdrawal(1, 0xaa, 3),
+                withdrawal(2, 0xbb, 0),
+            ],
+        );
+        assert_eq!(state.get_balance(0xaa.into()), U256::from(5_000_000_001u64));
+        // a zero amount doesn't touch the recipient.
+        assert!(!state.exists(0xbb.into()));
+    }
+
+    #[test]
+    fn beacon_block_root_is_stored_by_timestamp() {
+        let beacon_roots = U256::from_big_endian(&BEACON_ROOTS_ADDRESS);
+        let mut state = State::default();
+        // stores the calldata at TIMESTAMP and the caller at 0, as the EIP-4788 contract reads it.
+        state.set_code(
+            beacon_roots,
+            assemble("PUSH1 0 CALLDATALOAD TIMESTAMP SSTORE  CALLER PUSH1 0 SSTORE  STOP").unwrap(),
+        );
+        let mut storage = Storage::new_empty();
+        let block = BlockData {
+            parent_beacon_block_root: Some(0xabcd.into()),
+            ..block(1)
+        };
+        pre_block(&mut state, &mut storage, &block);
+        assert_eq!(storage.load_word(beacon_roots, 1000.into()), 0xabcd.into());
+        assert_eq!(
+            storage.load_word(beacon_roots, U256::zero()),
+            U256::from_big_endian(&SYSTEM_ADDRESS)
+        );
+    }
+
+    #[test]
+    fn parent_block_hash_is_stored_by_number() {
+        let history = U256::from_big_endian(&HISTORY_STORAGE_ADDRESS);
+        let mut state = State::default();
+        // stores the calldata at NUMBER - 1.
+        state.set_code(
+            history,
+            assemble("PUSH1 0 CALLDATALOAD PUSH1 1 NUMBER SUB SSTORE STOP").unwrap(),
+        );
+        let mut storage = Storage::new_empty();
+        let block = BlockData {
+            block_hashes: BlockHashes::new([(9.into(), 0x1234.into())].into()),
+            ..block(10)
+        };
+        pre_block(&mut state, &mut storage, &block);
+        assert_eq!(storage.load_word(history, 9.into()), 0x1234.into());
+    }
+
+    #[test]
+    fn system_calls_without_contract_do_nothing() {
+        let mut state = State::default();
+        let mut storage = Storage::new_empty();
+        let block = block(10);
+        assert!(process_beacon_block_root(&mut state, &mut storage, &block, 1.into()).is_none());
+        assert!(process_parent_block_hash(&mut state, &mut storage, &block, 1.into()).is_none());
+        let beacon_roots = U256::from_big_endian(&BEACON_ROOTS_ADDRESS);
+        assert_eq!(storage.load_word(beacon_roots, 1000.into()), U256::zero());
+        assert!(!state.exists(U256::from_big_endian(&SYSTEM_ADDRESS)));
+    }
+
+    #[test]
+    fn genesis_block_has_no_parent_hash_to_store() {
+        let history = U256::from_big_endian(&HISTORY_STORAGE_ADDRESS);
+        let mut state = State::default();
+        state.set_code(history, assemble("PUSH1 1 PUSH1 0 SSTORE STOP").unwrap());
+        let mut storage = Storage::new_empty();
+        let block = BlockData {
+            block_hashes: BlockHashes::new([(U256::zero(), 0x1234.into())].into()),
+            ..block(0)
+        };
+        pre_block(&mut state, &mut storage, &block);
+        assert!(process_parent_block_hash(&mut state, &mut storage, &block, 1.into()).is_none());
+        assert_eq!(storage.load_word(history, U256::zero()), U256::zero());
+    }
+}
@@ -0,0 +1,342 @@
+use std::rc::Rc;
+
+use primitive_types::U256;
+
+use crate::{
+    address::Address,
+    block_data::{BlockData, BLOB_BASE_FEE_UPDATE_FRACTION, BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE},
+    env::Env,
+    evm::Evm,
+    gas::Gas,
+    inspector::SharedInspector,
+    memory::Memory,
+    node::DEFAULT_BLOCK_GAS_LIMIT,
+    state_data::{AddressData, State},
+    storage::Storage,
+    tx_data::TxData,
+    STACK_LIMIT,
+};
+
+/// The fork whose block fields are used. The opcodes always follow the latest rules.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Spec {
+    /// Before the merge: `DIFFICULTY` returns the difficulty, `prevrandao` is ignored.
+    London,
+    Merge,
+    Shanghai,
+    /// Blobs (EIP-4844) and the parent beacon block root (EIP-4788).
+    #[default]
+    Cancun,
+    /// The blob base fee update fraction of EIP-7691.
+    Prague,
+}
+
+impl Spec {
+    /// Clears the fields of `block` that don't exist in the fork, and fills the ones it needs.
+    fn apply(self, block: &mut BlockData) {
+        if self < Spec::Merge {
+            block.prevrandao = None;
+        }
+        if self < Spec::Cancun {
+            block.excess_blob_gas = None;
+            block.parent_beacon_block_root = None;
+        } else {
+            block.excess_blob_gas.get_or_insert(0);
+        }
+        block.blob_base_fee_update_fraction = match self {
+            Spec::Prague => BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE,
+            _ => BLOB_BASE_FEE_UPDATE_FRACTION,
+        };
+    }
+}
+
+/// The transaction executed by an `Evm` built with `Evm::builder()`.
+#[derive(Debug, Clone, Default)]
+pub struct Tx {
+    pub from: Address,
+    pub to: Address,
+    /// `from` if `None`.
+    pub origin: Option<Address>,
+    pub gas_price: U256,
+    pub value: U256,
+    pub data: Vec<u8>,
+    /// Without it, the execution is not metered.
+    pub gas_limit: Option<u64>,
+}
+
+/// The addresses are stored as 32 bytes words, like the other fields.
+impl From<Tx> for TxData {
+    fn from(tx: Tx) -> TxData {
+        let word = |value: U256| {
+            let mut bytes = [0u8; 32];
+            value.to_big_endian(&mut bytes);
+            bytes.to_vec()
+        };
+        TxData {
+            to: word(tx.to.into()),
+            from: word(tx.from.into()),
+            origin: word(tx.origin.unwrap_or(tx.from).into()),
+            gasprice: word(tx.gas_price),
+            value: word(tx.value),
+            data: tx.data,
+            gas: tx.gas_limit,
+        }
+    }
+}
+
+/// Builds the top-level frame of a transaction from typed values.
+///
+/// Without `code`, the code of `tx.to` in the state is executed. The default block is block 1
+/// of chain 1, with the gas limit of the dev node.
+#[derive(Clone)]
+pub struct EvmBuilder {
+    code: Option<Vec<u8>>,
+    tx: Tx,
+    block: BlockData,
+    state: State,
+    storage: Storage,
+    spec: Spec,
+    inspector: SharedInspector,
+}
+
+impl Default for EvmBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EvmBuilder {
+    pub fn new() -> EvmBuilder {
+        EvmBuilder {
+            code: None,
+            tx: Tx::default(),
+            block: BlockData {
+                number: U256::one(),
+                gaslimit: U256::from(DEFAULT_BLOCK_GAS_LIMIT),
+                chainid: U256::one(),
+                ..BlockData::default()
+            },
+            state: State::default(),
+            storage: Storage::new_empty(),
+            spec: Spec::default(),
+            inspector: None,
+        }
+    }
+
+    pub fn code(mut self, code: impl Into<Vec<u8>>) -> EvmBuilder {
+        self.code = Some(code.into());
+        self
+    }
+
+    pub fn tx(mut self, tx: Tx) -> EvmBuilder {
+        self.tx = tx;
+        self
+    }
+
+    pub fn block(mut self, block: BlockData) -> EvmBuilder {
+        self.block = block;
+        self
+    }
+
+    /// Replaces the accounts, keeping the storage.
+    pub fn state(mut self, state: State) -> EvmBuilder {
+        self.state = state;
+        self
+    }
+
+    pub fn storage(mut self, storage: Storage) -> EvmBuilder {
+        self.storage = storage;
+        self
+    }
+
+    /// Adds or replaces the account at `address`.
+    pub fn account(mut self, address: Address, account: AddressData) -> EvmBuilder {
+        let address = U256::from(address);
+        self.state.set_balance(address, account.balance);
+        self.state.set_nonce(address, account.nonce);
+        self.state.set_code(address, account.code);
+        self
+    }
+
+    pub fn storage_at(mut self, address: Address, slot: U256, value: U256) -> EvmBuilder {
+        self.storage.set_word(address.into(), slot, value);
+        self
+    }
+
+    pub fn spec(mut self, spec: Spec) -> EvmBuilder {
+        self.spec = spec;
+        self
+    }
+
+    /// Reports the execution to `inspector` (e.g. a `JsonTracer`).
+    pub fn inspector(mut self, inspector: SharedInspector) -> EvmBuilder {
+        self.inspector = inspector;
+        self
+    }
+
+    /// Builds the frame, to execute with `Evm::run`.
+    pub fn build(self) -> Evm {
+        let mut block = self.block;
+        self.spec.apply(&mut block);
+        let code = self
+            .code
+            .unwrap_or_else(|| self.state.get_code(self.tx.to.into()));
+        let tx_data = TxData::from(self.tx);
+        let env = Rc::new(Env::new(block, &tx_data));
+        let gas = tx_data.gas.map(Gas::new).unwrap_or_else(Gas::unlimited);
+        Evm::new(
+            code.into_boxed_slice(),
+            tx_data,
+            env,
+            self.state,
+            self.storage,
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            Memory::new(),
+            STACK_LIMIT,
+            false,
+            gas,
+        )
+        .with_inspector(self.inspector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use primitive_types::U256;
+
+    use super::{Spec, Tx};
+    use crate::{
+        address::Address, assembler::assemble, block_data::BlockData, evm::Evm,
+        node::DEFAULT_BLOCK_GAS_LIMIT, state_data::AddressData, tx_data::TxData,
+        utility::contract_address,
+    };
+
+    fn run_block(spec: Spec, block: BlockData, code: &str) -> Vec<U256> {
+        let result = Evm::builder()
+            .code(assemble(code).unwrap())
+            .block(block)
+            .spec(spec)
+            .build()
+            .run();
+        assert!(result.success);
+        result.stack
+    }
+
+    #[test]
+    fn addresses_are_words() {
+        let (from, to, origin) = (Address([1; 20]), Address([2; 20]), Address([3; 20]));
+        let data = TxData::from(Tx {
+            from,
+            to,
+            ..Tx::default()
+        });
+        assert_eq!(data.to, [vec![0; 12], vec![2; 20]].concat());
+        assert_eq!(data.origin, [vec![0; 12], vec![1; 20]].concat());
+
+        // ADDRESS, CALLER, ORIGIN, then an empty CREATE.
+        let result = Evm::builder()
+            .code(hex::decode("3033325f5f5ff000").unwrap())
+            .tx(Tx {
+                from,
+                to,
+                origin: Some(origin),
+                ..Tx::default()
+            })
+            .build()
+            .run();
+        assert!(result.success);
+        assert_eq!(
+            result.stack,
+            vec![
+                contract_address(to.into(), 0),
+                origin.into(),
+                from.into(),
+                to.into()
+            ]
+        );
+        assert_eq!(result.state.get_nonce(to.into()), 1);
+    }
+
+    #[test]
+    fn defaults_to_block_1_of_chain_1_in_cancun() {
+        assert_eq!(Spec::default(), Spec::Cancun);
+        let tx = Tx::default();
+        assert_eq!(
+            (tx.from, tx.to, tx.origin),
+            (Address::ZERO, Address::ZERO, None)
+        );
+        assert_eq!(TxData::from(tx).gas, None);
+
+        let result = Evm::builder()
+            .code(assemble("NUMBER CHAINID GASLIMIT COINBASE BLOBBASEFEE").unwrap())
+            .build()
+            .run();
+        assert_eq!(
+            result.stack,
+            vec![
+                U256::one(),
+                U256::zero(),
+                DEFAULT_BLOCK_GAS_LIMIT.into(),
+                U256::one(),
+                U256::one()
+            ]
+        );
+    }
+
+    #[test]
+    fn specs_clear_the_fields_of_later_forks() {
+        let block = BlockData {
+            coinbase: Address([0xcc; 20]),
+            difficulty: 5.into(),
+            prevrandao: Some(7.into()),
+            excess_blob_gas: Some(1 << 22),
+            ..BlockData::default()
+        };
+        let difficulty = |spec| run_block(spec, block.clone(), "DIFFICULTY COINBASE");
+        assert_eq!(
+            difficulty(Spec::London),
+            vec![Address([0xcc; 20]).into(), 5.into()]
+        );
+        assert_eq!(
+            difficulty(Spec::Merge),
+            vec![Address([0xcc; 20]).into(), 7.into()]
+        );
+
+        // Prague raises the update fraction, so the same excess gives a lower fee.
+        let fee = |spec| run_block(spec, block.clone(), "BLOBBASEFEE")[0];
+        assert!(fee(Spec::Prague) < fee(Spec::Cancun));
+        assert_eq!(fee(Spec::Shanghai), U256::one());
+    }
+
+    #[test]
+    fn accounts_are_seeded_in_the_state() {
+        let (to, other) = (Address([0xaa; 20]), Address([0xbb; 20]));
+        let code = assemble(
+            "SELFBALANCE PUSH20 0xbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb BALANCE PUSH1 0 SLOAD",
+        )
+        .unwrap();
+        let account = |balance: u64| AddressData {
+            nonce: 1,
+            balance: balance.into(),
+            code: code.clone(),
+        };
+        // without code, the code of `tx.to` is executed, and the last account given wins.
+        let result = Evm::builder()
+            .account(to, account(1))
+            .account(to, account(2))
+            .account(other, account(3))
+            .storage_at(to, U256::zero(), 9.into())
+            .tx(Tx {
+                to,
+                ..Tx::default()
+            })
+            .build()
+            .run();
+        assert!(result.success);
+        assert_eq!(result.stack, vec![9.into(), 3.into(), 2.into()]);
+        assert_eq!(result.state.get_nonce(to.into()), 1);
+    }
+}
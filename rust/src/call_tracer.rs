@@ -0,0 +1,292 @@
+use primitive_types::U256;
+use serde_json::{json, Value};
+
+use crate::{
+    evm::ExecutionResult,
+    inspector::{CallInputs, CallScheme, CreateInputs, CreateOutcome, FrameOutcome, Inspector},
+    logs::Log,
+    revert_reason::RevertReason,
+};
+
+/// A frame of the call tree recorded by the `CallTracer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+    /// `CALL`, `DELEGATECALL`, `STATICCALL`, `CREATE` or `SELFDESTRUCT`.
+    pub kind: &'static str,
+    pub from: U256,
+    pub to: U256,
+    /// `None` for `DELEGATECALL` and `STATICCALL`, which can't transfer value.
+    pub value: Option<U256>,
+    pub gas: u64,
+    pub gas_used: u64,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    pub error: Option<String>,
+    pub revert_reason: Option<String>,
+    /// The logs emitted by this frame, each with the number of sub calls made before it.
+    pub logs: Vec<(Log, usize)>,
+    pub calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    fn new(kind: &'static str, from: U256, to: U256, value: Option<U256>, gas: u64) -> CallFrame {
+        CallFrame {
+            kind,
+            from,
+            to,
+            value,
+            gas,
+            gas_used: 0,
+            input: vec![],
+            output: vec![],
+            error: None,
+            revert_reason: None,
+            logs: vec![],
+            calls: vec![],
+        }
+    }
+
+    /// Records how the frame ended.
+    fn finish(&mut self, result: &ExecutionResult, output: &[u8], gas_used: u64) {
+        self.output = output.to_vec();
+        self.gas_used = gas_used;
+        match result {
+            ExecutionResult::Success { .. } => {}
+            ExecutionResult::Revert { data } => {
+                self.error = Some("execution reverted".to_string());
+                self.revert_reason = match RevertReason::decode(data) {
+                    RevertReason::Error(message) => Some(message),
+                    RevertReason::Unknown(_) => None,
+                    reason => Some(reason.to_string()),
+                };
+            }
+            ExecutionResult::Halt { reason, .. } => {
+                self.error = Some(reason.to_string());
+                self.output.clear();
+            }
+        }
+        if self.error.is_some() {
+            self.clear_logs();
+        }
+    }
+
+    /// The logs of a failed frame are discarded, along with the ones of its sub calls.
+    fn clear_logs(&mut self) {
+        self.logs.clear();
+        for call in &mut self.calls {
+            call.clear_logs();
+        }
+    }
+
+    /// Serializes the frame in the format of geth's `callTracer`.
+    pub fn to_json(&self) -> Value {
+        let mut frame = json!({
+            "type": self.kind,
+            "from": format!("0x{:040x}", self.from),
+            "to": format!("0x{:040x}", self.to),
+            "gas": format!("{:#x}", self.gas),
+            "gasUsed": format!("{:#x}", self.gas_used),
+            "input": format!("0x{}", hex::encode(&self.input)),
+        });
+        if let Some(value) = self.value {
+            frame["value"] = Value::from(format!("{:#x}", value));
+        }
+        if !self.output.is_empty() {
+            frame["output"] = Value::from(format!("0x{}", hex::encode(&self.output)));
+        }
+        if let Some(error) = &self.error {
+            frame["error"] = Value::from(error.as_str());
+        }
+        if let Some(reason) = &self.revert_reason {
+            frame["revertReason"] = Value::from(reason.as_str());
+        }
+        if !self.logs.is_empty() {
+            let logs: Vec<Value> = self
+                .logs
+                .iter()
+                .map(|(log, position)| {
+                    let topics: Vec<String> = log
+                        .topics
+                        .iter()
+                        .map(|topic| format!("0x{:064x}", topic))
+                        .collect();
+                    json!({
+                        "address": format!("0x{:040x}", log.address),
+                        "topics": topics,
+                        "data": format!("0x{}", hex::encode(&log.data)),
+                        "position": format!("{:#x}", position),
+                    })
+                })
+                .collect();
+            frame["logs"] = Value::from(logs);
+        }
+        if !self.calls.is_empty() {
+            let calls: Vec<Value> = self.calls.iter().map(CallFrame::to_json).collect();
+            frame["calls"] = Value::from(calls);
+        }
+        frame
+    }
+}
+
+/// An inspector recording the tree of the frames entered during an execution.
+#[derive(Debug, Default)]
+pub struct CallTracer {
+    /// The frames entered and not exited yet, the innermost last.
+    stack: Vec<CallFrame>,
+    root: Option<CallFrame>,
+}
+
+impl CallTracer {
+    pub fn new() -> CallTracer {
+        CallTracer::default()
+    }
+
+    /// Returns the outermost frame, once the execution is over.
+    pub fn root(&self) -> Option<&CallFrame> {
+        self.root.as_ref()
+    }
+
+    /// Returns the call tree in the format of geth's `callTracer`.
+    pub fn to_json(&self) -> Value {
+        self.root
+            .as_ref()
+            .map(CallFrame::to_json)
+            .unwrap_or(Value::Null)
+    }
+
+    /// Attaches an exited frame to its parent, or makes it the root if it's the outermost one.
+    fn exit(&mut self, frame: CallFrame) {
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+impl Inspector for CallTracer {
+    fn call(&mut self, inputs: &CallInputs) -> Option<FrameOutcome> {
+        let (kind, value) = match inputs.scheme {
+            CallScheme::Call => ("CALL", Some(inputs.value)),
+            CallScheme::DelegateCall => ("DELEGATECALL", None),
+            CallScheme::StaticCall => ("STATICCALL", None),
+        };
+        let mut frame = CallFrame::new(
+            kind,
+            inputs.caller,
+            inputs.code_address,
+            value,
+            inputs.gas.limit(),
+        );
+        frame.input = inputs.input.clone();
+        self.stack.push(frame);
+        None
+    }
+
+    fn call_end(&mut self, inputs: &CallInputs, outcome: FrameOutcome) -> FrameOutcome {
+        if let Some(mut frame) = self.stack.pop() {
+            // the transaction reports the gas used after the refund.
+            let gas_used = if inputs.depth == 1 {
+                outcome.gas.spent()
+            } else {
+                outcome.gas.used()
+            };
+            frame.finish(&outcome.result, &outcome.output, gas_used);
+            self.exit(frame);
+        }
+        outcome
+    }
+
+    fn create(&mut self, inputs: &CreateInputs) -> Option<CreateOutcome> {
+        let mut frame = CallFrame::new(
+            "CREATE",
+            inputs.caller,
+            inputs.address,
+            Some(inputs.value),
+            inputs.gas.limit(),
+        );
+        frame.input = inputs.init_code.clone();
+        self.stack.push(frame);
+        None
+    }
+
+    fn create_end(&mut self, _inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        if let Some(mut frame) = self.stack.pop() {
+            frame.finish(&outcome.result, &outcome.output, outcome.gas.used());
+            if outcome.result.is_success() && outcome.address.is_none() {
+                frame.error = Some("contract creation code storage out of gas".to_string());
+                frame.output.clear();
+                frame.clear_logs();
+            }
+            self.exit(frame);
+        }
+        outcome
+    }
+
+    fn log(&mut self, log: &Log) {
+        if let Some(frame) = self.stack.last_mut() {
+            let position = frame.calls.len();
+            frame.logs.push((log.clone(), position));
+        }
+    }
+
+    fn selfdestruct(&mut self, address: U256, beneficiary: U256, value: U256) {
+        if let Some(frame) = self.stack.last_mut() {
+            let selfdestruct = CallFrame::new("SELFDESTRUCT", address, beneficiary, Some(value), 0);
+            frame.calls.push(selfdestruct);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use super::CallTracer;
+    use crate::evm_with_inspector;
+
+    #[test]
+    fn nested_frames_with_a_revert() {
+        // PUSH1 0 (x5), PUSH1 0xbb, GAS, CALL, POP, then CREATEs a contract with an
+        // empty runtime code: PUSH5 0x60006000f3, PUSH1 0, MSTORE, PUSH1 5, PUSH1 27,
+        // PUSH1 0, CREATE, STOP
+        let code = hex::decode("6000600060006000600060bb5af1506460006000f36000526005601b6000f000")
+            .unwrap();
+        // an empty CREATE, then a revert with one byte:
+        // PUSH1 0, DUP1, DUP1, CREATE, POP, PUSH1 1, PUSH1 0, REVERT
+        let callee = hex::decode("60008080f05060016000fd").unwrap();
+        let state = HashMap::from([(vec![0xbb], (0, vec![], callee))]);
+        // to, from, origin, gasprice, value, data and a gas limit of 1_000_000.
+        let mut tx_data = vec![vec![]; 6];
+        tx_data.push(vec![0x0f, 0x42, 0x40]);
+        let tracer = Rc::new(RefCell::new(CallTracer::new()));
+        let result = evm_with_inspector(code, tx_data, vec![], state, tracer.clone());
+        assert!(result.success);
+
+        let tracer = tracer.borrow();
+        let root = tracer.root().unwrap();
+        assert_eq!((root.kind, root.error.as_deref()), ("CALL", None));
+        assert_eq!(root.calls.len(), 2);
+
+        let call = &root.calls[0];
+        assert_eq!((call.kind, call.to), ("CALL", 0xbb.into()));
+        assert_eq!(call.error.as_deref(), Some("execution reverted"));
+        assert_eq!(call.output, vec![0]);
+        assert_eq!(call.calls.len(), 1);
+        let reverted_create = &call.calls[0];
+        assert_eq!(reverted_create.kind, "CREATE");
+        assert_eq!(reverted_create.from, 0xbb.into());
+
+        let create = &root.calls[1];
+        assert_eq!(create.kind, "CREATE");
+        assert_eq!(create.to, result.stack[0]);
+        assert_eq!(create.input, hex::decode("60006000f3").unwrap());
+        assert_eq!(create.error, None);
+        assert!(create.calls.is_empty());
+
+        let json = tracer.to_json();
+        assert_eq!(json["calls"][0]["error"], "execution reverted");
+        assert_eq!(json["calls"][0]["output"], "0x00");
+        assert_eq!(json["calls"][0]["calls"][0]["type"], "CREATE");
+        assert_eq!(json["calls"][1].get("output"), None);
+    }
+}
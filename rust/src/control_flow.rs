@@ -0,0 +1,379 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use primitive_types::U256;
+
+use crate::{
+    disassembler::{find_metadata, instructions, Instruction},
+    opcode::OpCode,
+};
+
+/// The maximum number of different entry stacks analyzed for a block. Internal functions are
+/// entered with a different return address on the stack from each call site, so the analysis
+/// keeps the entry stacks apart, up to this limit.
+const MAX_CONTEXTS: usize = 64;
+
+/// A stack item during the analysis: the value is known if it was pushed by a `PUSH` or `PC`.
+type Value = Option<U256>;
+
+/// A sequence of instructions that's only entered at the first one and left at the last one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// The pc of the first instruction.
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+    /// The blocks reached by `JUMP` or `JUMPI`, by their start.
+    pub jump_targets: BTreeSet<usize>,
+    /// The block reached by running past the last instruction.
+    pub fallthrough: Option<usize>,
+    /// True if the target of the final jump couldn't be resolved on some path.
+    pub dynamic_jump: bool,
+    /// True if the final jump can go to a pc that isn't a `JUMPDEST`.
+    pub invalid_jump: bool,
+    /// The change of the stack size after running the whole block.
+    pub stack_delta: isize,
+    /// The stack size needed when entering the block not to underflow.
+    pub stack_required: usize,
+    /// True if some path enters the block with less than `stack_required` items.
+    pub can_underflow: bool,
+    /// True if the analysis found a path from the start of the code to the block.
+    pub reachable: bool,
+}
+
+impl BasicBlock {
+    /// Returns the pc following the last instruction.
+    pub fn end(&self) -> usize {
+        self.instructions
+            .last()
+            .map(|instruction| instruction.pc + instruction.size())
+            .unwrap_or(self.start)
+    }
+
+    /// Returns the blocks that can run after this one.
+    pub fn successors(&self) -> impl Iterator<Item = usize> + '_ {
+        self.jump_targets.iter().copied().chain(self.fallthrough)
+    }
+}
+
+/// The control flow graph of some bytecode, with its blocks by their start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub blocks: BTreeMap<usize, BasicBlock>,
+}
+
+impl ControlFlowGraph {
+    /// Splits `code` into basic blocks and links them, resolving the targets of the jumps
+    /// by following the constants pushed on the stack. The metadata trailer of solc is skipped.
+    pub fn new(code: &[u8]) -> ControlFlowGraph {
+        let end = find_metadata(code)
+            .map(|metadata| metadata.offset)
+            .unwrap_or(code.len());
+        let mut graph = ControlFlowGraph {
+            blocks: split_blocks(instructions(&code[..end])),
+        };
+        graph.analyze();
+        graph
+    }
+
+    /// Returns the block containing `pc`.
+    pub fn block_at(&self, pc: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .range(..=pc)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| pc < block.end())
+    }
+
+    /// Returns the blocks that can jump or run into the block starting at `start`.
+    pub fn predecessors(&self, start: usize) -> Vec<usize> {
+        self.blocks
+            .values()
+            .filter(|block| block.successors().any(|successor| successor == start))
+            .map(|block| block.start)
+            .collect()
+    }
+
+    /// Exports the graph in the Graphviz DOT format. Unreachable blocks are gray, blocks that
+    /// can underflow are red, and fallthrough edges are dashed.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box fontname=monospace];\n");
+        for block in self.blocks.values() {
+            let mut label = format!(
+                "block {:#06x} (stack {:+}, needs {})\\l",
+                block.start, block.stack_delta, block.stack_required
+            );
+            for instruction in &block.instructions {
+                label.push_str(&format!("{:04x}: {}", instruction.pc, instruction.name));
+                if !instruction.immediate.is_empty() {
+                    label.push_str(&format!(" 0x{}", hex::encode(&instruction.immediate)));
+                }
+                label.push_str("\\l");
+            }
+            if block.dynamic_jump {
+                label.push_str("dynamic jump\\l");
+            }
+            if block.invalid_jump {
+                label.push_str("invalid jump\\l");
+            }
+            let style = if block.can_underflow {
+                " color=red"
+            } else if !block.reachable {
+                " color=gray fontcolor=gray"
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "    b{} [label=\"{}\"{}];\n",
+                block.start, label, style
+            ));
+        }
+        for block in self.blocks.values() {
+            for target in &block.jump_targets {
+                dot.push_str(&format!("    b{} -> b{};\n", block.start, target));
+            }
+            if let Some(next) = block.fallthrough {
+                dot.push_str(&format!(
+                    "    b{} -> b{} [style=dashed];\n",
+                    block.start, next
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Runs the blocks on abstract stacks from the start of the code, to find the jump targets,
+    /// the reachable blocks and the ones that can underflow.
+    fn analyze(&mut self) {
+        let jumpdests: HashSet<U256> = self
+            .blocks
+            .values()
+            .filter(|block| block.instructions[0].is_jumpdest())
+            .map(|block| U256::from(block.start))
+            .collect();
+        let mut contexts: HashMap<usize, HashSet<Vec<Value>>> = HashMap::new();
+        let mut queue = VecDeque::from([(0, vec![])]);
+        while let Some((start, stack)) = queue.pop_front() {
+            let seen = contexts.entry(start).or_default();
+            if seen.len() >= MAX_CONTEXTS || !seen.insert(stack.clone()) {
+                continue;
+            }
+            let Some(block) = self.blocks.get_mut(&start) else {
+                continue;
+            };
+            block.reachable = true;
+            let Some((stack, target)) = run_block(block, stack) else {
+                block.can_underflow = true;
+                continue;
+            };
+
+            let last = block.instructions.last().expect("blocks are not empty");
+            if matches!(OpCode::new(last.opcode), Some(OpCode::Jump | OpCode::Jumpi)) {
+                match target {
+                    Some(target) if jumpdests.contains(&target) => {
+                        block.jump_targets.insert(target.as_usize());
+                        queue.push_back((target.as_usize(), stack.clone()));
+                    }
+                    Some(_) => block.invalid_jump = true,
+                    None => block.dynamic_jump = true,
+                }
+            }
+            if let Some(next) = block.fallthrough {
+                queue.push_back((next, stack));
+            }
+        }
+    }
+}
+
+/// Splits the instructions into blocks: a block starts at a `JUMPDEST` or after an
+/// instruction that jumps or ends the execution.
+fn split_blocks(instructions: Vec<Instruction>) -> BTreeMap<usize, BasicBlock> {
+    let mut groups: Vec<Vec<Instruction>> = vec![];
+    let mut ended = true;
+    for instruction in instructions {
+        if ended || instruction.is_jumpdest() {
+            groups.push(vec![]);
+        }
+        ended = instruction.is_terminator() || instruction.opcode == OpCode::Jumpi as u8;
+        groups
+            .last_mut()
+            .expect("a block was started")
+            .push(instruction);
+    }
+
+    let starts: Vec<usize> = groups.iter().map(|group| group[0].pc).collect();
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(index, instructions)| {
+            let last = instructions.last().expect("blocks are not empty");
+            let fallthrough = if last.is_terminator() {
+                None
+            } else {
+                starts.get(index + 1).copied()
+            };
+            let (stack_delta, stack_required) = stack_effect(&instructions);
+            let start = instructions[0].pc;
+            let block = BasicBlock {
+                start,
+                instructions,
+                jump_targets: BTreeSet::new(),
+                fallthrough,
+                dynamic_jump: false,
+                invalid_jump: false,
+                stack_delta,
+                stack_required,
+                can_underflow: false,
+                reachable: false,
+            };
+            (start, block)
+        })
+        .collect()
+}
+
+/// Returns the change of the stack size after running `instructions`, and the stack size
+/// they need not to underflow.
+fn stack_effect(instructions: &[Instruction]) -> (isize, usize) {
+    let mut delta: isize = 0;
+    let mut required: isize = 0;
+    for instruction in instructions {
+        let (inputs, outputs) = OpCode::new(instruction.opcode)
+            .map(|opcode| opcode.stack_items())
+            .unwrap_or_default();
+        required = required.max(inputs as isize - delta);
+        delta += outputs as isize - inputs as isize;
+    }
+    (delta, required as usize)
+}
+
+/// Runs `block` on an abstract stack. Returns the stack at the end of the block and the
+/// target of the final jump if it's known, or `None` if the stack underflows.
+fn run_block(block: &BasicBlock, mut stack: Vec<Value>) -> Option<(Vec<Value>, Value)> {
+    let mut target = None;
+    for instruction in &block.instructions {
+        let Some(opcode) = OpCode::new(instruction.opcode) else {
+            break;
+        };
+        let (inputs, outputs) = opcode.stack_items();
+        if stack.len() < inputs {
+            return None;
+        }
+        let size = stack.len();
+        match opcode {
+            _ if opcode.is_push() => {
+                stack.push(Some(U256::from_big_endian(&instruction.immediate)));
+            }
+            _ if opcode.is_dup() => stack.push(stack[size - opcode.data_index()]),
+            _ if opcode.is_swap() => stack.swap(size - 1, size - 1 - opcode.data_index()),
+            OpCode::Pc => stack.push(Some(U256::from(instruction.pc))),
+            _ => {
+                if matches!(opcode, OpCode::Jump | OpCode::Jumpi) {
+                    target = stack[size - 1];
+                }
+                stack.truncate(size - inputs);
+                stack.extend(std::iter::repeat_n(None, outputs));
+            }
+        }
+    }
+    Some((stack, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::ControlFlowGraph;
+    use crate::assembler::assemble;
+
+    #[test]
+    fn blocks_split_and_static_jumps_resolve() {
+        let code = assemble(
+            "
+            PUSH1 1 PUSH @a JUMPI   // 0x00
+            PUSH @b JUMP            // 0x05
+            a: JUMPDEST PUSH1 0 POP // 0x08
+            b: JUMPDEST STOP        // 0x0c
+            INVALID                 // 0x0e
+            ",
+        )
+        .unwrap();
+        let graph = ControlFlowGraph::new(&code);
+        let starts: Vec<usize> = graph.blocks.keys().copied().collect();
+        assert_eq!(starts, vec![0x00, 0x05, 0x08, 0x0c, 0x0e]);
+
+        let block = |start| &graph.blocks[&start];
+        assert_eq!(block(0x00).jump_targets, BTreeSet::from([0x08]));
+        assert_eq!(block(0x00).fallthrough, Some(0x05));
+        assert_eq!(block(0x05).jump_targets, BTreeSet::from([0x0c]));
+        assert_eq!(block(0x05).fallthrough, None);
+        assert_eq!(block(0x08).fallthrough, Some(0x0c));
+        assert_eq!(
+            (block(0x08).stack_delta, block(0x08).stack_required),
+            (0, 0)
+        );
+        assert_eq!(block(0x0c).fallthrough, None);
+        assert_eq!(graph.predecessors(0x0c), vec![0x05, 0x08]);
+        assert_eq!(graph.block_at(0x0a).map(|block| block.start), Some(0x08));
+
+        for start in [0x00, 0x05, 0x08, 0x0c] {
+            assert!(block(start).reachable);
+            assert!(!block(start).dynamic_jump && !block(start).invalid_jump);
+        }
+        assert!(!block(0x0e).reachable);
+    }
+
+    #[test]
+    fn return_addresses_are_followed_per_call_site() {
+        // an internal function called from two sites, returning to the address they pushed.
+        let code = assemble(
+            "
+            PUSH @ret1 PUSH @f JUMP
+            ret1: JUMPDEST PUSH @ret2 PUSH @f JUMP
+            ret2: JUMPDEST STOP
+            f: JUMPDEST JUMP
+            ",
+        )
+        .unwrap();
+        let graph = ControlFlowGraph::new(&code);
+        let function = graph.blocks.values().last().unwrap();
+        assert_eq!(function.jump_targets.len(), 2);
+        assert!(!function.dynamic_jump);
+    }
+
+    #[test]
+    fn underflows_and_unresolved_jumps_are_flagged() {
+        let code = assemble("PUSH1 1 PUSH @f JUMP  f: JUMPDEST ADD STOP").unwrap();
+        let graph = ControlFlowGraph::new(&code);
+        let function = &graph.blocks[&5];
+        assert_eq!((function.stack_delta, function.stack_required), (-1, 2));
+        assert!(function.can_underflow);
+        assert!(!graph.blocks[&0].can_underflow);
+
+        let graph = ControlFlowGraph::new(&assemble("PUSH1 0 CALLDATALOAD JUMP").unwrap());
+        assert!(graph.blocks[&0].dynamic_jump);
+        assert!(graph.blocks[&0].jump_targets.is_empty());
+
+        // 3 is the STOP, not a JUMPDEST.
+        let graph = ControlFlowGraph::new(&assemble("PUSH1 3 JUMP STOP").unwrap());
+        assert!(graph.blocks[&0].invalid_jump);
+        assert!(graph.blocks[&0].jump_targets.is_empty());
+    }
+
+    #[test]
+    fn dot_export() {
+        let code = assemble("PUSH1 1 PUSH @f JUMPI ADD  f: JUMPDEST STOP  INVALID").unwrap();
+        let dot = ControlFlowGraph::new(&code).to_dot();
+        let expected = [
+            "digraph cfg {",
+            "    node [shape=box fontname=monospace];",
+            r#"    b0 [label="block 0x0000 (stack +0, needs 0)\l0000: PUSH1 0x01\l0002: PUSH1 0x06\l0004: JUMPI\l"];"#,
+            r#"    b5 [label="block 0x0005 (stack -1, needs 2)\l0005: ADD\l" color=red];"#,
+            r#"    b6 [label="block 0x0006 (stack +0, needs 0)\l0006: JUMPDEST\l0007: STOP\l"];"#,
+            r#"    b8 [label="block 0x0008 (stack +0, needs 0)\l0008: INVALID\l" color=gray fontcolor=gray];"#,
+            "    b0 -> b6;",
+            "    b0 -> b5 [style=dashed];",
+            "    b5 -> b6 [style=dashed];",
+            "}",
+        ];
+        assert_eq!(dot.lines().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,346 @@
+use std::fmt;
+
+use crate::{
+    jumpdest::code_bitmap,
+    opcode::{mnemonic, OpCode},
+    selectors::function_entries,
+};
+
+/// The first byte of the CBOR metadata appended by solc is the header of a map.
+const CBOR_MAP_MIN: u8 = 0xa1;
+const CBOR_MAP_MAX: u8 = 0xb7;
+
+/// An opcode with its `PUSH` data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub pc: usize,
+    pub opcode: u8,
+    pub name: String,
+    /// The `PUSH` data, shorter than expected if the code ends in the middle of it.
+    pub immediate: Vec<u8>,
+}
+
+impl Instruction {
+    pub fn is_jumpdest(&self) -> bool {
+        self.opcode == OpCode::Jumpdest as u8
+    }
+
+    /// Returns true if the execution can't continue to the next instruction.
+    pub fn is_terminator(&self) -> bool {
+        match OpCode::new(self.opcode) {
+            Some(opcode) => matches!(
+                opcode,
+                OpCode::Stop
+                    | OpCode::Jump
+                    | OpCode::Return
+                    | OpCode::Revert
+                    | OpCode::Selfdestruct
+            ),
+            None => true,
+        }
+    }
+
+    /// Returns the size of the instruction in bytes.
+    pub fn size(&self) -> usize {
+        1 + self.immediate.len()
+    }
+}
+
+/// The CBOR encoded metadata that solc appends to the runtime code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    /// Where the metadata starts.
+    pub offset: usize,
+    /// The raw metadata, including the 2 bytes of its length.
+    pub bytes: Vec<u8>,
+    /// The decoded entries, e.g. `ipfs` and `solc`, with their raw values.
+    pub entries: Vec<(String, Vec<u8>)>,
+}
+
+impl Metadata {
+    /// Returns the version of the compiler, e.g. `0.8.19`.
+    pub fn solc_version(&self) -> Option<String> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key == "solc")
+            .and_then(|(_, value)| match value.as_slice() {
+                [major, minor, patch] => Some(format!("{}.{}.{}", major, minor, patch)),
+                // prerelease versions are stored as a string.
+                value => String::from_utf8(value.to_vec()).ok(),
+            })
+    }
+}
+
+/// An element of a disassembly listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Instruction(Instruction),
+    /// Bytes that can't be reached by the execution, usually data appended to the code.
+    Data {
+        pc: usize,
+        bytes: Vec<u8>,
+    },
+    Metadata(Metadata),
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Item::Instruction(instruction) if instruction.is_jumpdest() => {
+                write!(f, "{:04x}: > {}", instruction.pc, instruction.name)
+            }
+            Item::Instruction(instruction) if instruction.immediate.is_empty() => {
+                write!(f, "{:04x}:   {}", instruction.pc, instruction.name)
+            }
+            Item::Instruction(instruction) => write!(
+                f,
+                "{:04x}:   {} 0x{}",
+                instruction.pc,
+                instruction.name,
+                hex::encode(&instruction.immediate)
+            ),
+            Item::Data { pc, bytes } => write!(
+                f,
+                "{:04x}:   data ({} bytes): 0x{}",
+                pc,
+                bytes.len(),
+                hex::encode(bytes)
+            ),
+            Item::Metadata(metadata) => {
+                let entries: Vec<String> = metadata
+                    .entries
+                    .iter()
+                    .map(|(key, value)| match key.as_str() {
+                        "solc" => format!("solc {}", metadata.solc_version().unwrap_or_default()),
+                        _ => format!("{} 0x{}", key, hex::encode(value)),
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "{:04x}:   metadata ({} bytes): {}",
+                    metadata.offset,
+                    metadata.bytes.len(),
+                    entries.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Decodes every instruction of `code`, data included.
+pub fn instructions(code: &[u8]) -> Vec<Instruction> {
+    let bitmap = code_bitmap(code);
+    let mut instructions = vec![];
+    let mut pc = 0;
+    while pc < code.len() {
+        let mut end = pc + 1;
+        // the bitmap already knows which bytes are `PUSH` data.
+        while end < code.len() && !bitmap[end] {
+            end += 1;
+        }
+        instructions.push(Instruction {
+            pc,
+            opcode: code[pc],
+            name: mnemonic(code[pc]),
+            immediate: code[pc + 1..end].to_vec(),
+        });
+        pc = end;
+    }
+    instructions
+}
+
+/// Disassembles `code`. The code following an instruction that ends the execution is
+/// unreachable up to the next `JUMPDEST`, so it's reported as data, as is the metadata trailer.
+pub fn disassemble(code: &[u8]) -> Vec<Item> {
+    let metadata = find_metadata(code);
+    let end = metadata
+        .as_ref()
+        .map(|metadata| metadata.offset)
+        .unwrap_or(code.len());
+
+    let mut items = vec![];
+    let mut reachable = true;
+    for instruction in instructions(&code[..end]) {
+        if instruction.is_jumpdest() {
+            reachable = true;
+        }
+        if reachable {
+            reachable = !instruction.is_terminator();
+            items.push(Item::Instruction(instruction));
+            continue;
+        }
+        let bytes = &code[instruction.pc..instruction.pc + instruction.size()];
+        match items.last_mut() {
+            Some(Item::Data { bytes: data, .. }) => data.extend_from_slice(bytes),
+            _ => items.push(Item::Data {
+                pc: instruction.pc,
+                bytes: bytes.to_vec(),
+            }),
+        }
+    }
+    if let Some(metadata) = metadata {
+        items.push(Item::Metadata(metadata));
+    }
+    items
+}
+
+/// Finds the CBOR metadata trailer of solc: a CBOR map followed by its length on 2 bytes.
+pub fn find_metadata(code: &[u8]) -> Option<Metadata> {
+    let length_offset = code.len().checked_sub(2)?;
+    let length = u16::from_be_bytes([code[length_offset], code[length_offset + 1]]) as usize;
+    let offset = length_offset.checked_sub(length)?;
+    let cbor = &code[offset..length_offset];
+    if length == 0 || !(CBOR_MAP_MIN..=CBOR_MAP_MAX).contains(&cbor[0]) {
+        return None;
+    }
+    let entries = decode_cbor_map(cbor)?;
+    Some(Metadata {
+        offset,
+        bytes: code[offset..].to_vec(),
+        entries,
+    })
+}
+
+/// Splits creation code into its constructor and the runtime code it deploys.
+///
+/// solc copies the runtime code with `CODECOPY` from a constant offset, right after the
+/// `INVALID` opcode that ends the constructor. Returns `None` if no such offset is found.
+pub fn split_constructor(code: &[u8]) -> Option<(&[u8], &[u8])> {
+    let instructions = instructions(code);
+    for (index, instruction) in instructions.iter().enumerate() {
+        if instruction.opcode != OpCode::Codecopy as u8 {
+            continue;
+        }
+        // the offset is one of the constants pushed right before the `CODECOPY`, and a
+        // constant wider than a `usize` can't be one.
+        let candidates = instructions[index.saturating_sub(4)..index]
+            .iter()
+            .filter(|instruction| !instruction.immediate.is_empty())
+            .filter_map(|instruction| {
+                instruction
+                    .immediate
+                    .iter()
+                    .try_fold(0usize, |value, byte| {
+                        value.checked_mul(256)?.checked_add(*byte as usize)
+                    })
+            });
+        for offset in candidates {
+            if offset > 0 && offset < code.len() && code[offset - 1] == 0xfe {
+                return Some(code.split_at(offset));
+            }
+        }
+    }
+    None
+}
+
+/// Formats the disassembly of `code`, split into constructor and runtime code if it's creation code.
+/// The entries of the functions found in the selector dispatcher are annotated.
+pub fn disassembly_listing(code: &[u8]) -> String {
+    let sections = match split_constructor(code) {
+        Some((constructor, runtime)) => vec![("constructor", constructor), ("runtime", runtime)],
+        None => vec![("", code)],
+    };
+    let mut listing = String::new();
+    for (title, code) in sections {
+        if !title.is_empty() {
+            listing.push_str(&format!("{}:\n", title));
+        }
+        let functions = function_entries(code);
+        for item in disassemble(code) {
+            let function = match &item {
+                Item::Instruction(instruction) => functions
+                    .iter()
+                    .find(|function| function.pc == instruction.pc),
+                _ => None,
+            };
+            match function {
+                Some(function) => listing.push_str(&format!(
+                    "{}  ; function 0x{} ({})\n",
+                    item,
+                    hex::encode(function.selector),
+                    if function.payable {
+                        "payable"
+                    } else {
+                        "non-payable"
+                    }
+                )),
+                None => listing.push_str(&format!("{}\n", item)),
+            }
+        }
+    }
+    listing
+}
+
+/// Decodes a CBOR map whose keys are strings, keeping the raw values.
+fn decode_cbor_map(data: &[u8]) -> Option<Vec<(String, Vec<u8>)>> {
+    let size = (data[0] & 0x1f) as usize;
+    let mut offset = 1;
+    let mut entries = vec![];
+    for _ in 0..size {
+        let (key, next) = decode_cbor_item(data, offset)?;
+        let (value, next) = decode_cbor_item(data, next)?;
+        entries.push((String::from_utf8(key).ok()?, value));
+        offset = next;
+    }
+    // the map must span the whole metadata.
+    (offset == data.len()).then_some(entries)
+}
+
+/// Decodes a CBOR byte string, text string or simple value at `offset`.
+/// Returns its content and the offset of the next item.
+fn decode_cbor_item(data: &[u8], offset: usize) -> Option<(Vec<u8>, usize)> {
+    let header = *data.get(offset)?;
+    let major_type = header >> 5;
+    let info = (header & 0x1f) as usize;
+    match major_type {
+        // byte and text strings.
+        2 | 3 => {
+            let (length, start) = match info {
+                0..=23 => (info, offset + 1),
+                24 => (*data.get(offset + 1)? as usize, offset + 2),
+                25 => {
+                    let length =
+                        u16::from_be_bytes([*data.get(offset + 1)?, *data.get(offset + 2)?]);
+                    (length as usize, offset + 3)
+                }
+                _ => return None,
+            };
+            let content = data.get(start..start.checked_add(length)?)?;
+            Some((content.to_vec(), start + length))
+        }
+        // booleans, e.g. `experimental`.
+        7 => Some((vec![header], offset + 1)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    #[test]
+    fn constructors_end_before_the_copied_runtime() {
+        let code = assemble(
+            "
+            PUSH1 3 DUP1 PUSH1 10 PUSH0 CODECOPY PUSH0 RETURN INVALID
+            PUSH1 1 STOP
+            ",
+        )
+        .unwrap();
+        let (constructor, runtime) = split_constructor(&code).unwrap();
+        assert_eq!(constructor.last(), Some(&0xfe));
+        assert_eq!(runtime, assemble("PUSH1 1 STOP").unwrap());
+
+        let listing = disassembly_listing(&code);
+        assert!(listing.starts_with("constructor:\n"));
+        assert!(listing.contains("runtime:\n"));
+    }
+
+    #[test]
+    fn wide_codecopy_offsets_are_ignored() {
+        let code = hex::decode(format!("7f{}39fe6001", "ff".repeat(32))).unwrap();
+        assert_eq!(split_constructor(&code), None);
+        assert!(!disassembly_listing(&code).contains("runtime:"));
+    }
+}
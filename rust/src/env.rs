@@ -0,0 +1,87 @@
+use crate::{block_data::BlockData, tx_data::TxData};
+
+/// The execution environment of a transaction. It's read-only and shared by every frame,
+/// so that nested calls see the same block and transaction context of the top-level call.
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    pub block: BlockData,
+    /// The sender of the transaction.
+    pub origin: Vec<u8>,
+    pub gasprice: Vec<u8>,
+}
+
+impl Env {
+    pub fn new(block: BlockData, tx_data: &TxData) -> Env {
+        Env {
+            block,
+            origin: tx_data.origin.clone(),
+            gasprice: tx_data.gasprice.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use primitive_types::U256;
+
+    use crate::{
+        address::Address, assembler::assemble, block_data::BlockData, builder::Tx, evm::Evm,
+        state_data::AddressData, utility::contract_address,
+    };
+
+    /// Stores TIMESTAMP, NUMBER, CHAINID, GASPRICE and ORIGIN in the slots 0 to 4.
+    const READ_ENV: &str = "
+        TIMESTAMP PUSH1 0 SSTORE  NUMBER PUSH1 1 SSTORE  CHAINID PUSH1 2 SSTORE
+        GASPRICE PUSH1 3 SSTORE  ORIGIN PUSH1 4 SSTORE  STOP
+    ";
+
+    #[test]
+    fn nested_frames_read_the_transaction_env() {
+        // 21 bytes, stored at the end of the first memory word.
+        let init_code = hex::encode(assemble(READ_ENV).unwrap());
+        // CALLs and DELEGATECALLs 0xbb, then runs the same code as init code.
+        let code = assemble(&format!(
+            "
+            PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP
+            PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS DELEGATECALL POP
+            PUSH21 0x{init_code} PUSH1 0 MSTORE  PUSH1 21 PUSH1 11 PUSH1 0 CREATE
+            "
+        ))
+        .unwrap();
+        let callee = AddressData {
+            code: assemble(READ_ENV).unwrap(),
+            ..AddressData::default()
+        };
+        let block = BlockData {
+            timestamp: 1234.into(),
+            number: 7.into(),
+            chainid: 5.into(),
+            ..BlockData::default()
+        };
+        let result = Evm::builder()
+            .code(code)
+            .tx(Tx {
+                from: Address::from(U256::from(0xdd)),
+                to: Address::from(U256::from(0xaa)),
+                origin: Some(Address::from(U256::from(0xee))),
+                gas_price: 3.into(),
+                ..Tx::default()
+            })
+            .block(block)
+            .account(Address::from(U256::from(0xbb)), callee)
+            .build()
+            .run();
+        assert!(result.success);
+        let created = contract_address(0xaa.into(), 0);
+        assert_eq!(result.stack, vec![created]);
+
+        // the callee, the caller through DELEGATECALL and the created contract.
+        for address in [0xbb.into(), 0xaa.into(), created] {
+            let slots: Vec<U256> = (0..5)
+                .map(|slot| result.storage.load_word(address, slot.into()))
+                .collect();
+            let expected: Vec<U256> = [1234, 7, 5, 3, 0xee].map(U256::from).to_vec();
+            assert_eq!(slots, expected, "{:#x}", address);
+        }
+    }
+}
@@ -1,9 +1,16 @@
+use primitive_types::U256;
 use thiserror::Error;
 
+use crate::revert_reason::RevertReason;
+
 #[derive(Debug, Error)]
 pub enum ExecutionError {
     #[error("halt the execution")]
     Halt,
+    #[error("return opcode")]
+    Return,
+    #[error("selfdestruct opcode")]
+    SelfDestruct,
     #[error("there are not enough items in the stack")]
     StackUnderflow,
     #[error("there are not enough items in the code to be pushed")]
@@ -20,6 +27,134 @@ pub enum ExecutionError {
     Revert,
     #[error("execution is read only")]
     ReadOnly,
+    #[error("return data out of bounds")]
+    ReturnDataOutOfBounds,
     #[error("contract address already present")]
     ContractAddressCollision,
+    #[error("out of gas")]
+    OutOfGas,
+}
+
+/// The reason of an exceptional halt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum HaltReason {
+    #[error("stack underflow")]
+    StackUnderflow,
+    #[error("stack overflow")]
+    StackOverflow,
+    #[error("invalid jump destination")]
+    InvalidJump,
+    #[error("invalid opcode")]
+    InvalidOpcode,
+    #[error("not enough code left for the push data")]
+    InsufficientCodeItems,
+    #[error("integer overflow")]
+    IntegerOverflow,
+    #[error("return data out of bounds")]
+    ReturnDataOutOfBounds,
+    #[error("out of gas")]
+    OutOfGas,
+    #[error("state modification in a static call")]
+    StaticCallViolation,
+    #[error("contract address collision")]
+    CreateCollision,
+}
+
+/// An error found while assembling, with the line where it happened.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AssemblerError {
+    #[error("line {line}: unknown mnemonic `{mnemonic}`")]
+    UnknownMnemonic { line: usize, mnemonic: String },
+    #[error("line {line}: missing operand of `{mnemonic}`")]
+    MissingOperand { line: usize, mnemonic: String },
+    #[error("line {line}: invalid operand `{operand}`")]
+    InvalidOperand { line: usize, operand: String },
+    #[error("line {line}: `{operand}` doesn't fit in {size} bytes")]
+    OperandTooLarge {
+        line: usize,
+        operand: String,
+        size: usize,
+    },
+    #[error("line {line}: unknown label `{label}`")]
+    UnknownLabel { line: usize, label: String },
+    #[error("line {line}: label `{label}` is already defined")]
+    DuplicateLabel { line: usize, label: String },
+}
+
+/// An error found while parsing an ABI, or encoding and decoding values with it.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AbiError {
+    #[error("invalid type `{0}`")]
+    InvalidType(String),
+    #[error("invalid signature `{0}`")]
+    InvalidSignature(String),
+    #[error("invalid JSON ABI: {0}")]
+    InvalidJson(String),
+    #[error("expected {expected} values, got {actual}")]
+    WrongValueCount { expected: usize, actual: usize },
+    #[error("value {index} is not a `{kind}`")]
+    TypeMismatch { index: usize, kind: String },
+    #[error("the selector doesn't match `{0}`")]
+    SelectorMismatch(String),
+    #[error("the data is too short or malformed")]
+    InvalidData,
+}
+
+/// An error found while decoding RLP data, or a raw transaction encoded with it.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RlpError {
+    #[error("unexpected end of the RLP data")]
+    UnexpectedEnd,
+    #[error("trailing bytes after the RLP item")]
+    TrailingBytes,
+    #[error("expected an RLP string")]
+    ExpectedBytes,
+    #[error("expected an RLP list")]
+    ExpectedList,
+    #[error("integer too large")]
+    IntegerTooLarge,
+    #[error("too many nested RLP lists")]
+    TooDeep,
+    #[error("unsupported transaction type {0}")]
+    UnsupportedTxType(u8),
+}
+
+/// An error found while loading the accounts of a `genesis.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum GenesisError {
+    #[error("invalid JSON: {0}")]
+    InvalidJson(String),
+    #[error("expected an object of accounts")]
+    ExpectedObject,
+    #[error("invalid address `{0}`")]
+    InvalidAddress(String),
+    #[error("duplicate address `{0}`")]
+    DuplicateAddress(String),
+    #[error("invalid `{field}` of account {address:#x}")]
+    InvalidField { address: U256, field: String },
+}
+
+/// Why the dev node rejected a transaction or a call.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum NodeError {
+    #[error("invalid chain id {0}")]
+    InvalidChainId(u64),
+    #[error("nonce {actual} doesn't match the account nonce {expected}")]
+    InvalidNonce { expected: u64, actual: u64 },
+    #[error("intrinsic gas too low: have {limit}, want {intrinsic}")]
+    IntrinsicGasTooLow { limit: u64, intrinsic: u64 },
+    #[error("gas limit {0} exceeds the block gas limit")]
+    GasLimitTooHigh(u64),
+    #[error("insufficient funds for gas * price + value")]
+    InsufficientFunds,
+    #[error("contract address collision")]
+    CreateCollision,
+    #[error("account {0:#x} is not impersonated")]
+    NotImpersonated(U256),
+    #[error("timestamp {timestamp} is not after the last block timestamp {parent}")]
+    TimestampTooOld { timestamp: U256, parent: U256 },
+    #[error("execution reverted: {}", RevertReason::decode(.0))]
+    Reverted(Vec<u8>),
+    #[error("{0}")]
+    Halted(HaltReason),
 }
@@ -1,27 +1,35 @@
 use crate::{
-    block_data::BlockData,
-    errors::ExecutionError,
+    builder::EvmBuilder,
+    env::Env,
+    errors::{ExecutionError, HaltReason},
+    gas::{dynamic_gas, memory_expansion_gas, memory_size, Gas},
+    inspector::{
+        inspect_call, inspect_call_end, CallInputs, CallScheme, FrameOutcome, SharedInspector,
+        TraceStep,
+    },
     memory::Memory,
-    opcode::OpCode,
+    opcode::{mnemonic, OpCode},
+    revert_reason::RevertReason,
     state_data::State,
     storage::Storage,
     tx_data::TxData,
     utility::{
-        add, addmod, and, balance, byte, call, calldataload, copy_data_to_memory, create,
-        delegatecall, div, duplicate_data, eq, exp, extcodecopy, extcodehash, extcodesize, gt,
-        iszero, jump, logx, lt, mload, mod_fn, msize, mstore, mstore8, mul, mulmod, not, or, pop,
-        push, push_data, push_data_size, push_from_big_endian, return_func, revert, sar, sdiv,
-        selfbalance, selfdestruct, sgt, sha_3, shl, shr, sign_extend, sload, slt, smod, sstore,
-        staticcall, sub, swap_data, xor,
+        add, addmod, and, balance, blockhash, byte, call, calldataload, copy_data_to_memory,
+        create, delegatecall, div, duplicate_data, eq, exp, extcodecopy, extcodehash, extcodesize,
+        gt, iszero, jump, logx, lt, mload, mod_fn, msize, mstore, mstore8, mul, mulmod, not, or,
+        pop, push, push_data, push_data_size, push_from_big_endian, return_func, returndatacopy,
+        revert, sar, sdiv, selfbalance, selfdestruct, sgt, sha_3, shl, shr, sign_extend, sload,
+        slt, smod, sstore, staticcall, sub, swap_data, xor,
     },
-    Log,
+    EvmResult, Log,
 };
 use primitive_types::U256;
+use std::rc::Rc;
 
 pub struct Evm {
     code: Box<[u8]>,
     tx_data: TxData,
-    block_data: BlockData,
+    env: Rc<Env>,
     state: State,
     storage: Storage,
     stack: Vec<U256>,
@@ -31,13 +39,18 @@ pub struct Evm {
     memory: Memory,
     limit: usize,
     read_only: bool,
+    gas: Gas,
+    /// The call depth of this frame, 1 for the transaction itself.
+    depth: usize,
+    inspector: SharedInspector,
 }
 
 impl Evm {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         code: Box<[u8]>,
         tx_data: TxData,
-        block_data: BlockData,
+        env: Rc<Env>,
         state: State,
         storage: Storage,
         stack: Vec<U256>,
@@ -47,11 +60,12 @@ impl Evm {
         memory: Memory,
         limit: usize,
         read_only: bool,
+        gas: Gas,
     ) -> Self {
         Self {
             code,
             tx_data,
-            block_data,
+            env,
             state,
             storage,
             stack,
@@ -61,26 +75,212 @@ impl Evm {
             memory,
             limit,
             read_only,
+            gas,
+            depth: 1,
+            inspector: None,
+        }
+    }
+
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Reports the execution of this frame and its sub calls to `inspector`.
+    pub fn with_inspector(mut self, inspector: SharedInspector) -> Self {
+        self.inspector = inspector;
+        self
+    }
+
+    /// Returns a builder of the top-level frame of a transaction.
+    pub fn builder() -> EvmBuilder {
+        EvmBuilder::new()
+    }
+
+    /// Runs this frame as the transaction itself, reporting it to the inspector as the outermost
+    /// call. If the execution fails, the state is left as it was.
+    pub fn run(mut self) -> EvmResult {
+        let inputs = CallInputs {
+            scheme: CallScheme::Call,
+            caller: U256::from_big_endian(&self.tx_data.from),
+            target: U256::from_big_endian(&self.tx_data.to),
+            code_address: U256::from_big_endian(&self.tx_data.to),
+            value: U256::from_big_endian(&self.tx_data.value),
+            input: self.tx_data.data.clone(),
+            gas: self.gas,
+            depth: self.depth,
+            is_static: self.read_only,
+        };
+        let (initial_state, initial_storage) = (self.state.clone(), self.storage.clone());
+        let inspector = self.inspector.clone();
+
+        let outcome = match inspect_call(&inspector, &inputs) {
+            Some(outcome) => outcome,
+            None => FrameOutcome {
+                result: self.execute(),
+                output: self.return_data(),
+                gas: self.gas(),
+            },
+        };
+        let FrameOutcome {
+            result,
+            output,
+            gas,
+        } = inspect_call_end(&inspector, &inputs, outcome);
+        let revert_reason = match &result {
+            ExecutionResult::Revert { data } => Some(RevertReason::decode(data)),
+            _ => None,
+        };
+        let (state, storage) = if result.is_success() {
+            (self.state, self.storage)
+        } else {
+            (initial_state, initial_storage)
+        };
+
+        EvmResult {
+            stack: self.stack.iter().rev().cloned().collect(),
+            logs: self.logs.iter().rev().cloned().collect(),
+            success: result.is_success(),
+            ret: output,
+            gas_used: gas.spent(),
+            gas,
+            result,
+            revert_reason,
+            state,
+            storage,
         }
     }
 
     pub fn execute(&mut self) -> ExecutionResult {
         let mut pc = 0;
         while pc < self.code.len() {
-            if let Some(opcode) = OpCode::new(self.code[pc]) {
-                match self.transact(&mut pc, opcode) {
-                    Ok(_) => {
-                        // move the pc to the next instruction
-                        pc += 1;
-                    }
-                    Err(ExecutionError::Halt) => return ExecutionResult::Halt,
-                    Err(_) => return ExecutionResult::Revert,
+            let opcode_pc = pc;
+            let opcode = self.code[pc];
+            let result = match OpCode::new(opcode) {
+                Some(opcode) => self.step(&mut pc, opcode),
+                None => {
+                    self.inspect_step(opcode_pc, 0, false);
+                    Err(ExecutionError::InvalidOpcode)
+                }
+            };
+            match result {
+                Ok(_) => {
+                    // move the pc to the next instruction
+                    pc += 1;
                 }
-            } else {
-                return ExecutionResult::Revert;
+                Err(error) => return self.result_from_error(error, opcode_pc, opcode),
             }
         }
-        ExecutionResult::Success
+        // running out of code is the same as executing STOP.
+        ExecutionResult::Success {
+            reason: SuccessReason::Stop,
+        }
+    }
+
+    /// Executes a single opcode, charging its gas.
+    fn step(&mut self, pc: &mut usize, opcode: OpCode) -> Result<(), ExecutionError> {
+        let opcode_pc = *pc;
+        let address = U256::from_big_endian(&self.tx_data.to);
+        let (dynamic_gas, refund) = dynamic_gas(&opcode, &self.stack, &self.storage, address);
+        // the memory expansion is paid before the opcode runs, so that it never allocates
+        // memory it can't pay for.
+        let memory_size = memory_size(&opcode, &self.stack);
+        let memory_gas = memory_expansion_gas(self.memory.store.len(), memory_size);
+        let cost = opcode
+            .static_gas()
+            .saturating_add(dynamic_gas)
+            .saturating_add(memory_gas.unwrap_or(u64::MAX));
+        self.inspect_step(opcode_pc, cost, false);
+
+        let used = self.gas.used();
+        let result = memory_gas
+            .ok_or(ExecutionError::OutOfGas)
+            .and_then(|_| self.gas.record_cost(cost))
+            .and_then(|_| {
+                self.gas.record_refund(refund);
+                if memory_size > self.memory.store.len().into() {
+                    self.memory.resize(0, memory_size.as_usize())?;
+                }
+                self.transact(pc, opcode)
+            });
+
+        self.inspect_step(opcode_pc, self.gas.used().saturating_sub(used), true);
+        result
+    }
+
+    /// Reports the state of the frame to the inspector, before or after the opcode at `pc`
+    /// is executed.
+    fn inspect_step(&self, pc: usize, gas_cost: u64, executed: bool) {
+        let Some(inspector) = &self.inspector else {
+            return;
+        };
+        let step = TraceStep {
+            address: U256::from_big_endian(&self.tx_data.to),
+            pc,
+            opcode: self.code[pc],
+            op_name: mnemonic(self.code[pc]),
+            gas: self.gas.remaining(),
+            gas_cost,
+            memory_size: self.memory.store.len(),
+            memory: &self.memory.store,
+            stack: &self.stack,
+            storage: &self.storage,
+            depth: self.depth,
+            return_data: &self.last_return_data,
+            refund: self.gas.refunded(),
+        };
+        if executed {
+            inspector.borrow_mut().step_end(&step);
+        } else {
+            inspector.borrow_mut().step(&step);
+        }
+    }
+
+    /// Converts the error that stopped the execution at `pc` into the result of the execution.
+    /// Exceptional halts consume all the gas left and discard the return data, while a revert
+    /// only discards the gas refund.
+    fn result_from_error(
+        &mut self,
+        error: ExecutionError,
+        pc: usize,
+        opcode: u8,
+    ) -> ExecutionResult {
+        let reason = match error {
+            ExecutionError::Halt => {
+                return ExecutionResult::Success {
+                    reason: SuccessReason::Stop,
+                }
+            }
+            ExecutionError::Return => {
+                return ExecutionResult::Success {
+                    reason: SuccessReason::Return,
+                }
+            }
+            ExecutionError::SelfDestruct => {
+                return ExecutionResult::Success {
+                    reason: SuccessReason::SelfDestruct,
+                }
+            }
+            ExecutionError::Revert => {
+                self.gas.clear_refund();
+                return ExecutionResult::Revert {
+                    data: self.return_data.clone(),
+                };
+            }
+            ExecutionError::StackUnderflow => HaltReason::StackUnderflow,
+            ExecutionError::StackOverflow => HaltReason::StackOverflow,
+            ExecutionError::NotValidJumpDestination => HaltReason::InvalidJump,
+            ExecutionError::InvalidOpcode => HaltReason::InvalidOpcode,
+            ExecutionError::InsufficientCodeItems => HaltReason::InsufficientCodeItems,
+            ExecutionError::IntegerOverflow => HaltReason::IntegerOverflow,
+            ExecutionError::ReturnDataOutOfBounds => HaltReason::ReturnDataOutOfBounds,
+            ExecutionError::ReadOnly => HaltReason::StaticCallViolation,
+            ExecutionError::ContractAddressCollision => HaltReason::CreateCollision,
+            ExecutionError::OutOfGas => HaltReason::OutOfGas,
+        };
+        self.gas.burn_remaining();
+        self.return_data.clear();
+        ExecutionResult::Halt { reason, pc, opcode }
     }
 
     pub fn transact(&mut self, pc: &mut usize, opcode: OpCode) -> Result<(), ExecutionError> {
@@ -278,8 +478,13 @@ impl Evm {
                 Ok(())
             }
             OpCode::Gas => {
-                // it's not supported on this EVM. Always returns U256::MAX.
-                push(&mut self.stack, U256::max_value(), self.limit)?;
+                // without a gas limit, the gas left is always U256::MAX.
+                let gas = if self.gas.is_unlimited() {
+                    U256::max_value()
+                } else {
+                    self.gas.remaining().into()
+                };
+                push(&mut self.stack, gas, self.limit)?;
                 Ok(())
             }
             OpCode::Jump => {
@@ -330,43 +535,53 @@ impl Evm {
                 Ok(())
             }
             OpCode::Origin => {
-                push_from_big_endian(&mut self.stack, &self.tx_data.origin, self.limit)?;
+                push_from_big_endian(&mut self.stack, &self.env.origin, self.limit)?;
                 Ok(())
             }
             OpCode::Gasprice => {
-                push_from_big_endian(&mut self.stack, &self.tx_data.gasprice, self.limit)?;
+                push_from_big_endian(&mut self.stack, &self.env.gasprice, self.limit)?;
                 Ok(())
             }
             OpCode::Basfee => {
-                push_from_big_endian(&mut self.stack, &self.block_data.basefee, self.limit)?;
+                push(&mut self.stack, self.env.block.basefee, self.limit)?;
+                Ok(())
+            }
+            OpCode::Blobbasefee => {
+                let fee = self.env.block.blob_base_fee();
+                push(
+                    &mut self.stack,
+                    fee.ok_or(ExecutionError::IntegerOverflow)?,
+                    self.limit,
+                )?;
                 Ok(())
             }
             OpCode::Coinbase => {
-                push_from_big_endian(&mut self.stack, &self.block_data.coinbase, self.limit)?;
+                push(&mut self.stack, self.env.block.coinbase.into(), self.limit)?;
                 Ok(())
             }
             OpCode::Timestamp => {
-                push_from_big_endian(&mut self.stack, &self.block_data.timestamp, self.limit)?;
+                push(&mut self.stack, self.env.block.timestamp, self.limit)?;
                 Ok(())
             }
             OpCode::Number => {
-                push_from_big_endian(&mut self.stack, &self.block_data.number, self.limit)?;
+                push(&mut self.stack, self.env.block.number, self.limit)?;
                 Ok(())
             }
             OpCode::Difficulty => {
-                push_from_big_endian(&mut self.stack, &self.block_data.difficulty, self.limit)?;
+                let difficulty = self.env.block.difficulty_or_prevrandao();
+                push(&mut self.stack, difficulty, self.limit)?;
                 Ok(())
             }
             OpCode::Gaslimit => {
-                push_from_big_endian(&mut self.stack, &self.block_data.gaslimit, self.limit)?;
+                push(&mut self.stack, self.env.block.gaslimit, self.limit)?;
                 Ok(())
             }
             OpCode::Chainid => {
-                push_from_big_endian(&mut self.stack, &self.block_data.chainid, self.limit)?;
+                push(&mut self.stack, self.env.block.chainid, self.limit)?;
                 Ok(())
             }
             OpCode::Blockhash => {
-                // not used in this test suite, can return 0.
+                blockhash(&mut self.stack, &self.env.block, self.limit)?;
                 Ok(())
             }
             OpCode::Balance => {
@@ -436,11 +651,14 @@ impl Evm {
                     &mut self.logs,
                     self.read_only,
                 )?;
+                if let (Some(inspector), Some(log)) = (&self.inspector, self.logs.last()) {
+                    inspector.borrow_mut().log(log);
+                }
                 Ok(())
             }
             OpCode::Return => {
                 return_func(&mut self.stack, &mut self.memory, &mut self.return_data)?;
-                Err(ExecutionError::Halt)
+                Err(ExecutionError::Return)
             }
             OpCode::Revert => {
                 revert(&mut self.stack, &mut self.memory, &mut self.return_data)?;
@@ -453,8 +671,12 @@ impl Evm {
                     &mut self.state,
                     &mut self.storage,
                     &self.tx_data.to,
-                    &self.tx_data.origin,
+                    &self.env,
                     &mut self.last_return_data,
+                    &mut self.logs,
+                    &mut self.gas,
+                    self.depth,
+                    &self.inspector,
                     self.limit,
                     self.read_only,
                 )?;
@@ -465,7 +687,7 @@ impl Evm {
                 Ok(())
             }
             OpCode::Returndatacopy => {
-                copy_data_to_memory(&mut self.stack, &mut self.memory, &self.last_return_data)?;
+                returndatacopy(&mut self.stack, &mut self.memory, &self.last_return_data)?;
                 Ok(())
             }
             OpCode::Delegatecall => {
@@ -476,9 +698,13 @@ impl Evm {
                     &mut self.storage,
                     &self.tx_data.to,
                     &self.tx_data.from,
-                    &self.tx_data.origin,
+                    &self.env,
                     &self.tx_data.value,
                     &mut self.last_return_data,
+                    &mut self.logs,
+                    &mut self.gas,
+                    self.depth,
+                    &self.inspector,
                     self.limit,
                 )?;
                 Ok(())
@@ -490,9 +716,13 @@ impl Evm {
                     &mut self.state,
                     &mut self.storage,
                     &self.tx_data.to,
-                    &self.tx_data.origin,
+                    &self.env,
                     &self.tx_data.value,
                     &mut self.last_return_data,
+                    &mut self.logs,
+                    &mut self.gas,
+                    self.depth,
+                    &self.inspector,
                     self.limit,
                 )?;
                 Ok(())
@@ -504,21 +734,33 @@ impl Evm {
                     &mut self.state,
                     &mut self.storage,
                     &self.tx_data.to,
-                    &self.tx_data.origin,
+                    &self.env,
                     &mut self.last_return_data,
+                    &mut self.logs,
+                    &mut self.gas,
+                    self.depth,
+                    &self.inspector,
                     self.limit,
                     self.read_only,
                 )?;
                 Ok(())
             }
             OpCode::Selfdestruct => {
+                let address = U256::from_big_endian(&self.tx_data.to);
+                let beneficiary = self.stack.last().copied();
+                let value = self.state.get_balance(address);
                 selfdestruct(
                     &mut self.stack,
                     &mut self.state,
                     &self.tx_data.to,
                     self.read_only,
                 )?;
-                Ok(())
+                if let (Some(inspector), Some(beneficiary)) = (&self.inspector, beneficiary) {
+                    inspector
+                        .borrow_mut()
+                        .selfdestruct(address, beneficiary, value);
+                }
+                Err(ExecutionError::SelfDestruct)
             }
         }
     }
@@ -528,15 +770,24 @@ impl Evm {
         self.stack.iter().rev().cloned().collect()
     }
 
-    /// Returns the logs at the end of execution.
+    /// Returns the logs at the end of execution, the last emitted first.
     pub fn logs(&self) -> Vec<Log> {
         self.logs.iter().rev().cloned().collect()
     }
 
+    /// Returns the logs in the order they were emitted.
+    pub(crate) fn emitted_logs(&self) -> Vec<Log> {
+        self.logs.clone()
+    }
+
     pub fn return_data(&self) -> Vec<u8> {
         self.return_data.clone()
     }
 
+    pub fn gas(&self) -> Gas {
+        self.gas
+    }
+
     pub fn state(&self) -> State {
         self.state.clone()
     }
@@ -546,8 +797,99 @@ impl Evm {
     }
 }
 
+/// The outcome of an execution.
+#[derive(Debug, Clone, PartialEq)]
 pub enum ExecutionResult {
-    Success,
-    Revert,
-    Halt,
+    /// The execution ended successfully.
+    Success { reason: SuccessReason },
+    /// The execution was stopped by the `REVERT` opcode.
+    Revert { data: Vec<u8> },
+    /// The execution was stopped by an exceptional condition, at `pc` while executing `opcode`.
+    Halt {
+        reason: HaltReason,
+        pc: usize,
+        opcode: u8,
+    },
+}
+
+impl ExecutionResult {
+    pub fn is_success(&self) -> bool {
+        matches!(self, ExecutionResult::Success { .. })
+    }
+}
+
+/// The way a successful execution ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuccessReason {
+    Stop,
+    Return,
+    SelfDestruct,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExecutionResult, SuccessReason};
+    use crate::{assembler::assemble, builder::Tx, errors::HaltReason, evm::Evm};
+
+    fn run(code: &str, gas_limit: Option<u64>) -> (ExecutionResult, u64) {
+        let result = Evm::builder()
+            .code(assemble(code).unwrap())
+            .tx(Tx {
+                gas_limit,
+                ..Tx::default()
+            })
+            .build()
+            .run();
+        (result.result, result.gas_used)
+    }
+
+    fn halt(reason: HaltReason, pc: usize, opcode: u8) -> ExecutionResult {
+        ExecutionResult::Halt { reason, pc, opcode }
+    }
+
+    #[test]
+    fn successes_report_how_they_ended() {
+        let success = |reason| ExecutionResult::Success { reason };
+        assert_eq!(run("PUSH1 1", None).0, success(SuccessReason::Stop));
+        assert_eq!(run("PUSH1 1 STOP", None).0, success(SuccessReason::Stop));
+        assert_eq!(
+            run("PUSH1 0 PUSH1 0 RETURN", None).0,
+            success(SuccessReason::Return)
+        );
+        assert_eq!(
+            run("PUSH1 0 SELFDESTRUCT", None).0,
+            success(SuccessReason::SelfDestruct)
+        );
+    }
+
+    #[test]
+    fn reverts_keep_their_data_and_gas() {
+        let (result, gas_used) = run("PUSH1 7 PUSH1 0 MSTORE8  PUSH1 1 PUSH1 0 REVERT", Some(100));
+        assert_eq!(result, ExecutionResult::Revert { data: vec![7] });
+        assert!(gas_used < 100);
+    }
+
+    #[test]
+    fn halts_report_the_reason_and_where_it_happened() {
+        assert_eq!(
+            run("PUSH1 1 INVALID", None).0,
+            halt(HaltReason::InvalidOpcode, 2, 0xfe)
+        );
+        assert_eq!(
+            run("PUSH1 1 ADD", None).0,
+            halt(HaltReason::StackUnderflow, 2, 0x01)
+        );
+        assert_eq!(
+            run("PUSH1 1 PUSH1 6 JUMP  JUMPDEST", None).0,
+            halt(HaltReason::InvalidJump, 4, 0x56)
+        );
+        assert_eq!(
+            run(&format!("PUSH32 0x{} JUMP", "ff".repeat(32)), None).0,
+            halt(HaltReason::InvalidJump, 33, 0x56)
+        );
+        // the second PUSH1 costs 3 gas, with 2 left.
+        let (result, gas_used) = run("PUSH1 1 PUSH1 2 ADD", Some(5));
+        assert_eq!(result, halt(HaltReason::OutOfGas, 2, 0x60));
+        assert_eq!(gas_used, 5);
+    }
 }
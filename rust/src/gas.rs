@@ -0,0 +1,336 @@
+use primitive_types::U256;
+
+use crate::{errors::ExecutionError, opcode::OpCode, storage::Storage};
+
+/// Gas given for free to the callee when a `CALL` transfers value.
+pub const CALL_STIPEND: u64 = 2300;
+
+/// Maximum refund is `gas_used / MAX_REFUND_QUOTIENT` (EIP-3529).
+pub const MAX_REFUND_QUOTIENT: u64 = 5;
+
+const SSTORE_SET: u64 = 20000;
+const SSTORE_RESET: u64 = 5000;
+const SSTORE_CLEARS_REFUND: u64 = 4800;
+const CALL_VALUE_TRANSFER: u64 = 9000;
+const COPY_WORD: u64 = 3;
+const SHA3_WORD: u64 = 6;
+const LOG_DATA_BYTE: u64 = 8;
+const EXP_BYTE: u64 = 50;
+const INITCODE_WORD: u64 = 2;
+const CODE_DEPOSIT_BYTE: u64 = 200;
+const TX_BASE: u64 = 21000;
+const TX_CREATE: u64 = 32000;
+const TX_DATA_ZERO: u64 = 4;
+const TX_DATA_NON_ZERO: u64 = 16;
+
+/// Gas accounting of a single frame.
+///
+/// An unlimited meter still records the gas used, but never runs out of gas: the `GAS` opcode
+/// returns `U256::MAX` and sub calls get an unlimited meter too, whatever gas they ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gas {
+    limit: u64,
+    used: u64,
+    refunded: u64,
+    unlimited: bool,
+}
+
+impl Gas {
+    pub fn new(limit: u64) -> Gas {
+        Gas {
+            limit,
+            used: 0,
+            refunded: 0,
+            unlimited: false,
+        }
+    }
+
+    pub fn unlimited() -> Gas {
+        Gas {
+            limit: u64::MAX,
+            used: 0,
+            refunded: 0,
+            unlimited: true,
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.unlimited
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.limit - self.used
+    }
+
+    pub fn refunded(&self) -> u64 {
+        self.refunded
+    }
+
+    /// Returns the gas spent by a transaction once the refund (capped by EIP-3529) is applied.
+    pub fn spent(&self) -> u64 {
+        self.used - std::cmp::min(self.refunded, self.used / MAX_REFUND_QUOTIENT)
+    }
+
+    pub fn record_cost(&mut self, cost: u64) -> Result<(), ExecutionError> {
+        let used = self.used.saturating_add(cost);
+        if used > self.limit {
+            self.used = self.limit;
+            return Err(ExecutionError::OutOfGas);
+        }
+        self.used = used;
+        Ok(())
+    }
+
+    pub fn record_refund(&mut self, refund: u64) {
+        self.refunded = self.refunded.saturating_add(refund);
+    }
+
+    /// Consumes all the gas left, as it happens on exceptional halts.
+    pub fn burn_remaining(&mut self) {
+        if !self.unlimited {
+            self.used = self.limit;
+        }
+        self.refunded = 0;
+    }
+
+    /// Refunds are discarded when a frame reverts.
+    pub fn clear_refund(&mut self) {
+        self.refunded = 0;
+    }
+
+    /// Reserves the gas of a sub call, giving it at most all but one 64th of the gas left
+    /// (EIP-150), and returns the meter of the sub call. The stipend is given for free.
+    pub fn reserve_call_gas(
+        &mut self,
+        requested: U256,
+        stipend: u64,
+    ) -> Result<Gas, ExecutionError> {
+        if self.unlimited {
+            return Ok(Gas::unlimited());
+        }
+        let available = self.remaining() - self.remaining() / 64;
+        let forwarded = if requested > available.into() {
+            available
+        } else {
+            requested.as_u64()
+        };
+        self.record_cost(forwarded)?;
+        Ok(Gas::new(forwarded + stipend))
+    }
+
+    /// Gives back the gas left by a sub call, and its refund if the sub call succeeded.
+    pub fn return_call_gas(&mut self, child: &Gas, success: bool) {
+        if self.unlimited {
+            self.used = self.used.saturating_add(child.used);
+        } else {
+            self.used = self.used.saturating_sub(child.remaining());
+        }
+        if success {
+            self.record_refund(child.refunded);
+        }
+    }
+}
+
+/// Returns the cost of expanding the memory from `current` bytes to `size` bytes, or `None` if
+/// it's so large that no gas limit could pay for it.
+pub fn memory_expansion_gas(current: usize, size: U256) -> Option<u64> {
+    if size <= current.into() {
+        return Some(0);
+    }
+    let cost = |size: U256| -> Option<u64> {
+        let words = Some(size)
+            .filter(|size| *size <= u64::MAX.into())
+            .map(words)?;
+        words
+            .checked_mul(3)?
+            .checked_add(words.checked_mul(words)? / 512)
+    };
+    Some(cost(size)? - cost(current.into())?)
+}
+
+/// Returns the memory size, in bytes, needed by the operands of `opcode`. Reading or writing
+/// zero bytes needs no memory, whatever the offset.
+pub fn memory_size(opcode: &OpCode, stack: &[U256]) -> U256 {
+    let range = |offset: usize, size: usize| {
+        let size = peek(stack, size);
+        if size.is_zero() {
+            U256::zero()
+        } else {
+            peek(stack, offset).saturating_add(size)
+        }
+    };
+    match opcode {
+        OpCode::Mload | OpCode::Mstore => peek(stack, 0).saturating_add(32.into()),
+        OpCode::Mstore8 => peek(stack, 0).saturating_add(1.into()),
+        OpCode::Sha3 | OpCode::Return | OpCode::Revert => range(0, 1),
+        OpCode::Log0 | OpCode::Log1 | OpCode::Log2 | OpCode::Log3 | OpCode::Log4 => range(0, 1),
+        OpCode::Calldatacopy | OpCode::Codecopy | OpCode::Returndatacopy => range(0, 2),
+        OpCode::Extcodecopy => range(1, 3),
+        OpCode::Create => range(1, 2),
+        OpCode::Call => range(3, 4).max(range(5, 6)),
+        OpCode::Delegatecall | OpCode::Staticcall => range(2, 3).max(range(4, 5)),
+        _ => U256::zero(),
+    }
+}
+
+/// Returns the gas charged to a transaction before its execution: the base cost, the calldata
+/// and, for a creation, the creation cost and the init code words (EIP-3860).
+pub fn intrinsic_gas(data: &[u8], create: bool) -> u64 {
+    let data_gas: u64 = data
+        .iter()
+        .map(|byte| match byte {
+            0 => TX_DATA_ZERO,
+            _ => TX_DATA_NON_ZERO,
+        })
+        .sum();
+    let create_gas = if create {
+        TX_CREATE + INITCODE_WORD * (data.len() as u64).div_ceil(32)
+    } else {
+        0
+    };
+    TX_BASE + data_gas + create_gas
+}
+
+/// Returns the cost of creating a contract with the given code (EIP-170 deposit cost).
+pub fn code_deposit_gas(code: &[u8]) -> u64 {
+    CODE_DEPOSIT_BYTE.saturating_mul(code.len() as u64)
+}
+
+/// Returns the cost of `opcode` that depends on its operands, and the refund it grants.
+/// The memory expansion is not included, see `memory_expansion_gas`.
+///
+/// The schedule is simplified: there are no warm/cold access costs (EIP-2929), no cost for a
+/// `CALL` creating an account, and `SSTORE` ignores the original value of the slot and doesn't
+/// check that more than the stipend is left (EIP-2200).
+pub fn dynamic_gas(
+    opcode: &OpCode,
+    stack: &[U256],
+    storage: &Storage,
+    address: U256,
+) -> (u64, u64) {
+    let cost = match opcode {
+        OpCode::Exp => EXP_BYTE * (peek(stack, 1).bits() as u64).div_ceil(8),
+        OpCode::Sha3 => SHA3_WORD.saturating_mul(words(peek(stack, 1))),
+        OpCode::Calldatacopy | OpCode::Codecopy | OpCode::Returndatacopy => {
+            COPY_WORD.saturating_mul(words(peek(stack, 2)))
+        }
+        OpCode::Extcodecopy => COPY_WORD.saturating_mul(words(peek(stack, 3))),
+        OpCode::Log0 | OpCode::Log1 | OpCode::Log2 | OpCode::Log3 | OpCode::Log4 => {
+            LOG_DATA_BYTE.saturating_mul(saturating_u64(peek(stack, 1)))
+        }
+        OpCode::Create => INITCODE_WORD.saturating_mul(words(peek(stack, 2))),
+        OpCode::Call if !peek(stack, 2).is_zero() => CALL_VALUE_TRANSFER,
+        OpCode::Sstore => {
+            let current = storage.load_word(address, peek(stack, 0));
+            let new = peek(stack, 1);
+            return sstore_gas(current, new);
+        }
+        _ => 0,
+    };
+    (cost, 0)
+}
+
+/// A simplified SSTORE cost, without the warm/cold and original value distinctions.
+fn sstore_gas(current: U256, new: U256) -> (u64, u64) {
+    if current.is_zero() && !new.is_zero() {
+        (SSTORE_SET, 0)
+    } else if !current.is_zero() && new.is_zero() {
+        (SSTORE_RESET, SSTORE_CLEARS_REFUND)
+    } else {
+        (SSTORE_RESET, 0)
+    }
+}
+
+/// Returns the `n`-th item from the top of the stack, or 0 if the stack is too short
+/// (the opcode itself will fail with a stack underflow).
+fn peek(stack: &[U256], n: usize) -> U256 {
+    stack
+        .len()
+        .checked_sub(n + 1)
+        .map(|index| stack[index])
+        .unwrap_or_default()
+}
+
+fn saturating_u64(value: U256) -> u64 {
+    if value > u64::MAX.into() {
+        u64::MAX
+    } else {
+        value.as_u64()
+    }
+}
+
+fn words(size: U256) -> u64 {
+    saturating_u64(size).div_ceil(32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        address::Address, builder::Tx, errors::HaltReason, evm::Evm, evm::ExecutionResult,
+        state_data::AddressData, EvmResult,
+    };
+
+    fn run(code: &str, gas_limit: u64) -> EvmResult {
+        let callee = hex::decode("5a60005260206000f3").unwrap(); // returns GAS
+        Evm::builder()
+            .code(hex::decode(code).unwrap())
+            .account(
+                Address::from(U256::from(0xaa)),
+                AddressData {
+                    code: callee,
+                    ..AddressData::default()
+                },
+            )
+            .tx(Tx {
+                gas_limit: Some(gas_limit),
+                ..Tx::default()
+            })
+            .build()
+            .run()
+    }
+
+    #[test]
+    fn memory_expansion_cost() {
+        assert_eq!(memory_expansion_gas(0, 0.into()), Some(0));
+        assert_eq!(memory_expansion_gas(0, 32.into()), Some(3));
+        assert_eq!(memory_expansion_gas(32, 64.into()), Some(3));
+        assert_eq!(memory_expansion_gas(0, (1024 * 32).into()), Some(5120));
+        assert_eq!(memory_expansion_gas(64, 32.into()), Some(0));
+        assert_eq!(memory_expansion_gas(0, U256::MAX), None);
+    }
+
+    #[test]
+    fn memory_expansion_is_paid_before_allocating() {
+        // PUSH5 0x0100000000, MLOAD
+        let result = run("64010000000051", 100_000);
+        assert!(matches!(
+            result.result,
+            ExecutionResult::Halt {
+                reason: HaltReason::OutOfGas,
+                ..
+            }
+        ));
+        assert_eq!(result.gas_used, 100_000);
+    }
+
+    #[test]
+    fn call_gas_is_computed_after_the_memory_expansion() {
+        // CALL 0xaa with all the gas, writing 32 bytes at 0x1000, then MLOAD 0x1000.
+        // The stack of the result starts from the top.
+        let result = run("602061100060006000600060aa5af161100051", 100_000);
+        // GAS leaves 100000 - 20, CALL costs 100 and 419 for the memory, the callee gets 63/64
+        // of what's left and its GAS costs 2.
+        let available: u64 = 100_000 - 20 - 100 - 419;
+        let forwarded = available - available / 64;
+        assert_eq!(result.stack, vec![U256::from(forwarded - 2), U256::one()]);
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use primitive_types::U256;
+use serde_json::{json, Map, Value};
+
+use crate::{address::Address, errors::GenesisError, state_data::State, storage::Storage};
+
+/// Loads the accounts of a geth `genesis.json`: its `alloc` section, or the file itself if it's
+/// just the allocation. Quantities can be hex (`0x` prefixed) or decimal, strings or numbers.
+pub fn load_alloc(json: &str) -> Result<(State, Storage), GenesisError> {
+    let genesis: Value =
+        serde_json::from_str(json).map_err(|error| GenesisError::InvalidJson(error.to_string()))?;
+    let alloc = genesis.get("alloc").unwrap_or(&genesis);
+    let alloc = alloc.as_object().ok_or(GenesisError::ExpectedObject)?;
+
+    let mut state = State::default();
+    let mut storage = Storage::new_empty();
+    let mut addresses = BTreeSet::new();
+    for (key, account) in alloc {
+        let address =
+            parse_address(key).ok_or_else(|| GenesisError::InvalidAddress(key.to_string()))?;
+        // the same address written with a different case.
+        if !addresses.insert(address) {
+            return Err(GenesisError::DuplicateAddress(key.to_string()));
+        }
+        let field = |name: &str| account.get(name).filter(|value| !value.is_null());
+        let invalid = |name: &str| GenesisError::InvalidField {
+            address,
+            field: name.to_string(),
+        };
+
+        let balance = match field("balance") {
+            Some(balance) => parse_quantity(balance).ok_or_else(|| invalid("balance"))?,
+            None => U256::zero(),
+        };
+        let nonce = match field("nonce") {
+            Some(nonce) => parse_quantity(nonce)
+                .filter(|nonce| *nonce <= U256::from(u64::MAX))
+                .ok_or_else(|| invalid("nonce"))?
+                .as_usize(),
+            None => 0,
+        };
+        let code = match field("code") {
+            Some(code) => code
+                .as_str()
+                .and_then(parse_bytes)
+                .ok_or_else(|| invalid("code"))?,
+            None => vec![],
+        };
+        state.set_balance(address, balance);
+        state.set_nonce(address, nonce);
+        state.set_code(address, code);
+
+        if let Some(slots) = field("storage") {
+            let slots = slots.as_object().ok_or_else(|| invalid("storage"))?;
+            for (slot, value) in slots {
+                let slot = parse_hex_u256(slot).ok_or_else(|| invalid("storage"))?;
+                let value = value
+                    .as_str()
+                    .and_then(parse_hex_u256)
+                    .ok_or_else(|| invalid("storage"))?;
+                storage.set_word(address, slot, value);
+            }
+        }
+    }
+    Ok((state, storage))
+}
+
+/// Dumps the accounts and their storage in the `alloc` format of geth's `genesis.json`, sorted
+/// by address. Empty fields and zero slots are omitted.
+pub fn dump_alloc(state: &State, storage: &Storage) -> Value {
+    let addresses: BTreeSet<U256> = state.addresses().chain(storage.addresses()).collect();
+
+    let mut alloc = Map::new();
+    for address in addresses {
+        let mut account = json!({ "balance": format!("{:#x}", state.get_balance(address)) });
+        let nonce = state.get_nonce(address);
+        if nonce != 0 {
+            account["nonce"] = Value::from(format!("{:#x}", nonce));
+        }
+        let code = state.get_code(address);
+        if !code.is_empty() {
+            account["code"] = Value::from(format!("0x{}", hex::encode(code)));
+        }
+        let slots: BTreeMap<U256, U256> = storage
+            .slots(address)
+            .filter(|(_, value)| !value.is_zero())
+            .collect();
+        if !slots.is_empty() {
+            account["storage"] = slots
+                .iter()
+                .map(|(slot, value)| {
+                    (
+                        format!("0x{:064x}", slot),
+                        Value::from(format!("0x{:064x}", value)),
+                    )
+                })
+                .collect::<Map<String, Value>>()
+                .into();
+        }
+        alloc.insert(format!("0x{:040x}", address), account);
+    }
+    Value::Object(alloc)
+}
+
+/// Parses a quantity, a hex or decimal string or a number.
+fn parse_quantity(value: &Value) -> Option<U256> {
+    if let Some(number) = value.as_u64() {
+        return Some(U256::from(number));
+    }
+    let text = value.as_str()?;
+    match strip_hex_prefix(text) {
+        Some(_) => parse_hex_u256(text),
+        None => U256::from_dec_str(text).ok(),
+    }
+}
+
+/// Parses an address of at most 20 bytes, with or without the `0x` prefix, in any case.
+fn parse_address(text: &str) -> Option<U256> {
+    let hex = strip_hex_prefix(text).unwrap_or(text);
+    if hex.is_empty() {
+        return None;
+    }
+    // the leading zeros can be left out.
+    let address: Address = format!("{:0>40}", hex).parse().ok()?;
+    Some(address.into())
+}
+
+/// Parses a hex string, with or without the `0x` prefix.
+fn parse_hex_u256(text: &str) -> Option<U256> {
+    let hex = strip_hex_prefix(text).unwrap_or(text);
+    match hex {
+        "" => Some(U256::zero()),
+        _ if hex.len() > 64 => None,
+        _ => U256::from_str_radix(hex, 16).ok(),
+    }
+}
+
+fn parse_bytes(text: &str) -> Option<Vec<u8>> {
+    hex::decode(strip_hex_prefix(text).unwrap_or(text)).ok()
+}
+
+fn strip_hex_prefix(text: &str) -> Option<&str> {
+    text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dump_alloc, load_alloc};
+    use crate::errors::GenesisError;
+    use primitive_types::U256;
+
+    #[test]
+    fn upper_case_hex_prefixes() {
+        let (state, storage) = load_alloc(
+            r#"{ "0XAB": { "balance": "0X10", "nonce": "0X2", "code": "0X6000",
+                "storage": { "0X01": "0X02" } } }"#,
+        )
+        .unwrap();
+        let address = U256::from(0xab);
+        assert_eq!(state.get_balance(address), 16.into());
+        assert_eq!(state.get_nonce(address), 2);
+        assert_eq!(state.get_code(address), vec![0x60, 0x00]);
+        assert_eq!(storage.load_word(address, 1.into()), 2.into());
+    }
+
+    #[test]
+    fn addresses_are_case_insensitive() {
+        let error = load_alloc(r#"{ "0xabcd": {}, "0xABCD": {} }"#).unwrap_err();
+        assert!(matches!(error, GenesisError::DuplicateAddress(_)));
+        let too_long = format!(r#"{{ "0x{}": {{}} }}"#, "1".repeat(41));
+        assert!(matches!(
+            load_alloc(&too_long).unwrap_err(),
+            GenesisError::InvalidAddress(_)
+        ));
+    }
+
+    #[test]
+    fn dump_round_trip() {
+        let json = r#"{ "0x00000000000000000000000000000000000000aa": { "balance": "0x1",
+            "nonce": "0x1", "code": "0x00", "storage": { "0x0": "0x3" } } }"#;
+        let (state, storage) = load_alloc(json).unwrap();
+        let dumped = dump_alloc(&state, &storage);
+        let (state, storage) = load_alloc(&dumped.to_string()).unwrap();
+        assert_eq!(dump_alloc(&state, &storage), dumped);
+    }
+}
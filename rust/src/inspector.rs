@@ -0,0 +1,214 @@
+use std::{cell::RefCell, rc::Rc};
+
+use primitive_types::U256;
+
+use crate::{evm::ExecutionResult, gas::Gas, logs::Log, storage::Storage};
+
+/// An inspector shared by all the frames of an execution.
+pub type SharedInspector = Option<Rc<RefCell<dyn Inspector>>>;
+
+/// Hooks called by the interpreter while executing.
+///
+/// Every method has an empty default implementation, so an inspector only implements
+/// the events it's interested in. The frame hooks can also replace the outcome of a frame.
+pub trait Inspector {
+    /// Called before an opcode is executed, with the gas it's about to be charged.
+    fn step(&mut self, _step: &TraceStep) {}
+
+    /// Called after an opcode is executed, with the gas it was charged, memory expansion included.
+    fn step_end(&mut self, _step: &TraceStep) {}
+
+    /// Called when a call frame is entered. Returning an outcome skips the execution of the frame.
+    fn call(&mut self, _inputs: &CallInputs) -> Option<FrameOutcome> {
+        None
+    }
+
+    /// Called when a call frame exits. The returned outcome replaces the one of the frame.
+    fn call_end(&mut self, _inputs: &CallInputs, outcome: FrameOutcome) -> FrameOutcome {
+        outcome
+    }
+
+    /// Called when a create frame is entered. Returning an outcome skips the execution of the frame.
+    fn create(&mut self, _inputs: &CreateInputs) -> Option<CreateOutcome> {
+        None
+    }
+
+    /// Called when a create frame exits. The returned outcome replaces the one of the frame.
+    fn create_end(&mut self, _inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        outcome
+    }
+
+    /// Called when a log is emitted.
+    fn log(&mut self, _log: &Log) {}
+
+    /// Called when `address` self destructs, sending its `value` to `beneficiary`.
+    fn selfdestruct(&mut self, _address: U256, _beneficiary: U256, _value: U256) {}
+}
+
+/// The state of a frame around the execution of an opcode.
+pub struct TraceStep<'a> {
+    /// The account executing, whose storage is used. With `DELEGATECALL` the code comes
+    /// from another account.
+    pub address: U256,
+    pub pc: usize,
+    pub opcode: u8,
+    pub op_name: String,
+    /// The gas left.
+    pub gas: u64,
+    pub gas_cost: u64,
+    pub memory_size: usize,
+    pub memory: &'a [u8],
+    pub stack: &'a [U256],
+    pub storage: &'a Storage,
+    pub depth: usize,
+    pub return_data: &'a [u8],
+    pub refund: u64,
+}
+
+/// The opcode that entered a call frame. The transaction itself is a `Call`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallScheme {
+    Call,
+    DelegateCall,
+    StaticCall,
+}
+
+/// The inputs of a call frame.
+#[derive(Debug, Clone)]
+pub struct CallInputs {
+    pub scheme: CallScheme,
+    pub caller: U256,
+    /// The account whose storage and balance are used.
+    pub target: U256,
+    /// The account whose code is executed, it differs from `target` in a `DELEGATECALL`.
+    pub code_address: U256,
+    pub value: U256,
+    pub input: Vec<u8>,
+    /// The gas meter given to the frame.
+    pub gas: Gas,
+    /// The depth of the frame, 1 for the transaction itself.
+    pub depth: usize,
+    pub is_static: bool,
+}
+
+/// The inputs of a create frame.
+#[derive(Debug, Clone)]
+pub struct CreateInputs {
+    pub caller: U256,
+    /// The address of the contract being created.
+    pub address: U256,
+    pub value: U256,
+    pub init_code: Vec<u8>,
+    /// The gas meter given to the frame.
+    pub gas: Gas,
+    /// The depth of the frame.
+    pub depth: usize,
+}
+
+/// The outcome of a call frame.
+#[derive(Debug, Clone)]
+pub struct FrameOutcome {
+    pub result: ExecutionResult,
+    /// The returned or reverted data.
+    pub output: Vec<u8>,
+    /// The gas meter of the frame, at its exit.
+    pub gas: Gas,
+}
+
+/// The outcome of a create frame.
+#[derive(Debug, Clone)]
+pub struct CreateOutcome {
+    pub result: ExecutionResult,
+    /// The deployed code, or the reverted data.
+    pub output: Vec<u8>,
+    /// The gas meter of the frame, at its exit.
+    pub gas: Gas,
+    /// The address of the created contract. `None` if the creation failed, also when the
+    /// constructor succeeded but there wasn't enough gas left to deposit the code.
+    pub address: Option<U256>,
+}
+
+pub(crate) fn inspect_call(
+    inspector: &SharedInspector,
+    inputs: &CallInputs,
+) -> Option<FrameOutcome> {
+    inspector
+        .as_ref()
+        .and_then(|inspector| inspector.borrow_mut().call(inputs))
+}
+
+pub(crate) fn inspect_call_end(
+    inspector: &SharedInspector,
+    inputs: &CallInputs,
+    outcome: FrameOutcome,
+) -> FrameOutcome {
+    match inspector {
+        Some(inspector) => inspector.borrow_mut().call_end(inputs, outcome),
+        None => outcome,
+    }
+}
+
+pub(crate) fn inspect_create(
+    inspector: &SharedInspector,
+    inputs: &CreateInputs,
+) -> Option<CreateOutcome> {
+    inspector
+        .as_ref()
+        .and_then(|inspector| inspector.borrow_mut().create(inputs))
+}
+
+pub(crate) fn inspect_create_end(
+    inspector: &SharedInspector,
+    inputs: &CreateInputs,
+    outcome: CreateOutcome,
+) -> CreateOutcome {
+    match inspector {
+        Some(inspector) => inspector.borrow_mut().create_end(inputs, outcome),
+        None => outcome,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use primitive_types::U256;
+
+    use super::{CallInputs, FrameOutcome, Inspector};
+    use crate::{
+        evm::{ExecutionResult, SuccessReason},
+        evm_with_inspector,
+    };
+
+    /// Answers the calls to `0xbb` with 42 instead of executing its code.
+    struct Mock;
+
+    impl Inspector for Mock {
+        fn call(&mut self, inputs: &CallInputs) -> Option<FrameOutcome> {
+            if inputs.code_address != 0xbb.into() {
+                return None;
+            }
+            let mut output = [0u8; 32];
+            U256::from(42).to_big_endian(&mut output);
+            Some(FrameOutcome {
+                result: ExecutionResult::Success {
+                    reason: SuccessReason::Return,
+                },
+                output: output.to_vec(),
+                gas: inputs.gas,
+            })
+        }
+    }
+
+    #[test]
+    fn call_outcome_can_be_overridden() {
+        // PUSH1 32, PUSH1 0 (x4), PUSH1 0xbb, GAS, CALL, PUSH1 0, MLOAD, STOP
+        let code = hex::decode("6020600060006000600060bb5af160005100").unwrap();
+        // returns 7 if it's executed: PUSH1 7, PUSH1 0, MSTORE, PUSH1 32, PUSH1 0, RETURN
+        let callee = hex::decode("600760005260206000f3").unwrap();
+        let state = HashMap::from([(vec![0xbb], (0, vec![], callee))]);
+        let result = evm_with_inspector(code, vec![], vec![], state, Rc::new(RefCell::new(Mock)));
+        assert!(result.success);
+        assert_eq!(result.stack, vec![42.into(), U256::one()]);
+    }
+}
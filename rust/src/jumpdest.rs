@@ -3,6 +3,10 @@ use bit_vec::BitVec;
 use primitive_types::U256;
 
 pub fn valid_jumpdest(position: U256, code: &[u8]) -> Result<bool, ExecutionError> {
+    // a destination past the end of the code can't be a JUMPDEST.
+    if position >= code.len().into() {
+        return Err(ExecutionError::NotValidJumpDestination);
+    }
     if let Some(opcode) = OpCode::new(code[position.as_usize()]) {
         if opcode != OpCode::Jumpdest {
             return Err(ExecutionError::NotValidJumpDestination);
@@ -15,7 +19,7 @@ pub fn valid_jumpdest(position: U256, code: &[u8]) -> Result<bool, ExecutionErro
 }
 
 pub fn is_code(position: usize, code: &[u8]) -> Result<bool, ExecutionError> {
-    let analysis = code_bitmap(code)?;
+    let analysis = code_bitmap(code);
     let is_code = match analysis.get(position) {
         Some(value) => value,
         None => return Err(ExecutionError::NotValidJumpDestination),
@@ -23,7 +27,9 @@ pub fn is_code(position: usize, code: &[u8]) -> Result<bool, ExecutionError> {
     Ok(is_code)
 }
 
-pub fn code_bitmap(code: &[u8]) -> Result<BitVec, ExecutionError> {
+/// Returns a bitmap where the bytes of `PUSH` data are unset. Bytes that are not valid opcodes
+/// are one byte instructions, as they only fail when they are executed.
+pub fn code_bitmap(code: &[u8]) -> BitVec {
     // the bitmap is 4 bytes (32 bit) longer than necessary, in case the code ends with a PUSH32,
     // the algorithm will set bits on the bitvector outside the bounds of the actual code.
     let mut bitvec = BitVec::from_elem(code.len() + 32, true);
@@ -32,16 +38,43 @@ pub fn code_bitmap(code: &[u8]) -> Result<BitVec, ExecutionError> {
         if let Some(opcode) = OpCode::new(code[pc]) {
             if opcode.is_push() {
                 let push_data_size = opcode.push_data_size();
-                let start = pc;
-                for i in start..=(start + push_data_size) {
+                let start = pc + 1;
+                for i in start..(start + push_data_size) {
                     bitvec.set(i, false);
                 }
                 pc += push_data_size;
             }
-            pc += 1;
-        } else {
-            return Err(ExecutionError::InvalidOpcode);
         }
+        pc += 1;
+    }
+    bitvec
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn undefined_bytes_are_code() {
+        // PUSH1 4, JUMP, 0x0c (undefined), JUMPDEST, STOP
+        let code = hex::decode("6004560c5b00").unwrap();
+        assert!(valid_jumpdest(4.into(), &code).unwrap());
+
+        let result = crate::evm(&code, vec![], vec![], HashMap::new());
+        assert!(result.success);
+    }
+
+    #[test]
+    fn push_data_is_not_code() {
+        // PUSH1 4, JUMP, PUSH1 0x5b, STOP
+        let code = hex::decode("600456605b00").unwrap();
+        let bitmap = code_bitmap(&code);
+        assert!(bitmap[0] && !bitmap[1] && bitmap[3] && !bitmap[4]);
+        assert!(!valid_jumpdest(4.into(), &code).unwrap());
+
+        let result = crate::evm(&code, vec![], vec![], HashMap::new());
+        assert!(!result.success);
     }
-    Ok(bitvec)
 }
@@ -1,52 +1,172 @@
+mod abi;
+mod address;
+mod assembler;
 mod block_data;
+mod block_processing;
+mod builder;
+mod call_tracer;
+mod control_flow;
+mod disassembler;
+mod env;
 mod errors;
 mod evm;
+mod gas;
+mod genesis;
+mod inspector;
 mod jumpdest;
 mod logs;
 mod memory;
+mod node;
 mod opcode;
+mod prestate_tracer;
+mod revert_reason;
+mod rlp;
+mod selectors;
 mod state_data;
 mod storage;
+mod tracer;
+mod transaction;
 mod tx_data;
 mod utility;
+mod world_state;
 
-use block_data::BlockData;
-use evm::{Evm, ExecutionResult};
 use memory::Memory;
 use primitive_types::U256;
-use state_data::State;
-use std::{boxed::Box, collections::HashMap};
-use storage::Storage;
-use tx_data::TxData;
+use std::{boxed::Box, cell::RefCell, collections::HashMap, rc::Rc};
 
 // Re-exports
-pub use logs::Log;
+pub use abi::{
+    decode_tokens, encode_tokens, Abi, CustomError, DecodedLog, Event, Function, Param, ParamType,
+    Token,
+};
+pub use address::Address;
+pub use assembler::assemble;
+pub use block_data::{
+    calc_blob_base_fee, fake_exponential, BlockData, BlockHashes, BLOB_BASE_FEE_UPDATE_FRACTION,
+    BLOB_BASE_FEE_UPDATE_FRACTION_PRAGUE, BLOCK_HASH_HISTORY, MIN_BLOB_BASE_FEE,
+};
+pub use block_processing::{
+    post_block, pre_block, process_beacon_block_root, process_parent_block_hash,
+    process_withdrawals, Withdrawal, BEACON_ROOTS_ADDRESS, HISTORY_STORAGE_ADDRESS, SYSTEM_ADDRESS,
+};
+pub use builder::{EvmBuilder, Spec, Tx};
+pub use call_tracer::{CallFrame, CallTracer};
+pub use control_flow::{BasicBlock, ControlFlowGraph};
+pub use disassembler::{
+    disassemble, disassembly_listing, find_metadata, instructions, split_constructor, Instruction,
+    Item, Metadata,
+};
+pub use env::Env;
+pub use errors::{AbiError, AssemblerError, GenesisError, HaltReason, NodeError, RlpError};
+pub use evm::{Evm, ExecutionResult, SuccessReason};
+pub use gas::Gas;
+pub use genesis::{dump_alloc, load_alloc};
+pub use inspector::{
+    CallInputs, CallScheme, CreateInputs, CreateOutcome, FrameOutcome, Inspector, SharedInspector,
+    TraceStep,
+};
+pub use logs::{Log, LogFilter};
+pub use node::{Block, CallRequest, DevNode, MiningMode, Receipt, DEFAULT_BLOCK_GAS_LIMIT};
+pub use prestate_tracer::{AccountState, PrestateTracer, StateDiff};
+pub use revert_reason::{panic_description, RevertReason};
+pub use rlp::Rlp;
+pub use selectors::{function_entries, FunctionEntry};
+pub use state_data::{AddressData, State};
+pub use storage::Storage;
+pub use tracer::JsonTracer;
+pub use transaction::{Signature, Transaction};
+pub use tx_data::TxData;
+pub use utility::contract_address;
+pub use world_state::WorldState;
+
+/// The maximum size of the stack.
+const STACK_LIMIT: usize = 1024;
+/// The depth of the deepest frame: the transaction, at depth 1, and 1024 nested calls.
+const CALL_DEPTH_LIMIT: usize = 1025;
 
 pub struct EvmResult {
+    /// The stack, top first.
     pub stack: Vec<U256>,
+    /// The logs, the last emitted first.
     pub logs: Vec<Log>,
     pub success: bool,
     pub ret: Vec<u8>,
+    /// The gas used by the execution, after the refund.
+    pub gas_used: u64,
+    /// The gas meter at the end of the execution, with the gas used before the refund.
+    pub gas: Gas,
+    /// Why the execution ended.
+    pub result: ExecutionResult,
+    /// The decoded revert data, if the execution reverted.
+    pub revert_reason: Option<RevertReason>,
+    /// The state after the execution, unchanged if the execution failed.
+    pub state: State,
+    pub storage: Storage,
+}
+
+impl EvmResult {
+    /// Decodes the revert data, recognizing also the custom errors declared in `abi`.
+    pub fn revert_reason_with_abi(&self, abi: &Abi) -> Option<RevertReason> {
+        match &self.result {
+            ExecutionResult::Revert { data } => Some(RevertReason::decode_with_abi(data, abi)),
+            _ => None,
+        }
+    }
 }
 
+/// Executes `_code` with the inputs encoded as in `evm.json`. `Evm::builder()` takes the same
+/// inputs as typed values instead.
 pub fn evm(
     _code: impl AsRef<[u8]>,
     _tx_data: Vec<Vec<u8>>,
     _block_data: Vec<Vec<u8>>,
     _state_data: HashMap<Vec<u8>, (usize, Vec<u8>, Vec<u8>)>,
 ) -> EvmResult {
-    let code = _code.as_ref();
-    let limit = 1024;
+    run(_code, _tx_data, _block_data, _state_data, None)
+}
+
+/// Like `evm`, but reports the execution to `inspector` (e.g. a `JsonTracer`).
+pub fn evm_with_inspector(
+    _code: impl AsRef<[u8]>,
+    _tx_data: Vec<Vec<u8>>,
+    _block_data: Vec<Vec<u8>>,
+    _state_data: HashMap<Vec<u8>, (usize, Vec<u8>, Vec<u8>)>,
+    inspector: Rc<RefCell<dyn Inspector>>,
+) -> EvmResult {
+    run(_code, _tx_data, _block_data, _state_data, Some(inspector))
+}
+
+fn run(
+    _code: impl AsRef<[u8]>,
+    _tx_data: Vec<Vec<u8>>,
+    _block_data: Vec<Vec<u8>>,
+    _state_data: HashMap<Vec<u8>, (usize, Vec<u8>, Vec<u8>)>,
+    inspector: SharedInspector,
+) -> EvmResult {
     let tx_data = TxData::new(_tx_data);
     let block_data = BlockData::new(_block_data);
     let state_data = State::new(_state_data);
     // here I create an empty storage (just for this purpose)
     let storage = Storage::new_empty();
+    execute(_code, tx_data, block_data, state_data, storage, inspector)
+}
 
-    let mut evm = Evm::new(
-        Box::from(code),
+/// Executes `code` as the code of `tx.to`, starting from `state` and `storage`.
+/// Unlike a real transaction, no value is transferred and no nonce is incremented.
+pub fn execute(
+    code: impl AsRef<[u8]>,
+    tx_data: TxData,
+    block_data: BlockData,
+    state_data: State,
+    storage: Storage,
+    inspector: SharedInspector,
+) -> EvmResult {
+    let env = Rc::new(Env::new(block_data, &tx_data));
+    let gas = tx_data.gas.map(Gas::new).unwrap_or_else(Gas::unlimited);
+    Evm::new(
+        Box::from(code.as_ref()),
         tx_data,
-        block_data,
+        env,
         state_data,
         storage,
         vec![],
@@ -54,30 +174,10 @@ pub fn evm(
         vec![],
         vec![],
         Memory::new(),
-        limit,
+        STACK_LIMIT,
         false,
-    );
-
-    let result = evm.execute();
-
-    match result {
-        ExecutionResult::Success => EvmResult {
-            stack: evm.stack(),
-            logs: evm.logs(),
-            success: true,
-            ret: evm.return_data(),
-        },
-        ExecutionResult::Revert => EvmResult {
-            stack: evm.stack(),
-            logs: evm.logs(),
-            success: false,
-            ret: evm.return_data(),
-        },
-        ExecutionResult::Halt => EvmResult {
-            stack: evm.stack(),
-            logs: evm.logs(),
-            success: true,
-            ret: evm.return_data(),
-        },
-    }
+        gas,
+    )
+    .with_inspector(inspector)
+    .run()
 }
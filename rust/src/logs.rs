@@ -1,5 +1,7 @@
 use primitive_types::U256;
 
+use crate::abi::Event;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Log {
     pub address: U256,
@@ -16,3 +18,119 @@ impl Log {
         }
     }
 }
+
+/// Selects logs like the filter of `eth_getLogs`: a log matches if it was emitted by one of the
+/// addresses, and each of its topics is one of the values given for its position.
+/// No addresses, or no values for a position, match anything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogFilter {
+    pub addresses: Vec<U256>,
+    pub topics: Vec<Vec<U256>>,
+}
+
+impl LogFilter {
+    pub fn new() -> LogFilter {
+        LogFilter::default()
+    }
+
+    /// Also matches the logs emitted by `address`.
+    pub fn address(mut self, address: U256) -> LogFilter {
+        self.addresses.push(address);
+        self
+    }
+
+    /// Also matches the logs with `topic` at `position`.
+    pub fn topic(mut self, position: usize, topic: U256) -> LogFilter {
+        if self.topics.len() <= position {
+            self.topics.resize(position + 1, vec![]);
+        }
+        self.topics[position].push(topic);
+        self
+    }
+
+    /// Also matches the logs emitted by `event`, by its signature topic.
+    pub fn event(self, event: &Event) -> LogFilter {
+        self.topic(0, event.topic())
+    }
+
+    pub fn matches(&self, log: &Log) -> bool {
+        if !self.addresses.is_empty() && !self.addresses.contains(&log.address) {
+            return false;
+        }
+        // like geth, a log needs at least as many topics as the filter.
+        if self.topics.len() > log.topics.len() {
+            return false;
+        }
+        self.topics
+            .iter()
+            .zip(&log.topics)
+            .all(|(topics, topic)| topics.is_empty() || topics.contains(topic))
+    }
+
+    /// Returns the logs matching the filter.
+    pub fn filter<'a>(&self, logs: &'a [Log]) -> Vec<&'a Log> {
+        logs.iter().filter(|log| self.matches(log)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::Event;
+
+    fn log(address: u64, topics: &[u64]) -> Log {
+        Log::new(
+            address.into(),
+            vec![],
+            topics.iter().map(|&topic| topic.into()).collect(),
+        )
+    }
+
+    #[test]
+    fn addresses_are_alternatives() {
+        assert!(LogFilter::new().matches(&log(0xaa, &[])));
+        let filter = LogFilter::new().address(0xaa.into()).address(0xbb.into());
+        assert!(filter.matches(&log(0xaa, &[1])));
+        assert!(filter.matches(&log(0xbb, &[])));
+        assert!(!filter.matches(&log(0xcc, &[1])));
+    }
+
+    #[test]
+    fn topics_are_alternatives_by_position() {
+        let filter = LogFilter::new().topic(0, 1.into()).topic(0, 2.into());
+        assert!(filter.matches(&log(0xaa, &[1])));
+        assert!(filter.matches(&log(0xaa, &[2, 3])));
+        assert!(!filter.matches(&log(0xaa, &[3, 1])));
+        assert!(!filter.matches(&log(0xaa, &[])));
+
+        // both the address and the topics must match.
+        let filter = filter.address(0xbb.into());
+        assert!(!filter.matches(&log(0xaa, &[1])));
+        assert!(filter.matches(&log(0xbb, &[1])));
+    }
+
+    #[test]
+    fn positions_without_topics_are_wildcards() {
+        let filter = LogFilter::new().topic(2, 5.into());
+        assert_eq!(filter.topics, vec![vec![], vec![], vec![U256::from(5)]]);
+        assert!(filter.matches(&log(0xaa, &[1, 2, 5])));
+        assert!(filter.matches(&log(0xaa, &[3, 4, 5, 6])));
+        assert!(!filter.matches(&log(0xaa, &[1, 2, 6])));
+        // the log needs a topic at every position of the filter, even a wildcard one.
+        assert!(!filter.matches(&log(0xaa, &[1, 2])));
+        assert!(!LogFilter::new()
+            .topic(1, 5.into())
+            .matches(&log(0xaa, &[5])));
+    }
+
+    #[test]
+    fn events_match_their_signature_topic() {
+        let event = Event::parse("event Ping(uint256 indexed id)").unwrap();
+        let logs = vec![
+            Log::new(U256::zero(), vec![], vec![event.topic(), 1.into()]),
+            log(0xaa, &[1]),
+        ];
+        let filter = LogFilter::new().event(&event);
+        assert_eq!(filter.filter(&logs), vec![&logs[0]]);
+    }
+}
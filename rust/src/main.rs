@@ -14,9 +14,13 @@ use std::collections::HashMap;
  * gave up and switched to JavaScript, Python, or Go. If you are new
  * to Rust, implement EVM in another programming language first.
  */
-use evm::{evm, Log};
+use evm::{
+    assemble, disassembly_listing, evm, evm_with_inspector, Abi, CallTracer, ControlFlowGraph,
+    JsonTracer, Log, PrestateTracer, State, Storage,
+};
 use primitive_types::U256;
 use serde::Deserialize;
+use std::{cell::RefCell, rc::Rc};
 
 #[derive(Debug, Deserialize)]
 struct Evmtest {
@@ -87,6 +91,37 @@ struct LogRaw {
 }
 
 fn main() {
+    // `disasm <bytecode>` prints the disassembly of some hex encoded bytecode, inline or in a file,
+    // and `cfg <bytecode>` prints its control flow graph in the Graphviz DOT format.
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(command @ ("disasm" | "cfg")) = args.get(1).map(String::as_str) {
+        let input = args
+            .get(2)
+            .unwrap_or_else(|| panic!("usage: {} <bytecode>", command));
+        let bytecode = std::fs::read_to_string(input).unwrap_or(input.to_string());
+        let bytecode = bytecode.trim();
+        let code = hex::decode(bytecode.strip_prefix("0x").unwrap_or(bytecode)).unwrap();
+        match command {
+            "disasm" => print!("{}", disassembly_listing(&code)),
+            _ => print!("{}", ControlFlowGraph::new(&code).to_dot()),
+        }
+        return;
+    }
+
+    // with `--trace`, the EIP-3155 trace of every test is written to stderr.
+    let trace = std::env::args().any(|arg| arg == "--trace");
+    // with `--call-trace`, the call tree of every test is written to stderr.
+    let call_trace = std::env::args().any(|arg| arg == "--call-trace");
+    // with `--prestate`, the pre-state and the state diff of every test are written to stderr.
+    let prestate = std::env::args().any(|arg| arg == "--prestate");
+    // with `--abi <file>`, the custom errors declared in the JSON ABI are decoded in the reverts.
+    let abi = args.iter().position(|arg| arg == "--abi").map(|index| {
+        let path = args.get(index + 1).expect("usage: --abi <file>");
+        let json =
+            std::fs::read_to_string(path).unwrap_or_else(|error| panic!("{}: {}", path, error));
+        Abi::from_json(&json).unwrap_or_else(|error| panic!("{}: {}", path, error))
+    });
+
     let text = std::fs::read_to_string("../evm.json").unwrap();
     let data: Vec<Evmtest> = serde_json::from_str(&text).unwrap();
 
@@ -96,6 +131,16 @@ fn main() {
         println!("Test {} of {}: {}", index + 1, total, test.name);
 
         let code: Vec<u8> = hex::decode(&test.code.bin).unwrap();
+        // the asm of every test must assemble to its bin.
+        match assemble(&test.code.asm) {
+            Ok(assembled) if assembled == code => {}
+            Ok(assembled) => panic!(
+                "Assembled code 0x{} doesn't match 0x{}",
+                hex::encode(assembled),
+                test.code.bin
+            ),
+            Err(error) => panic!("Can't assemble the code: {}", error),
+        }
         let tx = match &test.tx {
             Some(tx) => {
                 // [2..] is necessary to delete the initial 0x
@@ -208,14 +253,43 @@ fn main() {
                         .bin,
                 )
                 .unwrap();
-                state_map.insert(address, (nonce.clone(), balance, code));
+                state_map.insert(address, (*nonce, balance, code));
             }
             state_map
         } else {
             HashMap::default()
         };
 
-        let result = evm(&code, tx, block, state);
+        let result = if trace {
+            let tracer = Rc::new(RefCell::new(JsonTracer::stderr()));
+            evm_with_inspector(&code, tx, block, state, tracer)
+        } else if call_trace {
+            let tracer = Rc::new(RefCell::new(CallTracer::new()));
+            let result = evm_with_inspector(&code, tx, block, state, tracer.clone());
+            eprintln!("{}", tracer.borrow().to_json());
+            result
+        } else if prestate {
+            let pre_state = State::new(state.clone());
+            let tracer = Rc::new(RefCell::new(PrestateTracer::new(
+                pre_state,
+                Storage::new_empty(),
+            )));
+            let result = evm_with_inspector(&code, tx, block, state, tracer.clone());
+            let tracer = tracer.borrow();
+            eprintln!("{}", tracer.to_json());
+            eprintln!("{}", tracer.diff(&result.state, &result.storage).to_json());
+            result
+        } else {
+            evm(&code, tx, block, state)
+        };
+
+        let revert_reason = match &abi {
+            Some(abi) => result.revert_reason_with_abi(abi),
+            None => result.revert_reason.clone(),
+        };
+        if let Some(reason) = &revert_reason {
+            println!("Reverted: {}", reason);
+        }
 
         let mut expected_stack: Vec<U256> = Vec::new();
         if let Some(ref stacks) = test.expect.stack {
@@ -233,7 +307,7 @@ fn main() {
                     topics,
                 } = log;
                 let address = U256::from_str_radix(address, 16).unwrap();
-                let data = hex::decode(format!("{}", &data)).unwrap();
+                let data = hex::decode(data).unwrap();
                 let topics = topics
                     .iter()
                     .map(|topic| U256::from_str_radix(topic, 16).unwrap())
@@ -249,14 +323,17 @@ fn main() {
 
         let mut matching = result.stack.len() == expected_stack.len();
         if matching {
-            for i in 0..result.stack.len() {
-                if result.stack[i] != expected_stack[i] {
+            for (actual, expected) in result.stack.iter().zip(&expected_stack) {
+                if actual != expected {
                     matching = false;
                     break;
                 }
             }
-            for i in 0..result.logs.len() {
-                if result.logs[i] != expected_logs[i] {
+        }
+        matching = matching && result.logs.len() == expected_logs.len();
+        if matching {
+            for (actual, expected) in result.logs.iter().zip(&expected_logs) {
+                if actual != expected {
                     matching = false;
                     break;
                 }
@@ -266,12 +343,9 @@ fn main() {
         matching = matching && result.success == test.expect.success;
 
         let mut expected_ret = vec![];
-        match &test.expect.ret {
-            Some(ret) => {
-                let ret = hex::decode(format!("{}", &ret)).unwrap();
-                expected_ret = ret;
-            }
-            None => {}
+        if let Some(ret) = &test.expect.ret {
+            let ret = hex::decode(ret).unwrap();
+            expected_ret = ret;
         };
 
         matching = matching && result.ret == expected_ret;
@@ -295,6 +369,7 @@ fn main() {
             println!("{:#?}", expected_ret);
 
             println!("Actual success: {:?}", result.success);
+            println!("Actual result: {:?}", result.result);
             println!("Actual stack: [");
             for v in result.stack {
                 println!("  {:#X},", v);
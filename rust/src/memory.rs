@@ -31,9 +31,7 @@ impl Memory {
         // memory must have at least offset + 32 free bytes left.
         self.resize(offset, 32)?;
 
-        for i in 0..32 {
-            self.store[offset + i] = value_bytes[i];
-        }
+        self.store[offset..offset + 32].copy_from_slice(&value_bytes);
         Ok(word)
     }
 
@@ -46,8 +44,8 @@ impl Memory {
     }
 
     pub fn save_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(), ExecutionError> {
-        for i in 0..bytes.len() {
-            self.save_byte(offset + i, bytes[i])?;
+        for (i, byte) in bytes.iter().enumerate() {
+            self.save_byte(offset + i, *byte)?;
         }
 
         Ok(())
@@ -0,0 +1,1122 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use primitive_types::U256;
+
+use crate::{
+    block_data::BlockData,
+    errors::{HaltReason, NodeError},
+    evm::ExecutionResult,
+    execute,
+    gas::{code_deposit_gas, intrinsic_gas, CALL_STIPEND, MAX_REFUND_QUOTIENT},
+    logs::Log,
+    rlp::Rlp,
+    state_data::State,
+    storage::Storage,
+    transaction::{Signature, Transaction},
+    tx_data::TxData,
+    utility::{contract_address, sha3_hash},
+    world_state::WorldState,
+    EvmResult,
+};
+
+/// The gas limit of the blocks mined by the node.
+pub const DEFAULT_BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// A message executed by `eth_call` or `eth_estimateGas`, without signature.
+#[derive(Debug, Clone, Default)]
+pub struct CallRequest {
+    pub from: U256,
+    /// `None` to run `data` as init code.
+    pub to: Option<U256>,
+    /// The block gas limit if `None`, and capped by it.
+    pub gas: Option<u64>,
+    pub gas_price: U256,
+    pub value: U256,
+    pub data: Vec<u8>,
+}
+
+/// When the node mines a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiningMode {
+    /// A block for each transaction, as soon as it's sent.
+    Auto,
+    /// A block with the pending transactions every interval, mined by the caller with `mine`.
+    Interval(Duration),
+    /// Only when `mine` is called.
+    Manual,
+}
+
+/// The outcome of a mined transaction.
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    pub transaction_hash: U256,
+    pub tx_type: u8,
+    pub block_number: U256,
+    pub block_hash: U256,
+    /// The position of the transaction in its block.
+    pub transaction_index: u64,
+    pub from: U256,
+    pub to: Option<U256>,
+    /// The address of the created contract, for successful creations.
+    pub contract_address: Option<U256>,
+    /// The gas used, intrinsic gas included.
+    pub gas_used: u64,
+    /// The gas used by this transaction and the ones before it in the block.
+    pub cumulative_gas_used: u64,
+    pub effective_gas_price: U256,
+    pub success: bool,
+    pub logs: Vec<Log>,
+    /// The index in the block of the first log.
+    pub first_log_index: u64,
+}
+
+/// A mined block.
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub number: U256,
+    /// The hash of the RLP encoding of the other fields, the parent hash first.
+    pub hash: U256,
+    pub parent_hash: U256,
+    pub timestamp: U256,
+    pub gas_limit: U256,
+    /// The gas used by the transactions of the block.
+    pub gas_used: u64,
+    pub base_fee: U256,
+    pub coinbase: U256,
+    /// The hashes of the transactions, in execution order.
+    pub transactions: Vec<U256>,
+}
+
+/// An in-process chain backing the JSON-RPC dev node. The fees are burned.
+#[derive(Debug, Clone)]
+pub struct DevNode {
+    world: WorldState,
+    /// The next block. Its timestamp is the one of the last mined block until it's mined.
+    pub block: BlockData,
+    pub mining: MiningMode,
+    /// The mined blocks, by number. The genesis block is mined when the node is created.
+    blocks: Vec<Block>,
+    /// The transactions waiting for the next block, with their sender.
+    pending: Vec<(U256, Transaction)>,
+    receipts: HashMap<U256, Receipt>,
+    /// Accounts whose transactions are accepted without signature.
+    impersonated: HashSet<U256>,
+    /// The timestamp of the next block, overriding the clock.
+    next_timestamp: Option<U256>,
+    /// The seconds added to the clock by `increase_time`.
+    time_offset: u64,
+    snapshots: Vec<NodeSnapshot>,
+}
+
+/// What's needed to go back to a snapshot besides the world state, which keeps its own journal.
+#[derive(Debug, Clone)]
+struct NodeSnapshot {
+    id: usize,
+    block_number: U256,
+    timestamp: U256,
+    pending: Vec<(U256, Transaction)>,
+    next_timestamp: Option<U256>,
+    time_offset: u64,
+}
+
+impl DevNode {
+    pub fn new(chain_id: u64) -> DevNode {
+        let block = BlockData {
+            timestamp: U256::from(now()),
+            number: U256::zero(),
+            gaslimit: U256::from(DEFAULT_BLOCK_GAS_LIMIT),
+            chainid: U256::from(chain_id),
+            prevrandao: Some(U256::zero()),
+            ..BlockData::default()
+        };
+        let mut node = DevNode {
+            world: WorldState::new(State::default(), Storage::new_empty()),
+            block,
+            mining: MiningMode::Auto,
+            blocks: vec![],
+            pending: vec![],
+            receipts: HashMap::new(),
+            impersonated: HashSet::new(),
+            next_timestamp: None,
+            time_offset: 0,
+            snapshots: vec![],
+        };
+        node.close_block(&[]);
+        node
+    }
+
+    /// Starts from the accounts `state` and their `storage`, e.g. loaded with `load_alloc`.
+    pub fn with_state(mut self, state: State, storage: Storage) -> DevNode {
+        self.world = WorldState::new(state, storage);
+        self
+    }
+
+    pub fn with_mining(mut self, mining: MiningMode) -> DevNode {
+        self.mining = mining;
+        self
+    }
+
+    /// Funds `address` with `balance` wei.
+    pub fn with_account(mut self, address: U256, balance: U256) -> DevNode {
+        self.world.set_balance(address, balance);
+        self
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.block.chainid.low_u64()
+    }
+
+    /// Returns the number of the last mined block.
+    pub fn block_number(&self) -> U256 {
+        self.block.number - 1
+    }
+
+    pub fn balance(&self, address: U256) -> U256 {
+        self.world.state().get_balance(address)
+    }
+
+    pub fn code(&self, address: U256) -> Vec<u8> {
+        self.world.state().get_code(address)
+    }
+
+    pub fn nonce(&self, address: U256) -> u64 {
+        self.world.state().get_nonce(address) as u64
+    }
+
+    pub fn storage_at(&self, address: U256, slot: U256) -> U256 {
+        self.world.storage().load_word(address, slot)
+    }
+
+    pub fn receipt(&self, hash: U256) -> Option<&Receipt> {
+        self.receipts.get(&hash)
+    }
+
+    /// Returns the mined block `number`.
+    pub fn mined_block(&self, number: U256) -> Option<&Block> {
+        if number >= self.block.number {
+            return None;
+        }
+        self.blocks.get(number.as_usize())
+    }
+
+    pub fn block_by_hash(&self, hash: U256) -> Option<&Block> {
+        self.blocks.iter().find(|block| block.hash == hash)
+    }
+
+    pub fn latest_block(&self) -> &Block {
+        self.blocks.last().expect("the genesis block is mined")
+    }
+
+    /// Executes `request` on top of the current state without committing it. The gas used
+    /// in the result includes the intrinsic gas. The gas isn't paid, but the value must be.
+    pub fn call(&self, request: &CallRequest) -> Result<EvmResult, NodeError> {
+        let gas_limit = self.gas_cap(request);
+        let mut state = self.world.state().clone();
+        let balance = state.get_balance(request.from);
+        if balance < request.value {
+            return Err(NodeError::InsufficientFunds);
+        }
+        state.set_balance(request.from, balance - request.value);
+        let (result, _) = self.run(request, gas_limit, state, self.world.storage().clone())?;
+        Ok(result)
+    }
+
+    /// Returns the lowest gas limit with which `request` succeeds, capped by `request.gas` or
+    /// the block gas limit.
+    ///
+    /// The gas used isn't enough in general: a call forwards only 63/64 of the gas left, and the
+    /// refund is subtracted only at the end, so the limit is searched by running the call.
+    /// Fails with the revert data if the call doesn't succeed even at the cap.
+    pub fn estimate_gas(&self, request: &CallRequest) -> Result<u64, NodeError> {
+        let run = |gas: u64| {
+            self.call(&CallRequest {
+                gas: Some(gas),
+                ..request.clone()
+            })
+        };
+        let cap = self.gas_cap(request);
+        let result = run(cap)?;
+        match result.result {
+            ExecutionResult::Success { .. } => {}
+            ExecutionResult::Revert { data } => return Err(NodeError::Reverted(data)),
+            ExecutionResult::Halt { reason, .. } => return Err(NodeError::Halted(reason)),
+        }
+
+        // any limit below the gas used fails, and most calls succeed with the gas used, the
+        // stipend and what's withheld by the 63/64 rule.
+        let mut lowest = result.gas_used.saturating_sub(1);
+        let mut highest = cap;
+        let optimistic = (result.gas_used + CALL_STIPEND) * 64 / 63;
+        if optimistic < highest && succeeds(run(optimistic)) {
+            highest = optimistic;
+        }
+        while lowest + 1 < highest {
+            let middle = lowest + (highest - lowest) / 2;
+            if succeeds(run(middle)) {
+                highest = middle;
+            } else {
+                lowest = middle;
+            }
+        }
+        Ok(highest)
+    }
+
+    pub fn state(&self) -> &State {
+        self.world.state()
+    }
+
+    pub fn storage(&self) -> &Storage {
+        self.world.storage()
+    }
+
+    /// Takes a snapshot of the chain: the world state, the blocks, the pending transactions
+    /// and the clock. Returns its id.
+    pub fn snapshot(&mut self) -> usize {
+        let id = self.world.snapshot();
+        self.snapshots.push(NodeSnapshot {
+            id,
+            block_number: self.block.number,
+            timestamp: self.block.timestamp,
+            pending: self.pending.clone(),
+            next_timestamp: self.next_timestamp,
+            time_offset: self.time_offset,
+        });
+        id
+    }
+
+    /// Goes back to the snapshot `id`, dropping it and the ones taken after it, with the
+    /// blocks mined since. Returns false if there's no such snapshot.
+    pub fn revert_to(&mut self, id: usize) -> bool {
+        let Some(index) = self.snapshots.iter().position(|snapshot| snapshot.id == id) else {
+            return false;
+        };
+        if !self.world.revert_to(id) {
+            return false;
+        }
+        let snapshot = self.snapshots[index].clone();
+        self.snapshots.truncate(index);
+
+        let number = snapshot.block_number;
+        self.block.number = number;
+        self.block.timestamp = snapshot.timestamp;
+        self.block
+            .block_hashes
+            .hashes
+            .retain(|block, _| *block < number);
+        self.blocks.truncate(number.as_usize());
+        self.receipts
+            .retain(|_, receipt| receipt.block_number < number);
+        self.pending = snapshot.pending;
+        self.next_timestamp = snapshot.next_timestamp;
+        self.time_offset = snapshot.time_offset;
+        true
+    }
+
+    pub fn set_balance(&mut self, address: U256, balance: U256) {
+        self.world.set_balance(address, balance);
+    }
+
+    pub fn set_code(&mut self, address: U256, code: Vec<u8>) {
+        self.world.set_code(address, code);
+    }
+
+    pub fn set_nonce(&mut self, address: U256, nonce: u64) {
+        self.world.set_nonce(address, nonce as usize);
+    }
+
+    pub fn set_storage_at(&mut self, address: U256, slot: U256, value: U256) {
+        self.world.set_word(address, slot, value);
+    }
+
+    /// Accepts the transactions of `address` without signature, see `send_unsigned`.
+    pub fn impersonate(&mut self, address: U256) {
+        self.impersonated.insert(address);
+    }
+
+    pub fn stop_impersonating(&mut self, address: U256) {
+        self.impersonated.remove(&address);
+    }
+
+    /// Sets the timestamp of the next block, which must be after the last one.
+    pub fn set_next_block_timestamp(&mut self, timestamp: U256) -> Result<(), NodeError> {
+        if timestamp <= self.block.timestamp {
+            return Err(NodeError::TimestampTooOld {
+                timestamp,
+                parent: self.block.timestamp,
+            });
+        }
+        self.next_timestamp = Some(timestamp);
+        Ok(())
+    }
+
+    /// Moves the clock of the next blocks forward by `seconds`. Returns the total offset.
+    pub fn increase_time(&mut self, seconds: u64) -> u64 {
+        self.time_offset += seconds;
+        self.time_offset
+    }
+
+    /// Returns the nonce of `address` counting its pending transactions.
+    pub fn pending_nonce(&self, address: U256) -> u64 {
+        let pending = self
+            .pending
+            .iter()
+            .filter(|(from, _)| *from == address)
+            .count();
+        self.nonce(address) + pending as u64
+    }
+
+    /// Validates the signed transaction `tx` sent by `from` and queues it. With automine, it's
+    /// executed and mined right away. Returns the hash of the transaction.
+    pub fn send_transaction(&mut self, from: U256, tx: &Transaction) -> Result<U256, NodeError> {
+        if let Some(chain_id) = tx.chain_id {
+            if chain_id != self.chain_id() {
+                return Err(NodeError::InvalidChainId(chain_id));
+            }
+        }
+        let nonce = self.pending_nonce(from);
+        if tx.nonce != nonce {
+            return Err(NodeError::InvalidNonce {
+                expected: nonce,
+                actual: tx.nonce,
+            });
+        }
+        if U256::from(tx.gas_limit) > self.block.gaslimit {
+            return Err(NodeError::GasLimitTooHigh(tx.gas_limit));
+        }
+        // checked again when the transaction is executed, but rejected early if it can't be.
+        let intrinsic = intrinsic_gas(&tx.data, tx.to.is_none());
+        if tx.gas_limit < intrinsic {
+            return Err(NodeError::IntrinsicGasTooLow {
+                limit: tx.gas_limit,
+                intrinsic,
+            });
+        }
+        if self.balance(from) < max_fee(tx)? {
+            return Err(NodeError::InsufficientFunds);
+        }
+
+        if self.mining == MiningMode::Auto {
+            // a rejected transaction doesn't mine an empty block.
+            let (timestamp, next_timestamp) = (self.block.timestamp, self.next_timestamp);
+            self.open_block();
+            match self.apply(from, tx) {
+                Ok(hash) => {
+                    self.close_block(&[hash]);
+                    Ok(hash)
+                }
+                Err(error) => {
+                    self.block.timestamp = timestamp;
+                    self.next_timestamp = next_timestamp;
+                    Err(error)
+                }
+            }
+        } else {
+            self.pending.push((from, tx.clone()));
+            Ok(tx.hash())
+        }
+    }
+
+    /// Sends `request` from an impersonated account, without signature. The nonce is the
+    /// pending one and the gas limit defaults to, and is capped by, the block gas limit.
+    pub fn send_unsigned(&mut self, request: &CallRequest) -> Result<U256, NodeError> {
+        if !self.impersonated.contains(&request.from) {
+            return Err(NodeError::NotImpersonated(request.from));
+        }
+        let tx = Transaction {
+            tx_type: 0,
+            chain_id: Some(self.chain_id()),
+            nonce: self.pending_nonce(request.from),
+            gas_price: request.gas_price.max(self.block.basefee),
+            max_priority_fee_per_gas: None,
+            gas_limit: self.gas_cap(request),
+            to: request.to,
+            value: request.value,
+            data: request.data.clone(),
+            access_list: Rlp::List(vec![]),
+            signature: Signature {
+                v: 0,
+                r: U256::zero(),
+                s: U256::zero(),
+            },
+        };
+        self.send_transaction(request.from, &tx)
+    }
+
+    /// Mines a block with the pending transactions, dropping the ones that became invalid.
+    /// The transactions after the first one whose gas limit doesn't fit in the gas left in the
+    /// block stay pending. Returns the number of the block.
+    pub fn mine(&mut self) -> U256 {
+        self.open_block();
+        let mut pending = std::mem::take(&mut self.pending).into_iter();
+        let (mut hashes, mut gas_left) = (vec![], self.block.gaslimit.low_u64());
+        while let Some((from, tx)) = pending.next() {
+            if tx.gas_limit > gas_left {
+                self.pending = std::iter::once((from, tx)).chain(pending).collect();
+                break;
+            }
+            if let Ok(hash) = self.apply(from, &tx) {
+                gas_left -= self.receipts[&hash].gas_used;
+                hashes.push(hash);
+            }
+        }
+        let number = self.block.number;
+        self.close_block(&hashes);
+        number
+    }
+
+    /// Returns the gas limit of `request`: the one requested, at most the block gas limit, so
+    /// that a single request can't keep the node busy forever.
+    fn gas_cap(&self, request: &CallRequest) -> u64 {
+        let block_gas_limit = self.block.gaslimit.low_u64();
+        request.gas.unwrap_or(block_gas_limit).min(block_gas_limit)
+    }
+
+    /// Executes `tx` in the current block and stores its receipt.
+    fn apply(&mut self, from: U256, tx: &Transaction) -> Result<U256, NodeError> {
+        let nonce = self.nonce(from);
+        if tx.nonce != nonce {
+            return Err(NodeError::InvalidNonce {
+                expected: nonce,
+                actual: tx.nonce,
+            });
+        }
+        let gas_price = tx.effective_gas_price(self.block.basefee);
+        let balance = self.balance(from);
+        if balance < max_fee(tx)? {
+            return Err(NodeError::InsufficientFunds);
+        }
+
+        // the gas is bought upfront, and the unused part is given back after the execution.
+        let mut state = self.world.state().clone();
+        state.set_balance(
+            from,
+            balance - U256::from(tx.gas_limit) * gas_price - tx.value,
+        );
+        state.set_nonce(from, nonce as usize + 1);
+        let request = CallRequest {
+            from,
+            to: tx.to,
+            gas: Some(tx.gas_limit),
+            gas_price,
+            value: tx.value,
+            data: tx.data.clone(),
+        };
+        let (result, contract_address) = self.run(
+            &request,
+            tx.gas_limit,
+            state.clone(),
+            self.world.storage().clone(),
+        )?;
+
+        // a failed transaction still pays for the gas and increments the nonce.
+        let (mut state, storage) = if result.success {
+            (result.state.clone(), result.storage.clone())
+        } else {
+            state.set_balance(from, state.get_balance(from) + tx.value);
+            (state, self.world.storage().clone())
+        };
+        let refund = U256::from(tx.gas_limit - result.gas_used) * gas_price;
+        state.set_balance(from, state.get_balance(from) + refund);
+        self.world.commit(state, storage);
+
+        let hash = tx.hash();
+        let receipt = Receipt {
+            transaction_hash: hash,
+            tx_type: tx.tx_type,
+            block_number: self.block.number,
+            // known once the block is mined.
+            block_hash: U256::zero(),
+            transaction_index: 0,
+            from,
+            to: tx.to,
+            contract_address: contract_address.filter(|_| result.success),
+            gas_used: result.gas_used,
+            cumulative_gas_used: 0,
+            effective_gas_price: gas_price,
+            success: result.success,
+            // the logs of the result are the last emitted first.
+            logs: match result.success {
+                true => result.logs.into_iter().rev().collect(),
+                false => vec![],
+            },
+            first_log_index: 0,
+        };
+        self.receipts.insert(hash, receipt);
+        Ok(hash)
+    }
+
+    /// Sets the timestamp of the block about to be mined: the one given with
+    /// `set_next_block_timestamp`, or the clock, at least a second after the last block.
+    fn open_block(&mut self) {
+        self.block.timestamp = match self.next_timestamp.take() {
+            Some(timestamp) => timestamp,
+            None => (self.block.timestamp + 1).max(U256::from(now() + self.time_offset)),
+        };
+    }
+
+    /// Closes the current block with the transactions `hashes` and moves to the next one.
+    fn close_block(&mut self, hashes: &[U256]) {
+        let (mut gas_used, mut log_index) = (0, 0);
+        for (index, hash) in hashes.iter().enumerate() {
+            if let Some(receipt) = self.receipts.get_mut(hash) {
+                gas_used += receipt.gas_used;
+                receipt.transaction_index = index as u64;
+                receipt.cumulative_gas_used = gas_used;
+                receipt.first_log_index = log_index;
+                log_index += receipt.logs.len() as u64;
+            }
+        }
+        let parent_hash = self
+            .blocks
+            .last()
+            .map(|parent| parent.hash)
+            .unwrap_or_default();
+        let header = Rlp::List(vec![
+            Rlp::uint(parent_hash),
+            Rlp::uint(self.block.number),
+            Rlp::uint(self.block.timestamp),
+            Rlp::uint(self.block.gaslimit),
+            Rlp::uint(gas_used),
+            Rlp::uint(self.block.basefee),
+            Rlp::uint(self.block.coinbase),
+            Rlp::List(hashes.iter().map(|hash| Rlp::uint(*hash)).collect()),
+        ]);
+        let hash = U256::from_big_endian(&sha3_hash(&header.encode()));
+        for receipt in hashes {
+            if let Some(receipt) = self.receipts.get_mut(receipt) {
+                receipt.block_hash = hash;
+            }
+        }
+        self.blocks.push(Block {
+            number: self.block.number,
+            hash,
+            parent_hash,
+            timestamp: self.block.timestamp,
+            gas_limit: self.block.gaslimit,
+            gas_used,
+            base_fee: self.block.basefee,
+            coinbase: self.block.coinbase.into(),
+            transactions: hashes.to_vec(),
+        });
+        self.block.block_hashes.insert(self.block.number, hash);
+        self.block.number += U256::one();
+    }
+
+    /// Runs `request` with `gas_limit` (intrinsic gas included) on `state`, where the value is
+    /// already taken from the sender. Returns the result with the intrinsic gas added, and the
+    /// address of the contract for creations.
+    fn run(
+        &self,
+        request: &CallRequest,
+        gas_limit: u64,
+        mut state: State,
+        storage: Storage,
+    ) -> Result<(EvmResult, Option<U256>), NodeError> {
+        let intrinsic = intrinsic_gas(&request.data, request.to.is_none());
+        if gas_limit < intrinsic {
+            return Err(NodeError::IntrinsicGasTooLow {
+                limit: gas_limit,
+                intrinsic,
+            });
+        }
+
+        let (to, code, data, contract_address) = match request.to {
+            Some(to) => (to, state.get_code(to), request.data.clone(), None),
+            None => {
+                let address = contract_address(request.from, self.nonce(request.from));
+                if !state.get_code(address).is_empty() || state.get_nonce(address) != 0 {
+                    return Err(NodeError::CreateCollision);
+                }
+                state.set_nonce(address, 1);
+                (address, request.data.clone(), vec![], Some(address))
+            }
+        };
+        state.transfer_balance(request.value, to);
+
+        let tx_data = TxData {
+            to: word(to),
+            from: word(request.from),
+            origin: word(request.from),
+            gasprice: word(request.gas_price),
+            value: word(request.value),
+            data,
+            gas: Some(gas_limit - intrinsic),
+        };
+        let mut result = execute(code, tx_data, self.block.clone(), state, storage, None);
+        let mut used = intrinsic + result.gas.used();
+
+        // the returned code is deposited at the new address, if there's enough gas to pay for it.
+        if let (Some(address), true) = (contract_address, result.success) {
+            let deposit = code_deposit_gas(&result.ret);
+            if used + deposit <= gas_limit {
+                used += deposit;
+                result.state.set_code(address, result.ret.clone());
+            } else {
+                result.success = false;
+                result.result = ExecutionResult::Halt {
+                    reason: HaltReason::OutOfGas,
+                    pc: 0,
+                    opcode: 0,
+                };
+                used = gas_limit;
+            }
+        }
+        // the refund is capped by the gas used by the whole transaction (EIP-3529).
+        let refund = match result.success {
+            true => result.gas.refunded().min(used / MAX_REFUND_QUOTIENT),
+            false => 0,
+        };
+        result.gas_used = used - refund;
+        Ok((result, contract_address))
+    }
+}
+
+/// Returns what the sender of `tx` must hold: the value and the gas limit at the max fee.
+fn max_fee(tx: &Transaction) -> Result<U256, NodeError> {
+    U256::from(tx.gas_limit)
+        .checked_mul(tx.gas_price)
+        .and_then(|fee| fee.checked_add(tx.value))
+        .ok_or(NodeError::InsufficientFunds)
+}
+
+fn succeeds(result: Result<EvmResult, NodeError>) -> bool {
+    result.is_ok_and(|result| result.success)
+}
+
+fn word(value: U256) -> Vec<u8> {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    bytes.to_vec()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    const SENDER: u64 = 0x1000;
+
+    fn node() -> DevNode {
+        let mut node = DevNode::new(1337).with_account(SENDER.into(), U256::exp10(18));
+        node.impersonate(SENDER.into());
+        node
+    }
+
+    fn send(node: &mut DevNode, to: Option<U256>, data: Vec<u8>) -> Receipt {
+        let request = CallRequest {
+            from: SENDER.into(),
+            to,
+            gas: Some(1_000_000),
+            data,
+            ..CallRequest::default()
+        };
+        let hash = node.send_unsigned(&request).unwrap();
+        node.receipt(hash).unwrap().clone()
+    }
+
+    #[test]
+    fn receipt_logs_in_emission_order_with_sub_calls() {
+        let mut node = node();
+        let callee =
+            assemble("PUSH1 7 PUSH1 0 SSTORE  PUSH1 3 PUSH1 0 MSTORE  PUSH1 32 PUSH1 0 LOG0  STOP")
+                .unwrap();
+        let caller = assemble(
+            "
+            PUSH1 1 PUSH1 0 MSTORE  PUSH1 32 PUSH1 0 LOG0
+            PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP
+            PUSH1 2 PUSH1 0 MSTORE  PUSH1 32 PUSH1 0 LOG0
+            STOP
+            ",
+        )
+        .unwrap();
+        node.set_code(0xbb.into(), callee);
+        node.set_code(0xaa.into(), caller);
+
+        let receipt = send(&mut node, Some(0xaa.into()), vec![]);
+        assert!(receipt.success);
+        assert_eq!(node.storage_at(0xbb.into(), U256::zero()), 7.into());
+        let logs: Vec<(U256, U256)> = receipt
+            .logs
+            .iter()
+            .map(|log| (log.address, U256::from_big_endian(&log.data)))
+            .collect();
+        assert_eq!(
+            logs,
+            vec![
+                (0xaa.into(), 1.into()),
+                (0xbb.into(), 3.into()),
+                (0xaa.into(), 2.into())
+            ]
+        );
+    }
+
+    #[test]
+    fn code_deposit_is_paid_before_the_refund() {
+        let node = node();
+        // sets and clears a slot for a refund, then deploys 32 bytes of code.
+        let init_code =
+            assemble("PUSH1 1 PUSH1 0 SSTORE  PUSH1 0 PUSH1 0 SSTORE  PUSH1 32 PUSH1 0 RETURN")
+                .unwrap();
+        let deploy = |gas: u64| {
+            node.call(&CallRequest {
+                from: SENDER.into(),
+                gas: Some(gas),
+                data: init_code.clone(),
+                ..CallRequest::default()
+            })
+            .unwrap()
+        };
+        let result = deploy(1_000_000);
+        assert!(result.success);
+        let refund = result.gas.refunded();
+        assert!(refund > 0);
+        let needed =
+            intrinsic_gas(&init_code, true) + result.gas.used() + code_deposit_gas(&[0; 32]);
+        assert_eq!(
+            result.gas_used,
+            needed - refund.min(needed / MAX_REFUND_QUOTIENT)
+        );
+
+        let result = deploy(needed);
+        assert!(result.success);
+        let result = deploy(needed - 1);
+        assert!(!result.success);
+        assert_eq!(result.gas_used, needed - 1);
+    }
+
+    #[test]
+    fn mined_blocks_are_chained_by_hash() {
+        let mut node = node();
+        let genesis = node.latest_block().clone();
+        assert_eq!(genesis.number, U256::zero());
+        assert_eq!(genesis.parent_hash, U256::zero());
+
+        let receipt = send(&mut node, Some(0xaa.into()), vec![]);
+        let block = node.mined_block(U256::one()).unwrap().clone();
+        assert_eq!(block.parent_hash, genesis.hash);
+        assert_eq!(block.transactions, vec![receipt.transaction_hash]);
+        assert_eq!(block.gas_used, receipt.gas_used);
+        assert_eq!(receipt.block_hash, block.hash);
+        assert_eq!(node.block_by_hash(block.hash).unwrap().number, U256::one());
+        assert!(node.mined_block(2.into()).is_none());
+
+        let snapshot = node.snapshot();
+        node.mine();
+        assert_eq!(node.latest_block().parent_hash, block.hash);
+        assert!(node.revert_to(snapshot));
+        assert_eq!(node.latest_block().hash, block.hash);
+        assert!(node.mined_block(2.into()).is_none());
+    }
+
+    #[test]
+    fn receipts_are_positioned_in_their_block() {
+        let mut node = node().with_mining(MiningMode::Manual);
+        // emits two logs.
+        let code = assemble("PUSH1 0 PUSH1 0 LOG0  PUSH1 0 PUSH1 0 LOG0  STOP").unwrap();
+        node.set_code(0xaa.into(), code);
+        let request = CallRequest {
+            from: SENDER.into(),
+            to: Some(0xaa.into()),
+            gas: Some(100_000),
+            ..CallRequest::default()
+        };
+        let hashes = [
+            node.send_unsigned(&request).unwrap(),
+            node.send_unsigned(&request).unwrap(),
+        ];
+        node.mine();
+
+        let receipts: Vec<&Receipt> = hashes
+            .iter()
+            .map(|hash| node.receipt(*hash).unwrap())
+            .collect();
+        assert_eq!(
+            receipts
+                .iter()
+                .map(|receipt| (receipt.transaction_index, receipt.first_log_index))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (1, 2)]
+        );
+        assert_eq!(receipts[0].cumulative_gas_used, receipts[0].gas_used);
+        assert_eq!(
+            receipts[1].cumulative_gas_used,
+            receipts[0].gas_used + receipts[1].gas_used
+        );
+        assert_eq!(
+            node.latest_block().gas_used,
+            receipts[1].cumulative_gas_used
+        );
+    }
+
+    #[test]
+    fn automine_mines_a_block_per_transaction() {
+        let mut node = node();
+        let first = send(&mut node, Some(0xaa.into()), vec![]);
+        let second = send(&mut node, Some(0xaa.into()), vec![]);
+        assert_eq!(node.block_number(), 2.into());
+        assert_eq!(first.block_number, U256::one());
+        assert_eq!(second.block_number, 2.into());
+        assert_eq!(
+            node.latest_block().transactions,
+            vec![second.transaction_hash]
+        );
+    }
+
+    #[test]
+    fn manual_mining_waits_for_mine() {
+        let mut node = node().with_mining(MiningMode::Manual);
+        let request = CallRequest {
+            from: SENDER.into(),
+            to: Some(0xaa.into()),
+            ..CallRequest::default()
+        };
+        let hash = node.send_unsigned(&request).unwrap();
+        assert_eq!(node.block_number(), U256::zero());
+        assert!(node.receipt(hash).is_none());
+        assert_eq!(node.nonce(SENDER.into()), 0);
+        assert_eq!(node.pending_nonce(SENDER.into()), 1);
+
+        assert_eq!(node.mine(), U256::one());
+        assert_eq!(node.receipt(hash).unwrap().block_number, U256::one());
+        assert_eq!(node.nonce(SENDER.into()), 1);
+    }
+
+    #[test]
+    fn queued_transactions_are_validated() {
+        let mut node = node().with_mining(MiningMode::Manual);
+        node.impersonate(0xcc.into());
+        let request = CallRequest {
+            from: 0xcc.into(),
+            to: Some(0xaa.into()),
+            value: 1.into(),
+            ..CallRequest::default()
+        };
+        assert!(matches!(
+            node.send_unsigned(&request),
+            Err(NodeError::InsufficientFunds)
+        ));
+        let request = CallRequest {
+            from: SENDER.into(),
+            to: None,
+            gas: Some(21_000),
+            data: vec![0],
+            ..CallRequest::default()
+        };
+        assert!(matches!(
+            node.send_unsigned(&request),
+            Err(NodeError::IntrinsicGasTooLow { .. })
+        ));
+        assert_eq!(node.pending_nonce(SENDER.into()), 0);
+    }
+
+    #[test]
+    fn mined_blocks_respect_the_gas_limit() {
+        let mut node = node().with_mining(MiningMode::Manual);
+        node.block.gaslimit = 100_000.into();
+        let request = CallRequest {
+            from: SENDER.into(),
+            to: Some(0xaa.into()),
+            gas: Some(60_000),
+            ..CallRequest::default()
+        };
+        let hashes: Vec<U256> = (0..3)
+            .map(|_| node.send_unsigned(&request).unwrap())
+            .collect();
+
+        // the third transaction could use 60000 gas, but only 58000 are left after the others.
+        node.mine();
+        assert_eq!(node.latest_block().transactions, hashes[..2]);
+        assert_eq!(node.latest_block().gas_used, 42_000);
+        assert!(node.receipt(hashes[2]).is_none());
+        assert_eq!(node.pending_nonce(SENDER.into()), 3);
+
+        node.mine();
+        assert_eq!(node.latest_block().transactions, hashes[2..]);
+        assert_eq!(node.nonce(SENDER.into()), 3);
+    }
+
+    #[test]
+    fn timestamp_overrides_are_applied() {
+        let mut node = node();
+        // stores TIMESTAMP in slot 0.
+        node.set_code(0xaa.into(), assemble("TIMESTAMP PUSH1 0 SSTORE").unwrap());
+        let timestamp = node.latest_block().timestamp + 1000;
+        node.set_next_block_timestamp(timestamp).unwrap();
+        send(&mut node, Some(0xaa.into()), vec![]);
+        assert_eq!(node.latest_block().timestamp, timestamp);
+        assert_eq!(node.storage_at(0xaa.into(), U256::zero()), timestamp);
+        assert!(matches!(
+            node.set_next_block_timestamp(timestamp),
+            Err(NodeError::TimestampTooOld { .. })
+        ));
+
+        // the clock is moved forward, but a block is at least a second after its parent.
+        node.increase_time(3600);
+        let expected = (timestamp + 1).max(U256::from(now() + 3600));
+        node.mine();
+        assert!(node.latest_block().timestamp >= expected);
+    }
+
+    #[test]
+    fn impersonated_senders_need_no_signature() {
+        let mut node = node();
+        node.set_balance(0xcc.into(), U256::exp10(18));
+        let request = CallRequest {
+            from: 0xcc.into(),
+            to: Some(0xaa.into()),
+            value: 1.into(),
+            ..CallRequest::default()
+        };
+        assert!(matches!(
+            node.send_unsigned(&request),
+            Err(NodeError::NotImpersonated(_))
+        ));
+
+        node.impersonate(0xcc.into());
+        let hash = node.send_unsigned(&request).unwrap();
+        let receipt = node.receipt(hash).unwrap();
+        assert!(receipt.success);
+        assert_eq!(receipt.from, 0xcc.into());
+        assert_eq!(node.balance(0xaa.into()), 1.into());
+
+        node.stop_impersonating(0xcc.into());
+        assert!(matches!(
+            node.send_unsigned(&request),
+            Err(NodeError::NotImpersonated(_))
+        ));
+    }
+
+    /// Checks that `request` succeeds with the estimated gas and fails with one less unit.
+    fn assert_lowest_gas(node: &DevNode, request: &CallRequest) -> u64 {
+        let estimate = node.estimate_gas(request).unwrap();
+        let run = |gas: u64| {
+            let request = CallRequest {
+                gas: Some(gas),
+                ..request.clone()
+            };
+            node.call(&request).unwrap().success
+        };
+        assert!(run(estimate));
+        assert!(!run(estimate - 1));
+        estimate
+    }
+
+    #[test]
+    fn estimate_covers_the_gas_withheld_from_sub_calls() {
+        let mut node = node();
+        // 0xbb sets a slot, 0xaa calls it with all its gas and reverts if the call fails.
+        node.set_code(
+            0xbb.into(),
+            assemble("PUSH1 1 PUSH1 0 SSTORE STOP").unwrap(),
+        );
+        let caller = assemble(
+            "
+            PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL
+            PUSH @ok JUMPI  PUSH1 0 PUSH1 0 REVERT
+            ok: JUMPDEST STOP
+            ",
+        )
+        .unwrap();
+        node.set_code(0xaa.into(), caller);
+        let request = CallRequest {
+            from: SENDER.into(),
+            to: Some(0xaa.into()),
+            ..CallRequest::default()
+        };
+        let gas_used = node.call(&request).unwrap().gas_used;
+        let estimate = assert_lowest_gas(&node, &request);
+        // the 64th of the gas left kept by the caller is not used, but must be provided.
+        assert!(estimate > gas_used);
+    }
+
+    #[test]
+    fn estimate_covers_the_gas_refunded_at_the_end() {
+        let mut node = node();
+        // clears a slot, for a refund.
+        node.set_code(
+            0xaa.into(),
+            assemble("PUSH1 0 PUSH1 0 SSTORE STOP").unwrap(),
+        );
+        node.set_storage_at(0xaa.into(), U256::zero(), 1.into());
+        let request = CallRequest {
+            from: SENDER.into(),
+            to: Some(0xaa.into()),
+            ..CallRequest::default()
+        };
+        let result = node.call(&request).unwrap();
+        assert!(result.gas.refunded() > 0);
+        let estimate = assert_lowest_gas(&node, &request);
+        assert_eq!(estimate, result.gas_used + result.gas.refunded());
+    }
+
+    #[test]
+    fn estimate_fails_with_the_revert_data() {
+        let mut node = node();
+        let code =
+            assemble("PUSH4 0xdeadbeef PUSH1 224 SHL PUSH1 0 MSTORE  PUSH1 4 PUSH1 0 REVERT")
+                .unwrap();
+        node.set_code(0xaa.into(), code);
+        let request = CallRequest {
+            from: SENDER.into(),
+            to: Some(0xaa.into()),
+            ..CallRequest::default()
+        };
+        assert_eq!(
+            node.estimate_gas(&request),
+            Err(NodeError::Reverted(vec![0xde, 0xad, 0xbe, 0xef]))
+        );
+    }
+
+    #[test]
+    fn call_gas_is_capped_by_the_block_gas_limit() {
+        let node = node();
+        // with 2^64 gas, the memory expansion would be paid and tens of GB allocated.
+        let code = assemble("PUSH1 1 PUSH5 0x1000000000 MSTORE").unwrap();
+        let request = CallRequest {
+            from: SENDER.into(),
+            gas: Some(u64::MAX),
+            data: code,
+            ..CallRequest::default()
+        };
+        let result = node.call(&request).unwrap();
+        assert!(!result.success);
+        assert_eq!(result.gas_used, DEFAULT_BLOCK_GAS_LIMIT);
+    }
+
+    #[test]
+    fn call_value_must_be_covered_by_the_balance() {
+        let node = node();
+        let request = CallRequest {
+            from: SENDER.into(),
+            to: Some(0xaa.into()),
+            value: U256::exp10(18),
+            ..CallRequest::default()
+        };
+        let result = node.call(&request).unwrap();
+        assert!(result.success);
+        assert_eq!(result.state.get_balance(0xaa.into()), U256::exp10(18));
+
+        let request = CallRequest {
+            value: U256::exp10(18) + 1,
+            ..request
+        };
+        assert!(matches!(
+            node.call(&request),
+            Err(NodeError::InsufficientFunds)
+        ));
+        assert_eq!(
+            node.estimate_gas(&request),
+            Err(NodeError::InsufficientFunds)
+        );
+    }
+}
@@ -72,6 +72,7 @@ opcodes! {
     Chainid(70),
     Selfbalance(71),
     Basfee(72),
+    Blobbasefee(74),
     Mload(81),
     Mstore(82),
     Mstore8(83),
@@ -164,11 +165,7 @@ opcodes! {
 
 impl OpCode {
     pub fn new(opcode: u8) -> Option<Self> {
-        if let Ok(opcode) = opcode.try_into() {
-            Some(opcode)
-        } else {
-            None
-        }
+        opcode.try_into().ok()
     }
 
     /// Helper function to determine the push data size for each `Push` opcode
@@ -245,7 +242,143 @@ impl OpCode {
         }
     }
 
+    /// Helper function to determine the constant gas cost of each opcode.
+    /// Accessing accounts and storage always costs as a warm access.
+    pub fn static_gas(&self) -> u64 {
+        match self {
+            OpCode::Stop | OpCode::Return | OpCode::Revert | OpCode::Sstore => 0,
+            OpCode::Jumpdest => 1,
+            OpCode::Address
+            | OpCode::Origin
+            | OpCode::Caller
+            | OpCode::Callvalue
+            | OpCode::Calldatasize
+            | OpCode::Codesize
+            | OpCode::Gasprice
+            | OpCode::Coinbase
+            | OpCode::Timestamp
+            | OpCode::Number
+            | OpCode::Difficulty
+            | OpCode::Gaslimit
+            | OpCode::Chainid
+            | OpCode::Basfee
+            | OpCode::Blobbasefee
+            | OpCode::Returndatasize
+            | OpCode::Pop
+            | OpCode::Pc
+            | OpCode::Msize
+            | OpCode::Gas
+            | OpCode::Push0 => 2,
+            OpCode::Mul
+            | OpCode::Div
+            | OpCode::Sdiv
+            | OpCode::Mod
+            | OpCode::Smod
+            | OpCode::Signextend
+            | OpCode::Selfbalance => 5,
+            OpCode::Addmod | OpCode::Mulmod | OpCode::Jump => 8,
+            OpCode::Jumpi | OpCode::Exp => 10,
+            OpCode::Blockhash => 20,
+            OpCode::Sha3 => 30,
+            OpCode::Balance
+            | OpCode::Extcodesize
+            | OpCode::Extcodecopy
+            | OpCode::Extcodehash
+            | OpCode::Sload
+            | OpCode::Call
+            | OpCode::Delegatecall
+            | OpCode::Staticcall => 100,
+            OpCode::Log0 | OpCode::Log1 | OpCode::Log2 | OpCode::Log3 | OpCode::Log4 => {
+                375 * (self.topics() as u64 + 1)
+            }
+            OpCode::Selfdestruct => 5000,
+            OpCode::Create => 32000,
+            _ => 3, // arithmetic, comparison, bitwise, memory, `PUSH`, `DUP` and `SWAP` opcodes
+        }
+    }
+
+    /// Helper function to determine the number of items popped from and pushed to the stack
+    pub fn stack_items(&self) -> (usize, usize) {
+        match self {
+            OpCode::Stop | OpCode::Jumpdest => (0, 0),
+            OpCode::Address
+            | OpCode::Origin
+            | OpCode::Caller
+            | OpCode::Callvalue
+            | OpCode::Calldatasize
+            | OpCode::Codesize
+            | OpCode::Gasprice
+            | OpCode::Returndatasize
+            | OpCode::Coinbase
+            | OpCode::Timestamp
+            | OpCode::Number
+            | OpCode::Difficulty
+            | OpCode::Gaslimit
+            | OpCode::Chainid
+            | OpCode::Selfbalance
+            | OpCode::Basfee
+            | OpCode::Blobbasefee
+            | OpCode::Pc
+            | OpCode::Msize
+            | OpCode::Gas => (0, 1),
+            OpCode::Iszero
+            | OpCode::Not
+            | OpCode::Balance
+            | OpCode::Calldataload
+            | OpCode::Extcodesize
+            | OpCode::Extcodehash
+            | OpCode::Blockhash
+            | OpCode::Mload
+            | OpCode::Sload => (1, 1),
+            OpCode::Pop | OpCode::Jump | OpCode::Selfdestruct => (1, 0),
+            OpCode::Mstore
+            | OpCode::Mstore8
+            | OpCode::Sstore
+            | OpCode::Jumpi
+            | OpCode::Return
+            | OpCode::Revert => (2, 0),
+            OpCode::Calldatacopy | OpCode::Codecopy | OpCode::Returndatacopy => (3, 0),
+            OpCode::Extcodecopy => (4, 0),
+            OpCode::Addmod | OpCode::Mulmod | OpCode::Create => (3, 1),
+            OpCode::Delegatecall | OpCode::Staticcall => (6, 1),
+            OpCode::Call => (7, 1),
+            OpCode::Log0 | OpCode::Log1 | OpCode::Log2 | OpCode::Log3 | OpCode::Log4 => {
+                (2 + self.topics(), 0)
+            }
+            _ if self.is_push() => (0, 1),
+            _ if self.is_dup() => (self.data_index(), self.data_index() + 1),
+            _ if self.is_swap() => (self.data_index() + 1, self.data_index() + 1),
+            _ => (2, 1), // arithmetic, comparison, bitwise and `SHA3` opcodes
+        }
+    }
+
+    /// Returns the mnemonic of the opcode, as printed by geth and solc.
+    pub fn name(&self) -> String {
+        match self {
+            OpCode::Sha3 => "KECCAK256".to_string(),
+            OpCode::Basfee => "BASEFEE".to_string(),
+            _ => format!("{:?}", self).to_uppercase(),
+        }
+    }
+
     pub fn is_push(&self) -> bool {
         OpCode::Push0 <= *self && *self <= OpCode::Push32
     }
+
+    pub fn is_dup(&self) -> bool {
+        OpCode::Dup1 <= *self && *self <= OpCode::Dup16
+    }
+
+    pub fn is_swap(&self) -> bool {
+        OpCode::Swap1 <= *self && *self <= OpCode::Swap16
+    }
+}
+
+/// Returns the mnemonic of `opcode`, also for bytes that are not valid opcodes.
+pub fn mnemonic(opcode: u8) -> String {
+    match OpCode::new(opcode) {
+        Some(opcode) => opcode.name(),
+        None if opcode == 0xfe => "INVALID".to_string(),
+        None => format!("opcode {:#04x} not defined", opcode),
+    }
 }
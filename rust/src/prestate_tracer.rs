@@ -0,0 +1,319 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use primitive_types::U256;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    inspector::{CallInputs, CreateInputs, CreateOutcome, FrameOutcome, Inspector, TraceStep},
+    opcode::OpCode,
+    state_data::State,
+    storage::Storage,
+};
+
+/// An account as reported by the `PrestateTracer`. Fields left empty are omitted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountState {
+    pub balance: Option<U256>,
+    pub nonce: Option<usize>,
+    pub code: Option<Vec<u8>>,
+    pub storage: BTreeMap<U256, U256>,
+}
+
+impl AccountState {
+    /// Serializes the account in the format of geth's `prestateTracer`.
+    pub fn to_json(&self) -> Value {
+        let mut account = json!({});
+        if let Some(balance) = self.balance {
+            account["balance"] = Value::from(format!("{:#x}", balance));
+        }
+        if let Some(nonce) = self.nonce {
+            account["nonce"] = Value::from(nonce);
+        }
+        if let Some(code) = &self.code {
+            account["code"] = Value::from(format!("0x{}", hex::encode(code)));
+        }
+        if !self.storage.is_empty() {
+            let storage: Map<String, Value> = self
+                .storage
+                .iter()
+                .map(|(slot, value)| {
+                    (
+                        format!("0x{:064x}", slot),
+                        Value::from(format!("0x{:064x}", value)),
+                    )
+                })
+                .collect();
+            account["storage"] = Value::from(storage);
+        }
+        account
+    }
+}
+
+/// The accounts changed by an execution, before and after it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateDiff {
+    /// The changed accounts before the execution. Created accounts are not included.
+    pub pre: BTreeMap<U256, AccountState>,
+    /// Only the changed fields of the accounts after the execution.
+    /// Deleted accounts are not included, and cleared slots are omitted.
+    pub post: BTreeMap<U256, AccountState>,
+}
+
+impl StateDiff {
+    /// Serializes the diff in the format of geth's `prestateTracer` with `diffMode` enabled.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "pre": accounts_to_json(&self.pre),
+            "post": accounts_to_json(&self.post),
+        })
+    }
+}
+
+/// An inspector recording the accounts and the storage slots touched by an execution,
+/// to report their state before the execution and how the execution changed them.
+#[derive(Debug)]
+pub struct PrestateTracer {
+    state: State,
+    storage: Storage,
+    /// The touched accounts, with their touched slots.
+    touched: BTreeMap<U256, BTreeSet<U256>>,
+}
+
+impl PrestateTracer {
+    /// Creates a tracer for an execution starting from `state` and `storage`.
+    pub fn new(state: State, storage: Storage) -> PrestateTracer {
+        PrestateTracer {
+            state,
+            storage,
+            touched: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the state before the execution of every touched account and slot.
+    pub fn prestate(&self) -> BTreeMap<U256, AccountState> {
+        self.touched
+            .iter()
+            .map(|(address, slots)| {
+                let mut account = account_state(&self.state, *address);
+                account.storage = slots
+                    .iter()
+                    .map(|slot| (*slot, self.storage.load_word(*address, *slot)))
+                    .collect();
+                (*address, account)
+            })
+            .collect()
+    }
+
+    /// Compares the touched accounts and slots with `state` and `storage`, the ones left by
+    /// the execution.
+    pub fn diff(&self, state: &State, storage: &Storage) -> StateDiff {
+        let mut diff = StateDiff::default();
+        for (address, slots) in &self.touched {
+            let address = *address;
+            let existed = self.state.exists(address);
+            let exists = state.exists(address);
+            let pre = account_state(&self.state, address);
+            let post = account_state(state, address);
+
+            let changed_slots: Vec<(U256, U256, U256)> = slots
+                .iter()
+                .map(|slot| {
+                    let before = self.storage.load_word(address, *slot);
+                    (*slot, before, storage.load_word(address, *slot))
+                })
+                .filter(|(_, before, after)| before != after)
+                .collect();
+
+            if existed == exists && pre == post && changed_slots.is_empty() {
+                continue;
+            }
+            if existed {
+                let mut pre = pre.clone();
+                pre.storage = changed_slots
+                    .iter()
+                    .filter(|(_, before, _)| !before.is_zero())
+                    .map(|(slot, before, _)| (*slot, *before))
+                    .collect();
+                diff.pre.insert(address, pre);
+            }
+            // the storage of an account can change even if the account isn't in the state.
+            if exists || !changed_slots.is_empty() {
+                let mut changes = AccountState {
+                    balance: post
+                        .balance
+                        .filter(|_| !existed || post.balance != pre.balance),
+                    nonce: post.nonce.filter(|_| !existed || post.nonce != pre.nonce),
+                    code: post
+                        .code
+                        .clone()
+                        .filter(|_| !existed || post.code != pre.code),
+                    storage: BTreeMap::new(),
+                };
+                changes.storage = changed_slots
+                    .iter()
+                    .filter(|(_, _, after)| !after.is_zero())
+                    .map(|(slot, _, after)| (*slot, *after))
+                    .collect();
+                diff.post.insert(address, changes);
+            }
+        }
+        diff
+    }
+
+    /// Returns the pre-state in the format of geth's `prestateTracer`.
+    pub fn to_json(&self) -> Value {
+        accounts_to_json(&self.prestate())
+    }
+
+    fn touch(&mut self, address: U256) {
+        self.touched.entry(address).or_default();
+    }
+
+    fn touch_slot(&mut self, address: U256, slot: U256) {
+        self.touched.entry(address).or_default().insert(slot);
+    }
+}
+
+impl Inspector for PrestateTracer {
+    fn step(&mut self, step: &TraceStep) {
+        let Some(top) = step.stack.last().copied() else {
+            return;
+        };
+        match OpCode::new(step.opcode) {
+            Some(OpCode::Sload | OpCode::Sstore) => self.touch_slot(step.address, top),
+            Some(
+                OpCode::Balance | OpCode::Extcodesize | OpCode::Extcodecopy | OpCode::Extcodehash,
+            ) => self.touch(top),
+            _ => {}
+        }
+    }
+
+    fn call(&mut self, inputs: &CallInputs) -> Option<FrameOutcome> {
+        self.touch(inputs.caller);
+        self.touch(inputs.target);
+        self.touch(inputs.code_address);
+        None
+    }
+
+    fn create(&mut self, inputs: &CreateInputs) -> Option<CreateOutcome> {
+        self.touch(inputs.caller);
+        self.touch(inputs.address);
+        None
+    }
+
+    fn selfdestruct(&mut self, address: U256, beneficiary: U256, _value: U256) {
+        self.touch(address);
+        self.touch(beneficiary);
+    }
+}
+
+/// Reads an account, omitting the zero nonce and the empty code like geth does.
+fn account_state(state: &State, address: U256) -> AccountState {
+    let code = state.get_code(address);
+    let nonce = state.get_nonce(address);
+    AccountState {
+        balance: Some(state.get_balance(address)),
+        nonce: (nonce != 0).then_some(nonce),
+        code: (!code.is_empty()).then_some(code),
+        storage: BTreeMap::new(),
+    }
+}
+
+fn accounts_to_json(accounts: &BTreeMap<U256, AccountState>) -> Value {
+    let accounts: Map<String, Value> = accounts
+        .iter()
+        .map(|(address, account)| (format!("0x{:040x}", address), account.to_json()))
+        .collect();
+    Value::from(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use primitive_types::U256;
+    use serde_json::json;
+
+    use super::PrestateTracer;
+    use crate::{
+        address::Address, assembler::assemble, builder::Tx, evm::Evm, state_data::State,
+        storage::Storage, utility::contract_address,
+    };
+
+    #[test]
+    fn created_account_and_cleared_slot() {
+        let contract = U256::from(0xaa);
+        // clears slot 1, sets slot 2, then CREATEs a contract whose runtime code is 0xff.
+        let code = assemble(
+            "
+            PUSH1 0 PUSH1 1 SSTORE  PUSH1 7 PUSH1 2 SSTORE
+            PUSH10 0x60ff60005360016000f3 PUSH1 0 MSTORE  PUSH1 10 PUSH1 22 PUSH1 0 CREATE
+            STOP
+            ",
+        )
+        .unwrap();
+        let mut state = State::default();
+        state.set_code(contract, code);
+        let mut storage = Storage::new_empty();
+        storage.set_word(contract, 1.into(), 5.into());
+
+        let tracer = Rc::new(RefCell::new(PrestateTracer::new(
+            state.clone(),
+            storage.clone(),
+        )));
+        let result = Evm::builder()
+            .tx(Tx {
+                to: Address::from(contract),
+                ..Tx::default()
+            })
+            .state(state)
+            .storage(storage)
+            .inspector(Some(tracer.clone()))
+            .build()
+            .run();
+        assert!(result.success);
+        let created = contract_address(contract, 0);
+        assert_eq!(result.stack, vec![created]);
+
+        let tracer = tracer.borrow();
+        let code = format!("0x{}", hex::encode(result.state.get_code(contract)));
+        let word = |value: u64| format!("0x{:064x}", value);
+        assert_eq!(
+            tracer.to_json(),
+            json!({
+                "0x0000000000000000000000000000000000000000": { "balance": "0x0" },
+                format!("0x{:040x}", contract): {
+                    "balance": "0x0",
+                    "code": code,
+                    "storage": { word(1): word(5), word(2): word(0) },
+                },
+                format!("0x{:040x}", created): { "balance": "0x0" },
+            })
+        );
+
+        let diff = tracer.diff(&result.state, &result.storage);
+        assert_eq!(
+            diff.to_json(),
+            json!({
+                "pre": {
+                    format!("0x{:040x}", contract): {
+                        "balance": "0x0",
+                        "code": code,
+                        "storage": { word(1): word(5) },
+                    },
+                },
+                "post": {
+                    format!("0x{:040x}", contract): {
+                        "nonce": 1,
+                        "storage": { word(2): word(7) },
+                    },
+                    format!("0x{:040x}", created): {
+                        "balance": "0x0",
+                        "nonce": 1,
+                        "code": "0xff",
+                    },
+                },
+            })
+        );
+    }
+}
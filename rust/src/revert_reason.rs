@@ -0,0 +1,192 @@
+use std::fmt;
+
+use primitive_types::U256;
+
+use crate::abi::{decode_tokens, Abi, ParamType, Token};
+
+/// Selector of `Error(string)`, used by `require` and `revert` with a message.
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Selector of `Panic(uint256)`, used by failing asserts and checked arithmetic.
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// The decoded payload of a `REVERT`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RevertReason {
+    /// `Error(string)`.
+    Error(String),
+    /// `Panic(uint256)`.
+    Panic(U256),
+    /// A custom error declared in the ABI, with its arguments already formatted.
+    Custom { name: String, args: Vec<String> },
+    /// Data that doesn't match any known error.
+    Unknown(Vec<u8>),
+}
+
+impl RevertReason {
+    /// Decodes `Error(string)` and `Panic(uint256)` revert data.
+    pub fn decode(data: &[u8]) -> RevertReason {
+        if data.len() < 4 {
+            return RevertReason::Unknown(data.to_vec());
+        }
+        let (selector, args) = data.split_at(4);
+
+        if selector == ERROR_SELECTOR {
+            if let Ok([Token::String(message)]) =
+                decode_tokens(&[ParamType::String], args).as_deref()
+            {
+                return RevertReason::Error(message.clone());
+            }
+        } else if selector == PANIC_SELECTOR {
+            if let Ok([Token::Uint(code)]) = decode_tokens(&[ParamType::Uint(256)], args).as_deref()
+            {
+                return RevertReason::Panic(*code);
+            }
+        }
+        RevertReason::Unknown(data.to_vec())
+    }
+
+    /// Like `decode`, but also recognizes the custom errors declared in `abi`.
+    pub fn decode_with_abi(data: &[u8], abi: &Abi) -> RevertReason {
+        let reason = RevertReason::decode(data);
+        if !matches!(reason, RevertReason::Unknown(_)) {
+            return reason;
+        }
+
+        for error in &abi.errors {
+            if let Ok(args) = error.decode(data) {
+                return RevertReason::Custom {
+                    name: error.name.clone(),
+                    args: args.iter().map(Token::to_string).collect(),
+                };
+            }
+        }
+        reason
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevertReason::Error(message) => write!(f, "Error({:?})", message),
+            RevertReason::Panic(code) => {
+                write!(f, "Panic(0x{:02x}): {}", code, panic_description(*code))
+            }
+            RevertReason::Custom { name, args } => write!(f, "{}({})", name, args.join(", ")),
+            RevertReason::Unknown(data) if data.is_empty() => write!(f, "no revert data"),
+            RevertReason::Unknown(data) => write!(f, "0x{}", hex::encode(data)),
+        }
+    }
+}
+
+/// Returns the meaning of a Solidity panic code.
+pub fn panic_description(code: U256) -> &'static str {
+    if code > U256::from(u8::MAX) {
+        return "unknown panic code";
+    }
+    match code.low_u32() {
+        0x00 => "generic compiler inserted panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic underflow or overflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "conversion into non-existent enum type",
+        0x22 => "access to incorrectly encoded storage byte array",
+        0x31 => "pop on empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "memory allocation overflow",
+        0x51 => "call to zero-initialized function pointer",
+        _ => "unknown panic code",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use primitive_types::U256;
+
+    use super::{panic_description, RevertReason, ERROR_SELECTOR, PANIC_SELECTOR};
+    use crate::abi::{encode_tokens, Abi, Token};
+
+    fn payload(selector: [u8; 4], args: &[Token]) -> Vec<u8> {
+        [selector.to_vec(), encode_tokens(args)].concat()
+    }
+
+    #[test]
+    fn error_strings() {
+        let data = payload(ERROR_SELECTOR, &[Token::String("not owner".to_string())]);
+        let reason = RevertReason::decode(&data);
+        assert_eq!(reason, RevertReason::Error("not owner".to_string()));
+        assert_eq!(reason.to_string(), "Error(\"not owner\")");
+    }
+
+    #[test]
+    fn panic_codes() {
+        let data = payload(PANIC_SELECTOR, &[Token::Uint(0x11.into())]);
+        let reason = RevertReason::decode(&data);
+        assert_eq!(reason, RevertReason::Panic(0x11.into()));
+        assert_eq!(
+            reason.to_string(),
+            "Panic(0x11): arithmetic underflow or overflow"
+        );
+        assert_eq!(panic_description(0x01.into()), "assertion failed");
+        assert_eq!(panic_description(0x32.into()), "array index out of bounds");
+        assert_eq!(panic_description(0x02.into()), "unknown panic code");
+        assert_eq!(
+            panic_description(U256::from(0x100) + 0x01),
+            "unknown panic code"
+        );
+    }
+
+    #[test]
+    fn custom_errors_need_the_abi() {
+        let abi =
+            Abi::parse("error InsufficientBalance(uint256 available, uint256 required)").unwrap();
+        let error = abi.error("InsufficientBalance").unwrap();
+        let data = payload(
+            error.selector(),
+            &[Token::Uint(1.into()), Token::Uint(2.into())],
+        );
+        assert_eq!(
+            RevertReason::decode(&data),
+            RevertReason::Unknown(data.clone())
+        );
+
+        let reason = RevertReason::decode_with_abi(&data, &abi);
+        assert_eq!(
+            reason,
+            RevertReason::Custom {
+                name: "InsufficientBalance".to_string(),
+                args: vec!["1".to_string(), "2".to_string()],
+            }
+        );
+        assert_eq!(reason.to_string(), "InsufficientBalance(1, 2)");
+
+        // the standard errors are decoded first.
+        let data = payload(PANIC_SELECTOR, &[Token::Uint(1.into())]);
+        assert_eq!(
+            RevertReason::decode_with_abi(&data, &abi),
+            RevertReason::Panic(1.into())
+        );
+    }
+
+    #[test]
+    fn malformed_payloads_are_kept_raw() {
+        let unknown = |data: &[u8]| RevertReason::Unknown(data.to_vec());
+        assert_eq!(RevertReason::decode(&[]), unknown(&[]));
+        assert_eq!(RevertReason::decode(&[]).to_string(), "no revert data");
+        assert_eq!(RevertReason::decode(&[0x08, 0xc3]), unknown(&[0x08, 0xc3]));
+
+        // the string is cut short.
+        let mut data = payload(ERROR_SELECTOR, &[Token::String("not owner".to_string())]);
+        data.truncate(data.len() - 32);
+        assert_eq!(RevertReason::decode(&data), unknown(&data));
+
+        let data = [PANIC_SELECTOR.to_vec(), vec![0x11]].concat();
+        assert_eq!(RevertReason::decode(&data), unknown(&data));
+        assert_eq!(RevertReason::decode(&data).to_string(), "0x4e487b7111");
+
+        // the arguments don't match the declared error.
+        let abi = Abi::parse("error Unauthorized(address caller)").unwrap();
+        let data = [abi.errors[0].selector().to_vec(), vec![1, 2]].concat();
+        assert_eq!(RevertReason::decode_with_abi(&data, &abi), unknown(&data));
+    }
+}
@@ -0,0 +1,212 @@
+use primitive_types::U256;
+
+use crate::errors::RlpError;
+
+/// The deepest nesting of lists accepted by the decoder. Transactions need 4 levels: the
+/// transaction, its access list, an entry and its storage keys.
+const MAX_DEPTH: usize = 8;
+
+/// An item of the Recursive Length Prefix encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rlp {
+    Bytes(Vec<u8>),
+    List(Vec<Rlp>),
+}
+
+impl Rlp {
+    /// Returns the item encoding `value`, as big endian bytes without leading zeros.
+    pub fn uint(value: impl Into<U256>) -> Rlp {
+        let mut bytes = [0u8; 32];
+        value.into().to_big_endian(&mut bytes);
+        let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(32);
+        Rlp::Bytes(bytes[start..].to_vec())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Rlp::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => bytes.clone(),
+            Rlp::Bytes(bytes) => [encode_length(bytes.len(), 0x80), bytes.clone()].concat(),
+            Rlp::List(items) => {
+                let payload: Vec<u8> = items.iter().flat_map(Rlp::encode).collect();
+                [encode_length(payload.len(), 0xc0), payload].concat()
+            }
+        }
+    }
+
+    /// Decodes `data`, which must be exactly one item.
+    pub fn decode(data: &[u8]) -> Result<Rlp, RlpError> {
+        let (item, size) = decode_item(data, 0)?;
+        if size != data.len() {
+            return Err(RlpError::TrailingBytes);
+        }
+        Ok(item)
+    }
+
+    pub fn as_bytes(&self) -> Result<&[u8], RlpError> {
+        match self {
+            Rlp::Bytes(bytes) => Ok(bytes),
+            Rlp::List(_) => Err(RlpError::ExpectedBytes),
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[Rlp], RlpError> {
+        match self {
+            Rlp::List(items) => Ok(items),
+            Rlp::Bytes(_) => Err(RlpError::ExpectedList),
+        }
+    }
+
+    pub fn as_u256(&self) -> Result<U256, RlpError> {
+        let bytes = self.as_bytes()?;
+        if bytes.len() > 32 {
+            return Err(RlpError::IntegerTooLarge);
+        }
+        Ok(U256::from_big_endian(bytes))
+    }
+
+    pub fn as_u64(&self) -> Result<u64, RlpError> {
+        let value = self.as_u256()?;
+        if value > U256::from(u64::MAX) {
+            return Err(RlpError::IntegerTooLarge);
+        }
+        Ok(value.as_u64())
+    }
+}
+
+/// Encodes the prefix of a payload of `length` bytes. `offset` is 0x80 for strings and 0xc0
+/// for lists.
+fn encode_length(length: usize, offset: u8) -> Vec<u8> {
+    if length < 56 {
+        return vec![offset + length as u8];
+    }
+    let bytes = length.to_be_bytes();
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    let length_bytes = &bytes[start..];
+    [
+        vec![offset + 55 + length_bytes.len() as u8],
+        length_bytes.to_vec(),
+    ]
+    .concat()
+}
+
+/// Decodes the item at the start of `data`, nested in `depth` lists, returning it with its
+/// encoded size.
+fn decode_item(data: &[u8], depth: usize) -> Result<(Rlp, usize), RlpError> {
+    let prefix = *data.first().ok_or(RlpError::UnexpectedEnd)?;
+    let (is_list, start, length) = match prefix {
+        0x00..=0x7f => return Ok((Rlp::Bytes(vec![prefix]), 1)),
+        0x80..=0xb7 => (false, 1, (prefix - 0x80) as usize),
+        0xb8..=0xbf => decode_long_length(data, false, (prefix - 0xb7) as usize)?,
+        0xc0..=0xf7 => (true, 1, (prefix - 0xc0) as usize),
+        0xf8..=0xff => decode_long_length(data, true, (prefix - 0xf7) as usize)?,
+    };
+    let end = start.checked_add(length).ok_or(RlpError::UnexpectedEnd)?;
+    let payload = data.get(start..end).ok_or(RlpError::UnexpectedEnd)?;
+    if !is_list {
+        return Ok((Rlp::Bytes(payload.to_vec()), end));
+    }
+    if depth == MAX_DEPTH {
+        return Err(RlpError::TooDeep);
+    }
+
+    let mut items = vec![];
+    let mut offset = 0;
+    while offset < payload.len() {
+        let (item, size) = decode_item(&payload[offset..], depth + 1)?;
+        items.push(item);
+        offset += size;
+    }
+    Ok((Rlp::List(items), end))
+}
+
+/// Reads the length of a long string or list, stored in the `size` bytes after the prefix.
+fn decode_long_length(
+    data: &[u8],
+    is_list: bool,
+    size: usize,
+) -> Result<(bool, usize, usize), RlpError> {
+    let bytes = data.get(1..1 + size).ok_or(RlpError::UnexpectedEnd)?;
+    if size > std::mem::size_of::<usize>() {
+        return Err(RlpError::IntegerTooLarge);
+    }
+    let length = bytes
+        .iter()
+        .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+    Ok((is_list, 1 + size, length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_length, Rlp, MAX_DEPTH};
+    use crate::errors::RlpError;
+
+    fn bytes(value: &str) -> Rlp {
+        Rlp::Bytes(value.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn spec_examples() {
+        let lorem = "Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        let examples = [
+            (bytes("dog"), "83646f67"),
+            (
+                Rlp::List(vec![bytes("cat"), bytes("dog")]),
+                "c88363617483646f67",
+            ),
+            (bytes(""), "80"),
+            (Rlp::List(vec![]), "c0"),
+            (Rlp::uint(0), "80"),
+            (Rlp::Bytes(vec![0]), "00"),
+            (Rlp::uint(15), "0f"),
+            (Rlp::uint(1024), "820400"),
+            // the set theoretical representation of three.
+            (
+                Rlp::List(vec![
+                    Rlp::List(vec![]),
+                    Rlp::List(vec![Rlp::List(vec![])]),
+                    Rlp::List(vec![Rlp::List(vec![]), Rlp::List(vec![Rlp::List(vec![])])]),
+                ]),
+                "c7c0c1c0c3c0c1c0",
+            ),
+            (bytes(lorem), &format!("b838{}", hex::encode(lorem))),
+        ];
+        for (item, encoded) in examples {
+            let encoded = hex::decode(encoded).unwrap();
+            assert_eq!(item.encode(), encoded);
+            assert_eq!(Rlp::decode(&encoded).unwrap(), item);
+        }
+    }
+
+    #[test]
+    fn malformed_data() {
+        let decode = |data: &str| Rlp::decode(&hex::decode(data).unwrap()).unwrap_err();
+        assert_eq!(decode(""), RlpError::UnexpectedEnd);
+        assert_eq!(decode("83646f"), RlpError::UnexpectedEnd);
+        assert_eq!(decode("c88363617483646f"), RlpError::UnexpectedEnd);
+        assert_eq!(decode("83646f6700"), RlpError::TrailingBytes);
+        assert_eq!(decode("bfffffffffffffffff"), RlpError::UnexpectedEnd);
+    }
+
+    #[test]
+    fn nesting_is_bounded() {
+        let nested =
+            |depth: usize| (0..depth).fold(Rlp::List(vec![]), |item, _| Rlp::List(vec![item]));
+        let deepest = nested(MAX_DEPTH - 1);
+        assert_eq!(Rlp::decode(&deepest.encode()).unwrap(), deepest);
+        assert_eq!(
+            Rlp::decode(&nested(MAX_DEPTH).encode()).unwrap_err(),
+            RlpError::TooDeep
+        );
+        // 100000 nested lists fail without exhausting the stack, built backwards to stay linear.
+        let mut reversed = vec![0xc0];
+        for _ in 0..100_000 {
+            let prefix = encode_length(reversed.len(), 0xc0);
+            reversed.extend(prefix.iter().rev());
+        }
+        reversed.reverse();
+        assert_eq!(Rlp::decode(&reversed).unwrap_err(), RlpError::TooDeep);
+    }
+}
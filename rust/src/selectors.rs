@@ -0,0 +1,190 @@
+use crate::{
+    disassembler::{find_metadata, instructions, Instruction},
+    opcode::OpCode,
+};
+
+/// How many instructions after an entry are searched for the `CALLVALUE` check.
+const GUARD_WINDOW: usize = 8;
+
+/// How many instructions apart two comparisons of the dispatcher can be.
+const DISPATCHER_WINDOW: usize = 8;
+
+/// A public function found in the selector dispatcher.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionEntry {
+    pub selector: [u8; 4],
+    /// Where the dispatcher jumps for the selector.
+    pub pc: usize,
+    /// False if the function reverts when called with some value.
+    pub payable: bool,
+}
+
+/// Finds the functions of a contract in its selector dispatcher, the code comparing the first
+/// 4 bytes of the calldata with each selector.
+///
+/// solc compares with `PUSH4 selector EQ PUSH2 entry JUMPI`, with a `DUP1` in between, while
+/// Vyper jumps to the next comparison with `PUSH4 selector DUP2 XOR PUSH2 next JUMPI`, so the
+/// entry is the following instruction. A function is non-payable if `CALLVALUE` is checked
+/// right at its entry, or before the dispatcher if no function is payable.
+///
+/// Selectors with leading zero bytes are pushed with a shorter `PUSH`. As any comparison of a
+/// small constant looks the same, those are only accepted next to other comparisons of the
+/// dispatcher.
+pub fn function_entries(code: &[u8]) -> Vec<FunctionEntry> {
+    let end = find_metadata(code)
+        .map(|metadata| metadata.offset)
+        .unwrap_or(code.len());
+    let instructions = instructions(&code[..end]);
+
+    let comparisons: Vec<(usize, [u8; 4], usize)> = (0..instructions.len())
+        .filter_map(|index| {
+            match_comparison(&instructions, index).map(|(selector, entry)| (index, selector, entry))
+        })
+        .collect();
+    // the `PUSH4` comparisons are in the dispatcher, and so are the shorter ones next to them.
+    let mut dispatcher: Vec<bool> = comparisons
+        .iter()
+        .map(|(index, ..)| instructions[*index].immediate.len() == 4)
+        .collect();
+    loop {
+        let accepted: Vec<usize> = (0..comparisons.len())
+            .filter(|&candidate| {
+                !dispatcher[candidate]
+                    && (0..comparisons.len()).any(|other| {
+                        dispatcher[other]
+                            && comparisons[candidate].0.abs_diff(comparisons[other].0)
+                                <= DISPATCHER_WINDOW
+                    })
+            })
+            .collect();
+        if accepted.is_empty() {
+            break;
+        }
+        accepted
+            .iter()
+            .for_each(|&candidate| dispatcher[candidate] = true);
+    }
+
+    let mut entries: Vec<FunctionEntry> = vec![];
+    let mut dispatcher_start = None;
+    for ((index, selector, entry), _) in comparisons
+        .into_iter()
+        .zip(dispatcher)
+        .filter(|(_, dispatcher)| *dispatcher)
+    {
+        dispatcher_start.get_or_insert(index);
+        if entries.iter().any(|function| function.selector == selector) {
+            continue;
+        }
+        let Some(entry) = instructions
+            .iter()
+            .position(|instruction| instruction.pc == entry)
+        else {
+            continue;
+        };
+        entries.push(FunctionEntry {
+            selector,
+            pc: instructions[entry].pc,
+            payable: !has_callvalue_guard(&instructions[entry..]),
+        });
+    }
+
+    // the check before the dispatcher applies to every function.
+    if let Some(start) = dispatcher_start {
+        let prelude = &instructions[..start];
+        if (0..start).any(|index| {
+            prelude[index].opcode == OpCode::Callvalue as u8
+                && has_callvalue_guard(&prelude[index..])
+        }) {
+            entries
+                .iter_mut()
+                .for_each(|function| function.payable = false);
+        }
+    }
+    entries
+}
+
+/// Matches a selector comparison starting at `index`, returning the selector and the pc of
+/// the function it leads to.
+fn match_comparison(instructions: &[Instruction], index: usize) -> Option<([u8; 4], usize)> {
+    let push = &instructions[index];
+    let opcode = OpCode::new(push.opcode)?;
+    if !opcode.is_push() || !(1..=4).contains(&push.immediate.len()) {
+        return None;
+    }
+    let mut rest = instructions[index + 1..].iter();
+    let mut comparison = rest.next()?;
+    if OpCode::new(comparison.opcode)?.is_dup() {
+        comparison = rest.next()?;
+    }
+    let comparison = OpCode::new(comparison.opcode)?;
+    let target = rest.next()?;
+    let jumpi = rest.next()?;
+    if !OpCode::new(target.opcode)?.is_push() || jumpi.opcode != OpCode::Jumpi as u8 {
+        return None;
+    }
+
+    let mut selector = [0u8; 4];
+    selector[4 - push.immediate.len()..].copy_from_slice(&push.immediate);
+    let entry = match comparison {
+        // a target wider than a `usize` can't be a position in the code.
+        OpCode::Eq => target.immediate.iter().try_fold(0usize, |value, byte| {
+            value.checked_mul(256)?.checked_add(*byte as usize)
+        })?,
+        OpCode::Xor | OpCode::Sub => jumpi.pc + jumpi.size(),
+        _ => return None,
+    };
+    Some((selector, entry))
+}
+
+/// Returns true if `instructions` start by checking `CALLVALUE` and jumping on it.
+fn has_callvalue_guard(instructions: &[Instruction]) -> bool {
+    let mut callvalue = false;
+    for instruction in instructions.iter().take(GUARD_WINDOW) {
+        match OpCode::new(instruction.opcode) {
+            Some(OpCode::Callvalue) => callvalue = true,
+            Some(OpCode::Jumpi) => return callvalue,
+            _ if instruction.is_terminator() => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    #[test]
+    fn wide_jump_targets_are_ignored() {
+        let code = hex::decode(format!("63aabbccdd147f{}57", "ff".repeat(32))).unwrap();
+        assert_eq!(function_entries(&code), vec![]);
+    }
+
+    #[test]
+    fn short_selectors_only_in_the_dispatcher() {
+        let code = assemble(
+            "
+            PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR
+            DUP1 PUSH2 0xaabb EQ PUSH @a JUMPI
+            DUP1 PUSH4 0x11223344 EQ PUSH @b JUMPI
+            PUSH0 DUP1 REVERT
+            a: JUMPDEST STOP
+            b: JUMPDEST
+            PUSH1 1 POP PUSH1 1 POP PUSH1 1 POP PUSH1 1 POP PUSH1 1 POP
+            PUSH1 5 EQ PUSH @c JUMPI
+            c: JUMPDEST STOP
+            ",
+        )
+        .unwrap();
+        let selectors: Vec<[u8; 4]> = function_entries(&code)
+            .iter()
+            .map(|function| function.selector)
+            .collect();
+        assert_eq!(
+            selectors,
+            vec![[0, 0, 0xaa, 0xbb], [0x11, 0x22, 0x33, 0x44]]
+        );
+    }
+}
@@ -1,13 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use primitive_types::U256;
 
 use crate::errors::ExecutionError;
 
 /// State data.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct State {
-    pub state: Vec<StateData>,
+    /// Private, so that every write goes through the setters and is recorded in `written`.
+    state: Vec<StateData>,
+    /// The accounts written since the last `take_written`.
+    written: HashSet<U256>,
 }
 
 #[derive(Debug, Clone)]
@@ -24,13 +27,13 @@ impl StateData {
             code,
         };
         StateData {
-            address: address,
+            address,
             data: address_data,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct AddressData {
     pub nonce: usize,
     pub balance: U256,
@@ -51,14 +54,50 @@ impl State {
             };
             state.push(state_data);
         }
-        State { state }
+        State {
+            state,
+            written: HashSet::new(),
+        }
+    }
+
+    /// Returns the addresses of the accounts, in insertion order.
+    pub fn addresses(&self) -> impl Iterator<Item = U256> + '_ {
+        self.state.iter().map(|account| account.address)
+    }
+
+    pub(crate) fn account(&self, address: U256) -> Option<&AddressData> {
+        self.state
+            .iter()
+            .find(|account| account.address == address)
+            .map(|account| &account.data)
+    }
+
+    /// Puts back the account at `address` as recorded by a journal, `None` if it didn't exist.
+    /// Not recorded as written.
+    pub(crate) fn restore_account(&mut self, address: U256, data: Option<AddressData>) {
+        let index = self
+            .state
+            .iter()
+            .position(|account| account.address == address);
+        match (index, data) {
+            (Some(index), Some(data)) => self.state[index].data = data,
+            (Some(index), None) => {
+                self.state.remove(index);
+            }
+            (None, Some(data)) => self.state.push(StateData { address, data }),
+            (None, None) => {}
+        }
+    }
+
+    pub fn exists(&self, address: U256) -> bool {
+        self.state.iter().any(|elem| elem.address == address)
     }
 
     pub fn get_balance(&self, address: U256) -> U256 {
         self.state
             .iter()
             .find(|elem| elem.address == address)
-            .map(|elem| elem.data.balance.clone())
+            .map(|elem| elem.data.balance)
             .unwrap_or_default()
     }
 
@@ -74,7 +113,7 @@ impl State {
         self.state
             .iter()
             .find(|elem| elem.address == address)
-            .map(|elem| elem.data.nonce.clone())
+            .map(|elem| elem.data.nonce)
             .unwrap_or_default()
     }
 
@@ -97,16 +136,45 @@ impl State {
                     data: address_data,
                 };
                 self.state.push(state_data);
+                self.written.insert(address);
                 Ok(())
             }
         }
     }
 
+    pub fn set_balance(&mut self, address: U256, balance: U256) {
+        self.account_mut(address).balance = balance;
+    }
+
+    pub fn set_nonce(&mut self, address: U256, nonce: usize) {
+        self.account_mut(address).nonce = nonce;
+    }
+
+    pub fn set_code(&mut self, address: U256, code: Vec<u8>) {
+        self.account_mut(address).code = code;
+    }
+
+    /// Returns the account at `address`, creating an empty one if it doesn't exist.
+    fn account_mut(&mut self, address: U256) -> &mut AddressData {
+        self.written.insert(address);
+        let index = match self.state.iter().position(|elem| elem.address == address) {
+            Some(index) => index,
+            None => {
+                self.state
+                    .push(StateData::new(address, U256::zero(), vec![]));
+                self.state.len() - 1
+            }
+        };
+        &mut self.state[index].data
+    }
+
     pub fn delete_account(&mut self, address: U256) {
+        self.written.insert(address);
         self.state.retain(|account| account.address != address);
     }
 
     pub fn transfer_balance(&mut self, balance: U256, dest: U256) {
+        self.written.insert(dest);
         if let Some(account) = self.state.iter_mut().find(|elem| elem.address == dest) {
             account.data.balance += balance;
         } else {
@@ -114,4 +182,9 @@ impl State {
             self.state.push(new_account);
         }
     }
+
+    /// Returns the accounts written since the last call, so that a journal can record them.
+    pub(crate) fn take_written(&mut self) -> HashSet<U256> {
+        std::mem::take(&mut self.written)
+    }
 }
@@ -1,16 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use primitive_types::U256;
 
 /// Storage of a contract.
 #[derive(Debug, Clone)]
 pub struct Storage {
-    /// The mapping between the contract address and its storage.
-    pub store: HashMap<U256, StorageData>,
+    /// The mapping between the contract address and its storage. Private, so that every write
+    /// goes through `set_word` and is recorded in `written`.
+    store: HashMap<U256, StorageData>,
+    /// The slots written since the last `take_written`, with their contract.
+    written: HashSet<(U256, U256)>,
 }
 
 /// Storage data for a contract.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct StorageData {
     /// Mapping between storage slot and value.
     pub data: HashMap<U256, U256>,
@@ -26,35 +29,62 @@ impl StorageData {
     }
 }
 
-impl Default for StorageData {
-    fn default() -> Self {
-        Self {
-            data: HashMap::default(),
-        }
-    }
-}
-
 impl Storage {
     pub fn new_empty() -> Storage {
         Storage {
             store: HashMap::default(),
+            written: HashSet::new(),
         }
     }
 
     pub fn set_word(&mut self, address: U256, slot: U256, value: U256) {
-        let contract_storage = self
-            .store
-            .entry(address)
-            .or_insert_with(|| StorageData::default());
+        let contract_storage = self.store.entry(address).or_default();
         contract_storage.set_value(slot, value);
+        self.written.insert((address, slot));
     }
 
     pub fn load_word(&self, address: U256, slot: U256) -> U256 {
         if let Some(contract_storage) = self.store.get(&address) {
-            let value = contract_storage.get_value(slot);
-            value
+            contract_storage.get_value(slot)
         } else {
             0.into()
         }
     }
+
+    /// Returns the addresses of the contracts with some slot written.
+    pub fn addresses(&self) -> impl Iterator<Item = U256> + '_ {
+        self.store.keys().copied()
+    }
+
+    /// Returns the written slots of the contract at `address` with their value, in no order.
+    pub fn slots(&self, address: U256) -> impl Iterator<Item = (U256, U256)> + '_ {
+        self.store
+            .get(&address)
+            .into_iter()
+            .flat_map(|contract_storage| contract_storage.data.iter())
+            .map(|(slot, value)| (*slot, *value))
+    }
+
+    /// Returns the value of `slot`, `None` if it was never written.
+    pub(crate) fn slot(&self, address: U256, slot: U256) -> Option<U256> {
+        self.store
+            .get(&address)
+            .and_then(|contract_storage| contract_storage.data.get(&slot))
+            .copied()
+    }
+
+    /// Puts back `slot` as recorded by a journal, `None` if it was never written. Not
+    /// recorded as written.
+    pub(crate) fn restore_word(&mut self, address: U256, slot: U256, value: Option<U256>) {
+        let data = &mut self.store.entry(address).or_default().data;
+        match value {
+            Some(value) => data.insert(slot, value),
+            None => data.remove(&slot),
+        };
+    }
+
+    /// Returns the slots written since the last call, so that a journal can record them.
+    pub(crate) fn take_written(&mut self) -> HashSet<(U256, U256)> {
+        std::mem::take(&mut self.written)
+    }
 }
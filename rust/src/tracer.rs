@@ -0,0 +1,136 @@
+use std::io::Write;
+
+use serde_json::{json, Value};
+
+use crate::{
+    evm::ExecutionResult,
+    inspector::{CallInputs, FrameOutcome, Inspector, TraceStep},
+};
+
+/// A tracer writing one JSON line per executed opcode, in the EIP-3155 format,
+/// followed by a summary line at the end of the transaction.
+pub struct JsonTracer {
+    writer: Box<dyn Write>,
+}
+
+impl JsonTracer {
+    pub fn new(writer: Box<dyn Write>) -> JsonTracer {
+        JsonTracer { writer }
+    }
+
+    pub fn stderr() -> JsonTracer {
+        JsonTracer::new(Box::new(std::io::stderr()))
+    }
+
+    fn write_line(&mut self, line: Value) {
+        // a broken trace output must not stop the execution.
+        let _ = writeln!(self.writer, "{}", line);
+    }
+}
+
+impl Inspector for JsonTracer {
+    fn step(&mut self, step: &TraceStep) {
+        let stack: Vec<String> = step
+            .stack
+            .iter()
+            .map(|item| format!("{:#x}", item))
+            .collect();
+        let line = json!({
+            "pc": step.pc,
+            "op": step.opcode,
+            "gas": format!("{:#x}", step.gas),
+            "gasCost": format!("{:#x}", step.gas_cost),
+            "memSize": step.memory_size,
+            "stack": stack,
+            "depth": step.depth,
+            "returnData": format!("0x{}", hex::encode(step.return_data)),
+            "refund": step.refund,
+            "opName": step.op_name,
+        });
+        self.write_line(line);
+    }
+
+    fn call_end(&mut self, inputs: &CallInputs, outcome: FrameOutcome) -> FrameOutcome {
+        if inputs.depth == 1 {
+            let mut line = json!({
+                "output": hex::encode(&outcome.output),
+                "gasUsed": format!("{:#x}", outcome.gas.spent()),
+            });
+            match &outcome.result {
+                ExecutionResult::Success { .. } => {}
+                ExecutionResult::Revert { .. } => {
+                    line["error"] = Value::from("execution reverted");
+                }
+                ExecutionResult::Halt { reason, .. } => {
+                    line["error"] = Value::from(reason.to_string());
+                }
+            }
+            self.write_line(line);
+        }
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap, io::Write, rc::Rc};
+
+    use serde_json::Value;
+
+    use super::JsonTracer;
+    use crate::evm_with_inspector;
+
+    /// A writer whose output can still be read once the tracer is boxed.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_lines_match_eip_3155() {
+        let buffer = SharedBuffer::default();
+        let tracer = Rc::new(RefCell::new(JsonTracer::new(Box::new(buffer.clone()))));
+        // to, from, origin, gasprice, value, data and a gas limit of 100.
+        let mut tx_data = vec![vec![]; 6];
+        tx_data.push(vec![100]);
+        // PUSH1 1, PUSH1 2, ADD, PUSH1 0, MSTORE8, STOP
+        let result = evm_with_inspector(
+            hex::decode("600160020160005300").unwrap(),
+            tx_data,
+            vec![],
+            HashMap::new(),
+            tracer,
+        );
+        assert!(result.success);
+
+        // as printed by `evm --json run`, with the gas counted from the start of the code.
+        let expected = [
+            r#"{"pc":0,"op":96,"gas":"0x64","gasCost":"0x3","memSize":0,"stack":[],"depth":1,"returnData":"0x","refund":0,"opName":"PUSH1"}"#,
+            r#"{"pc":2,"op":96,"gas":"0x61","gasCost":"0x3","memSize":0,"stack":["0x1"],"depth":1,"returnData":"0x","refund":0,"opName":"PUSH1"}"#,
+            r#"{"pc":4,"op":1,"gas":"0x5e","gasCost":"0x3","memSize":0,"stack":["0x1","0x2"],"depth":1,"returnData":"0x","refund":0,"opName":"ADD"}"#,
+            r#"{"pc":5,"op":96,"gas":"0x5b","gasCost":"0x3","memSize":0,"stack":["0x3"],"depth":1,"returnData":"0x","refund":0,"opName":"PUSH1"}"#,
+            r#"{"pc":7,"op":83,"gas":"0x58","gasCost":"0x6","memSize":0,"stack":["0x3","0x0"],"depth":1,"returnData":"0x","refund":0,"opName":"MSTORE8"}"#,
+            r#"{"pc":8,"op":0,"gas":"0x52","gasCost":"0x0","memSize":32,"stack":[],"depth":1,"returnData":"0x","refund":0,"opName":"STOP"}"#,
+            r#"{"output":"","gasUsed":"0x12"}"#,
+        ];
+        let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let expected: Vec<Value> = expected
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, expected);
+    }
+}
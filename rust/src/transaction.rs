@@ -0,0 +1,238 @@
+use primitive_types::U256;
+
+use crate::{errors::RlpError, rlp::Rlp, utility::sha3_hash};
+
+/// The signature of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    /// The `v` of legacy transactions, or the y parity of typed ones.
+    pub v: u64,
+    pub r: U256,
+    pub s: U256,
+}
+
+/// A signed transaction, legacy (EIP-155 or not), EIP-2930 or EIP-1559.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    /// 0 for legacy transactions, 1 for EIP-2930 and 2 for EIP-1559 ones.
+    pub tx_type: u8,
+    /// `None` for legacy transactions signed without replay protection.
+    pub chain_id: Option<u64>,
+    pub nonce: u64,
+    /// The gas price, or the max fee per gas of EIP-1559 transactions.
+    pub gas_price: U256,
+    /// The max priority fee per gas of EIP-1559 transactions.
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub gas_limit: u64,
+    /// `None` for contract creations.
+    pub to: Option<U256>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    /// The access list of typed transactions, kept encoded since accesses are always warm.
+    pub access_list: Rlp,
+    pub signature: Signature,
+}
+
+impl Transaction {
+    /// Decodes a raw transaction, as sent to `eth_sendRawTransaction`.
+    pub fn decode(raw: &[u8]) -> Result<Transaction, RlpError> {
+        let tx_type = match raw.first() {
+            Some(0xc0..=0xff) => 0,
+            Some(tx_type @ (1 | 2)) => *tx_type,
+            Some(tx_type) => return Err(RlpError::UnsupportedTxType(*tx_type)),
+            None => return Err(RlpError::UnexpectedEnd),
+        };
+        let payload = if tx_type == 0 { raw } else { &raw[1..] };
+        let rlp = Rlp::decode(payload)?;
+        let fields = rlp.as_list()?;
+        let expected = match tx_type {
+            0 => 9,
+            1 => 11,
+            _ => 12,
+        };
+        if fields.len() != expected {
+            return Err(RlpError::ExpectedList);
+        }
+
+        let to = |field: &Rlp| -> Result<Option<U256>, RlpError> {
+            match field.as_bytes()? {
+                [] => Ok(None),
+                _ => field.as_u256().map(Some),
+            }
+        };
+        let signature = |fields: &[Rlp]| -> Result<Signature, RlpError> {
+            Ok(Signature {
+                v: fields[0].as_u64()?,
+                r: fields[1].as_u256()?,
+                s: fields[2].as_u256()?,
+            })
+        };
+        match tx_type {
+            0 => {
+                let signature = signature(&fields[6..])?;
+                // EIP-155 stores the chain id in `v`.
+                let chain_id = (signature.v >= 35).then(|| (signature.v - 35) / 2);
+                Ok(Transaction {
+                    tx_type,
+                    chain_id,
+                    nonce: fields[0].as_u64()?,
+                    gas_price: fields[1].as_u256()?,
+                    max_priority_fee_per_gas: None,
+                    gas_limit: fields[2].as_u64()?,
+                    to: to(&fields[3])?,
+                    value: fields[4].as_u256()?,
+                    data: fields[5].as_bytes()?.to_vec(),
+                    access_list: Rlp::List(vec![]),
+                    signature,
+                })
+            }
+            1 => Ok(Transaction {
+                tx_type,
+                chain_id: Some(fields[0].as_u64()?),
+                nonce: fields[1].as_u64()?,
+                gas_price: fields[2].as_u256()?,
+                max_priority_fee_per_gas: None,
+                gas_limit: fields[3].as_u64()?,
+                to: to(&fields[4])?,
+                value: fields[5].as_u256()?,
+                data: fields[6].as_bytes()?.to_vec(),
+                access_list: fields[7].clone(),
+                signature: signature(&fields[8..])?,
+            }),
+            _ => Ok(Transaction {
+                tx_type,
+                chain_id: Some(fields[0].as_u64()?),
+                nonce: fields[1].as_u64()?,
+                max_priority_fee_per_gas: Some(fields[2].as_u256()?),
+                gas_price: fields[3].as_u256()?,
+                gas_limit: fields[4].as_u64()?,
+                to: to(&fields[5])?,
+                value: fields[6].as_u256()?,
+                data: fields[7].as_bytes()?.to_vec(),
+                access_list: fields[8].clone(),
+                signature: signature(&fields[9..])?,
+            }),
+        }
+    }
+
+    /// Encodes the signed transaction.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut fields = self.fields();
+        fields.extend([
+            Rlp::uint(self.signature.v),
+            Rlp::uint(self.signature.r),
+            Rlp::uint(self.signature.s),
+        ]);
+        self.typed(Rlp::List(fields).encode())
+    }
+
+    /// Returns the hash of the transaction.
+    pub fn hash(&self) -> U256 {
+        U256::from_big_endian(&sha3_hash(&self.encode()))
+    }
+
+    /// Returns the hash signed by the sender.
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut fields = self.fields();
+        if let (0, Some(chain_id)) = (self.tx_type, self.chain_id) {
+            fields.extend([Rlp::uint(chain_id), Rlp::uint(0), Rlp::uint(0)]);
+        }
+        sha3_hash(&self.typed(Rlp::List(fields).encode()))
+    }
+
+    /// Returns the recovery id of the signature, 0 or 1.
+    pub fn recovery_id(&self) -> Option<u8> {
+        let v = self.signature.v;
+        match (self.tx_type, self.chain_id) {
+            (0, Some(chain_id)) => v.checked_sub(35 + 2 * chain_id),
+            (0, None) => v.checked_sub(27),
+            _ => Some(v),
+        }
+        .filter(|id| *id <= 1)
+        .map(|id| id as u8)
+    }
+
+    /// Returns the price actually paid per unit of gas in a block with `basefee`.
+    pub fn effective_gas_price(&self, basefee: U256) -> U256 {
+        match self.max_priority_fee_per_gas {
+            Some(priority_fee) => self.gas_price.min(basefee.saturating_add(priority_fee)),
+            None => self.gas_price,
+        }
+    }
+
+    /// Returns the unsigned fields, in the order of the transaction type.
+    fn fields(&self) -> Vec<Rlp> {
+        let to = match self.to {
+            Some(to) => {
+                let mut bytes = [0u8; 32];
+                to.to_big_endian(&mut bytes);
+                Rlp::Bytes(bytes[12..].to_vec())
+            }
+            None => Rlp::Bytes(vec![]),
+        };
+        let common = [
+            Rlp::uint(self.gas_limit),
+            to,
+            Rlp::uint(self.value),
+            Rlp::Bytes(self.data.clone()),
+        ];
+        let chain_id = Rlp::uint(self.chain_id.unwrap_or_default());
+        let nonce = Rlp::uint(self.nonce);
+        let gas_price = Rlp::uint(self.gas_price);
+        match self.tx_type {
+            0 => [vec![nonce, gas_price], common.to_vec()].concat(),
+            1 => [
+                vec![chain_id, nonce, gas_price],
+                common.to_vec(),
+                vec![self.access_list.clone()],
+            ]
+            .concat(),
+            _ => [
+                vec![
+                    chain_id,
+                    nonce,
+                    Rlp::uint(self.max_priority_fee_per_gas.unwrap_or_default()),
+                    gas_price,
+                ],
+                common.to_vec(),
+                vec![self.access_list.clone()],
+            ]
+            .concat(),
+        }
+    }
+
+    /// Prepends the type byte to the encoding of typed transactions.
+    fn typed(&self, payload: Vec<u8>) -> Vec<u8> {
+        match self.tx_type {
+            0 => payload,
+            tx_type => [vec![tx_type], payload].concat(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transaction;
+    use primitive_types::U256;
+
+    /// The example of EIP-155, signed with the private key 0x4646...46.
+    const EIP155_EXAMPLE: &str = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+    #[test]
+    fn eip155_example() {
+        let raw = hex::decode(EIP155_EXAMPLE).unwrap();
+        let tx = Transaction::decode(&raw).unwrap();
+        assert_eq!(tx.tx_type, 0);
+        assert_eq!(tx.chain_id, Some(1));
+        assert_eq!(tx.nonce, 9);
+        assert_eq!(tx.gas_price, U256::from(20_000_000_000u64));
+        assert_eq!(tx.gas_limit, 21000);
+        assert_eq!(tx.value, U256::exp10(18));
+        assert_eq!(tx.recovery_id(), Some(0));
+        assert_eq!(
+            hex::encode(tx.signing_hash()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+        assert_eq!(tx.encode(), raw);
+    }
+}
@@ -1,4 +1,7 @@
+use crate::utility::saturating_word;
+
 /// Tx data.
+#[derive(Debug, Clone, Default)]
 pub struct TxData {
     pub to: Vec<u8>,
     pub from: Vec<u8>,
@@ -6,28 +9,43 @@ pub struct TxData {
     pub gasprice: Vec<u8>,
     pub value: Vec<u8>,
     pub data: Vec<u8>,
+    /// The gas limit of the transaction. Without it, the execution is not metered.
+    pub gas: Option<u64>,
 }
 
 impl TxData {
+    /// Builds the tx data from the big endian encoded values in this order:
+    /// `to, from, origin, gasprice, value, data`, optionally followed by the gas limit.
+    /// A gas limit above `u64::MAX` saturates.
     pub fn new(tx_data: Vec<Vec<u8>>) -> TxData {
-        if !tx_data.is_empty() {
-            Self {
-                to: tx_data[0].clone(),
-                from: tx_data[1].clone(),
-                origin: tx_data[2].clone(),
-                gasprice: tx_data[3].clone(),
-                value: tx_data[4].clone(),
-                data: tx_data[5].clone(),
-            }
-        } else {
-            Self {
-                to: vec![],
-                from: vec![],
-                origin: vec![],
-                gasprice: vec![],
-                value: vec![],
-                data: vec![],
-            }
+        let field = |index: usize| tx_data.get(index).cloned().unwrap_or_default();
+
+        Self {
+            to: field(0),
+            from: field(1),
+            origin: field(2),
+            gasprice: field(3),
+            value: field(4),
+            data: field(5),
+            gas: tx_data
+                .get(6)
+                .map(|gas| saturating_word(gas).min(u64::MAX.into()).as_u64()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TxData;
+
+    #[test]
+    fn oversized_gas_limits_saturate() {
+        let mut tx_data = vec![vec![]; 6];
+        tx_data.push(vec![1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(TxData::new(tx_data.clone()).gas, Some(u64::MAX));
+
+        tx_data[6] = vec![0; 40];
+        tx_data[6].push(0x10);
+        assert_eq!(TxData::new(tx_data).gas, Some(0x10));
+    }
+}
@@ -1,16 +1,24 @@
 use crate::{
     block_data::BlockData,
+    env::Env,
     errors::ExecutionError,
     evm::{Evm, ExecutionResult},
+    gas::{code_deposit_gas, Gas, CALL_STIPEND},
+    inspector::{
+        inspect_call, inspect_call_end, inspect_create, inspect_create_end, CallInputs, CallScheme,
+        CreateInputs, CreateOutcome, FrameOutcome, SharedInspector,
+    },
     jumpdest::valid_jumpdest,
     memory::Memory,
+    rlp::Rlp,
     state_data::State,
     storage::Storage,
     tx_data::TxData,
-    Log,
+    Log, CALL_DEPTH_LIMIT,
 };
 use primitive_types::U256;
 use sha3::{Digest, Keccak256};
+use std::rc::Rc;
 
 pub fn push_data(push_data_size: usize, code: &[u8], start: usize) -> Result<U256, ExecutionError> {
     let remaining_code = &code[start..];
@@ -138,12 +146,12 @@ pub fn sign_extend(stack: &mut Vec<U256>, limit: usize) -> Result<U256, Executio
     let mut data = [0u8; 32];
     x.to_little_endian(&mut data);
 
-    for i in 0..32 {
-        if i as usize > b.as_usize() {
+    for (i, byte) in data.iter_mut().enumerate() {
+        if i > b.as_usize() {
             if sign_byte > 0x7f {
-                data[i] = 0xFF;
+                *byte = 0xFF;
             } else {
-                data[i] = 0x00;
+                *byte = 0x00;
             }
         }
     }
@@ -241,7 +249,7 @@ pub fn slt(stack: &mut Vec<U256>, limit: usize) -> Result<U256, ExecutionError>
     let is_b_negative = b.bit(255);
 
     let result = match (is_a_negative, is_b_negative) {
-        (true, true) => !(a.overflowing_neg() <= b.overflowing_neg()),
+        (true, true) => a.overflowing_neg() > b.overflowing_neg(),
         (true, false) => true,
         (false, true) => false,
         (false, false) => a < b,
@@ -264,7 +272,7 @@ pub fn sgt(stack: &mut Vec<U256>, limit: usize) -> Result<U256, ExecutionError>
     let is_b_negative = b.bit(255);
 
     let result = match (is_a_negative, is_b_negative) {
-        (true, true) => !(a.overflowing_neg() >= b.overflowing_neg()),
+        (true, true) => a.overflowing_neg() < b.overflowing_neg(),
         (true, false) => false,
         (false, true) => true,
         (false, false) => a > b,
@@ -481,7 +489,7 @@ pub fn mload(
     let value = memory.get_word(offset)?;
 
     push(stack, value, limit)?;
-    Ok(value.into())
+    Ok(value)
 }
 
 pub fn mstore8(stack: &mut Vec<U256>, memory: &mut Memory) -> Result<U256, ExecutionError> {
@@ -516,17 +524,25 @@ pub fn sha_3(
 
     let value = &memory.store[offset..(offset + size)];
 
-    let result = U256::from_big_endian(&sha3_hash(&value));
+    let result = U256::from_big_endian(&sha3_hash(value));
 
     push(stack, result, limit)?;
 
     Ok(result)
 }
 
+/// Reads a big endian word, saturating to `U256::MAX` if it has more than 32 significant bytes.
+pub fn saturating_word(bytes: &[u8]) -> U256 {
+    let excess = bytes.len().saturating_sub(32);
+    if bytes[..excess].iter().any(|byte| *byte != 0) {
+        return U256::MAX;
+    }
+    U256::from_big_endian(&bytes[excess..])
+}
+
 pub fn sha3_hash(data: &[u8]) -> [u8; 32] {
     if data.is_empty() {
-        let result = [0; 32];
-        result
+        [0; 32]
     } else {
         // create hash
         let mut hasher = Keccak256::new();
@@ -557,9 +573,21 @@ pub fn balance(stack: &mut Vec<U256>, state: &State, limit: usize) -> Result<U25
     push_from_big_endian(stack, &balance_bytes, limit)
 }
 
+pub fn blockhash(
+    stack: &mut Vec<U256>,
+    block_data: &BlockData,
+    limit: usize,
+) -> Result<U256, ExecutionError> {
+    let number = pop(stack)?;
+    let hash = block_data.block_hash(number);
+
+    push(stack, hash, limit)?;
+    Ok(hash)
+}
+
 pub fn calldataload(
     stack: &mut Vec<U256>,
-    data: &Vec<u8>,
+    data: &[u8],
     limit: usize,
 ) -> Result<U256, ExecutionError> {
     let index = pop(stack)?.as_usize();
@@ -614,6 +642,47 @@ pub fn copy_data_to_memory(
     Ok(())
 }
 
+pub fn returndatacopy(
+    stack: &mut Vec<U256>,
+    memory: &mut Memory,
+    return_data: &[u8],
+) -> Result<(), ExecutionError> {
+    let dest_offset = pop(stack)?;
+    let offset = pop(stack)?;
+    let size = pop(stack)?;
+
+    // unlike the other copy opcodes, reading past the end of the return data is an error.
+    let (end, overflow) = offset.overflowing_add(size);
+    if overflow || end > return_data.len().into() {
+        return Err(ExecutionError::ReturnDataOutOfBounds);
+    }
+    // an empty copy doesn't touch the memory, whatever its offset.
+    if size.is_zero() {
+        return Ok(());
+    }
+    // a destination that doesn't fit in memory can't be paid for.
+    if dest_offset > usize::MAX.into() {
+        return Err(ExecutionError::OutOfGas);
+    }
+
+    memory.save_bytes(
+        dest_offset.as_usize(),
+        &return_data[offset.as_usize()..end.as_usize()],
+    )
+}
+
+/// Copies the return data of a sub call into the `ret_offset..ret_offset + ret_size` window of the
+/// caller memory. Return data exceeding the window is ignored.
+pub fn copy_return_data(
+    memory: &mut Memory,
+    ret_offset: usize,
+    ret_size: usize,
+    return_data: &[u8],
+) -> Result<(), ExecutionError> {
+    let size = std::cmp::min(ret_size, return_data.len());
+    memory.save_bytes(ret_offset, &return_data[..size])
+}
+
 pub fn push_data_size(
     stack: &mut Vec<U256>,
     data: &[u8],
@@ -761,18 +830,23 @@ pub fn revert(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn call(
     stack: &mut Vec<U256>,
     memory: &mut Memory,
     state: &mut State,
     storage: &mut Storage,
     tx_to: &[u8],
-    tx_origin: &[u8],
+    env: &Rc<Env>,
     last_ret_data: &mut Vec<u8>,
+    logs: &mut Vec<Log>,
+    gas: &mut Gas,
+    depth: usize,
+    inspector: &SharedInspector,
     limit: usize,
     read_only: bool,
 ) -> Result<U256, ExecutionError> {
-    let _gas = pop(stack)?;
+    let gas_requested = pop(stack)?;
     let address = pop(stack)?;
     let value = pop(stack)?;
 
@@ -783,7 +857,15 @@ pub fn call(
     let args_offset = pop(stack)?.as_usize();
     let args_size = pop(stack)?.as_usize();
     let ret_offset = pop(stack)?.as_usize();
-    let _ret_size = pop(stack)?.as_usize();
+    let ret_size = pop(stack)?.as_usize();
+
+    // the return data window is expanded even if the sub call fails.
+    if ret_size > 0 {
+        memory.resize(ret_offset, ret_size)?;
+    }
+    if depth >= CALL_DEPTH_LIMIT {
+        return fail_sub_call(stack, last_ret_data, limit);
+    }
 
     let code = state.get_code(address);
     let calldata = memory.get_bytes(args_offset, args_size)?;
@@ -791,20 +873,33 @@ pub fn call(
     address.to_big_endian(&mut to);
     let mut value_bytes = [0u8; 32];
     value.to_big_endian(&mut value_bytes);
+    let stipend = if value.is_zero() { 0 } else { CALL_STIPEND };
+    let child_gas = gas.reserve_call_gas(gas_requested, stipend)?;
     let tx_data = TxData::new(vec![
         to.to_vec(),
         tx_to.to_vec(),
-        tx_origin.to_vec(),
-        vec![],
+        env.origin.clone(),
+        env.gasprice.clone(),
         value_bytes.to_vec(),
         calldata,
     ]);
-    let block_data = BlockData::new(vec![]);
 
-    let mut new_evm = Evm::new(
+    let inputs = CallInputs {
+        scheme: CallScheme::Call,
+        caller: U256::from_big_endian(tx_to),
+        target: address,
+        code_address: address,
+        value,
+        input: tx_data.data.clone(),
+        gas: child_gas,
+        depth: depth + 1,
+        is_static: false,
+    };
+
+    let new_evm = Evm::new(
         Box::from(code),
         tx_data,
-        block_data,
+        Rc::clone(env),
         state.clone(),
         storage.clone(),
         vec![],
@@ -814,26 +909,33 @@ pub fn call(
         Memory::new(),
         limit,
         false,
-    );
-
-    let result = new_evm.execute();
-
-    memory.save_bytes(ret_offset, &new_evm.return_data())?;
-    *last_ret_data = new_evm.return_data();
-
-    let res = match result {
-        ExecutionResult::Success | ExecutionResult::Halt => {
-            *state = new_evm.state();
-            *storage = new_evm.storage();
-            1.into()
+        child_gas,
+    )
+    .with_depth(depth + 1)
+    .with_inspector(inspector.clone());
+
+    let (outcome, changes) = execute_call(new_evm, &inputs, inspector);
+    gas.return_call_gas(&outcome.gas, outcome.result.is_success());
+
+    copy_return_data(memory, ret_offset, ret_size, &outcome.output)?;
+    *last_ret_data = outcome.output;
+
+    let res = if outcome.result.is_success() {
+        if let Some((new_state, new_storage, new_logs)) = changes {
+            *state = new_state;
+            *storage = new_storage;
+            logs.extend(new_logs);
         }
-        ExecutionResult::Revert => 0.into(),
+        1.into()
+    } else {
+        0.into()
     };
 
     push(stack, res, limit)?;
     Ok(res)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn delegatecall(
     stack: &mut Vec<U256>,
     memory: &mut Memory,
@@ -841,36 +943,60 @@ pub fn delegatecall(
     storage: &mut Storage,
     tx_to: &[u8],
     tx_from: &[u8],
-    tx_origin: &[u8],
+    env: &Rc<Env>,
     value: &[u8],
     last_ret_data: &mut Vec<u8>,
+    logs: &mut Vec<Log>,
+    gas: &mut Gas,
+    depth: usize,
+    inspector: &SharedInspector,
     limit: usize,
 ) -> Result<U256, ExecutionError> {
-    let _gas = pop(stack)?;
+    let gas_requested = pop(stack)?;
     let address = pop(stack)?;
     let args_offset = pop(stack)?.as_usize();
     let args_size = pop(stack)?.as_usize();
     let ret_offset = pop(stack)?.as_usize();
-    let _ret_size = pop(stack)?.as_usize();
+    let ret_size = pop(stack)?.as_usize();
+
+    // the return data window is expanded even if the sub call fails.
+    if ret_size > 0 {
+        memory.resize(ret_offset, ret_size)?;
+    }
+    if depth >= CALL_DEPTH_LIMIT {
+        return fail_sub_call(stack, last_ret_data, limit);
+    }
 
     let code = state.get_code(address);
     let calldata = memory.get_bytes(args_offset, args_size)?;
     let mut to = [0u8; 32];
     address.to_big_endian(&mut to);
+    let child_gas = gas.reserve_call_gas(gas_requested, 0)?;
     let tx_data = TxData::new(vec![
         tx_to.to_vec(),
         tx_from.to_vec(),
-        tx_origin.to_vec(),
-        vec![],
+        env.origin.clone(),
+        env.gasprice.clone(),
         value.to_vec(),
         calldata,
     ]);
-    let block_data = BlockData::new(vec![]);
 
-    let mut new_evm = Evm::new(
+    let inputs = CallInputs {
+        scheme: CallScheme::DelegateCall,
+        caller: U256::from_big_endian(tx_from),
+        target: U256::from_big_endian(tx_to),
+        code_address: address,
+        value: U256::from_big_endian(value),
+        input: tx_data.data.clone(),
+        gas: child_gas,
+        depth: depth + 1,
+        is_static: false,
+    };
+
+    let new_evm = Evm::new(
         Box::from(code),
         tx_data,
-        block_data,
+        Rc::clone(env),
         state.clone(),
         storage.clone(),
         vec![],
@@ -880,62 +1006,93 @@ pub fn delegatecall(
         Memory::new(),
         limit,
         false,
-    );
-
-    let result = new_evm.execute();
-
-    memory.save_bytes(ret_offset, &new_evm.return_data())?;
-    *last_ret_data = new_evm.return_data();
-
-    let res = match result {
-        ExecutionResult::Success | ExecutionResult::Halt => {
-            *state = new_evm.state();
-            *storage = new_evm.storage();
-            1.into()
+        child_gas,
+    )
+    .with_depth(depth + 1)
+    .with_inspector(inspector.clone());
+
+    let (outcome, changes) = execute_call(new_evm, &inputs, inspector);
+    gas.return_call_gas(&outcome.gas, outcome.result.is_success());
+
+    copy_return_data(memory, ret_offset, ret_size, &outcome.output)?;
+    *last_ret_data = outcome.output;
+
+    let res = if outcome.result.is_success() {
+        if let Some((new_state, new_storage, new_logs)) = changes {
+            *state = new_state;
+            *storage = new_storage;
+            logs.extend(new_logs);
         }
-        ExecutionResult::Revert => 0.into(),
+        1.into()
+    } else {
+        0.into()
     };
 
     push(stack, res, limit)?;
     Ok(res)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn staticcall(
     stack: &mut Vec<U256>,
     memory: &mut Memory,
     state: &mut State,
     storage: &mut Storage,
     tx_to: &[u8],
-    tx_origin: &[u8],
+    env: &Rc<Env>,
     tx_value: &[u8],
     last_ret_data: &mut Vec<u8>,
+    logs: &mut Vec<Log>,
+    gas: &mut Gas,
+    depth: usize,
+    inspector: &SharedInspector,
     limit: usize,
 ) -> Result<U256, ExecutionError> {
-    let _gas = pop(stack)?;
+    let gas_requested = pop(stack)?;
     let address = pop(stack)?;
     let args_offset = pop(stack)?.as_usize();
     let args_size = pop(stack)?.as_usize();
     let ret_offset = pop(stack)?.as_usize();
-    let _ret_size = pop(stack)?.as_usize();
+    let ret_size = pop(stack)?.as_usize();
+
+    // the return data window is expanded even if the sub call fails.
+    if ret_size > 0 {
+        memory.resize(ret_offset, ret_size)?;
+    }
+    if depth >= CALL_DEPTH_LIMIT {
+        return fail_sub_call(stack, last_ret_data, limit);
+    }
 
     let code = state.get_code(address);
     let calldata = memory.get_bytes(args_offset, args_size)?;
     let mut to = [0u8; 32];
     address.to_big_endian(&mut to);
+    let child_gas = gas.reserve_call_gas(gas_requested, 0)?;
     let tx_data = TxData::new(vec![
         to.to_vec(),
         tx_to.to_vec(),
-        tx_origin.to_vec(),
-        vec![],
+        env.origin.clone(),
+        env.gasprice.clone(),
         tx_value.to_vec(),
         calldata,
     ]);
-    let block_data = BlockData::new(vec![]);
 
-    let mut new_evm = Evm::new(
+    let inputs = CallInputs {
+        scheme: CallScheme::StaticCall,
+        caller: U256::from_big_endian(tx_to),
+        target: address,
+        code_address: address,
+        value: 0.into(),
+        input: tx_data.data.clone(),
+        gas: child_gas,
+        depth: depth + 1,
+        is_static: true,
+    };
+
+    let new_evm = Evm::new(
         Box::from(code),
         tx_data,
-        block_data,
+        Rc::clone(env),
         state.clone(),
         storage.clone(),
         vec![],
@@ -945,34 +1102,45 @@ pub fn staticcall(
         Memory::new(),
         limit,
         true,
-    );
-
-    let result = new_evm.execute();
-
-    memory.save_bytes(ret_offset, &new_evm.return_data())?;
-    *last_ret_data = new_evm.return_data();
-
-    let res = match result {
-        ExecutionResult::Success | ExecutionResult::Halt => {
-            *state = new_evm.state();
-            *storage = new_evm.storage();
-            1.into()
+        child_gas,
+    )
+    .with_depth(depth + 1)
+    .with_inspector(inspector.clone());
+
+    let (outcome, changes) = execute_call(new_evm, &inputs, inspector);
+    gas.return_call_gas(&outcome.gas, outcome.result.is_success());
+
+    copy_return_data(memory, ret_offset, ret_size, &outcome.output)?;
+    *last_ret_data = outcome.output;
+
+    let res = if outcome.result.is_success() {
+        if let Some((new_state, new_storage, new_logs)) = changes {
+            *state = new_state;
+            *storage = new_storage;
+            logs.extend(new_logs);
         }
-        ExecutionResult::Revert => 0.into(),
+        1.into()
+    } else {
+        0.into()
     };
 
     push(stack, res, limit)?;
     Ok(res)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create(
     stack: &mut Vec<U256>,
     memory: &mut Memory,
     state: &mut State,
     storage: &mut Storage,
     tx_to: &[u8],
-    tx_origin: &[u8],
+    env: &Rc<Env>,
     last_ret_data: &mut Vec<u8>,
+    logs: &mut Vec<Log>,
+    gas: &mut Gas,
+    depth: usize,
+    inspector: &SharedInspector,
     limit: usize,
     read_only: bool,
 ) -> Result<U256, ExecutionError> {
@@ -983,68 +1151,168 @@ pub fn create(
     let value = pop(stack)?;
     let offset = pop(stack)?.as_usize();
     let size = pop(stack)?.as_usize();
+    if depth >= CALL_DEPTH_LIMIT {
+        return fail_sub_call(stack, last_ret_data, limit);
+    }
 
     let code = memory.get_bytes(offset, size)?;
     let address = U256::from_big_endian(tx_to);
     let nonce = state.get_nonce(address);
-
-    let contract_address = calculate_address(tx_to, nonce);
+    let contract_address = contract_address(address, nonce as u64);
+    // the nonce of the creator is incremented even if the creation fails.
+    state.set_nonce(address, nonce + 1);
+
+    // the new account exists, with a nonce of 1, while its constructor runs.
+    let mut created = state.clone();
+    created.save_code(contract_address, vec![], value)?;
+    created.set_nonce(contract_address, 1);
     let mut contract_address_bytes = [0u8; 32];
     contract_address.to_big_endian(&mut contract_address_bytes);
 
     let mut value_bytes = [0u8; 32];
     value.to_big_endian(&mut value_bytes);
 
+    let child_gas = gas.reserve_call_gas(U256::max_value(), 0)?;
     let tx_data = TxData::new(vec![
         contract_address_bytes.to_vec(),
         tx_to.to_vec(),
-        tx_origin.to_vec(),
-        vec![],
+        env.origin.clone(),
+        env.gasprice.clone(),
         value_bytes.to_vec(),
         vec![],
     ]);
-    let block_data = BlockData::new(vec![]);
-
-    let mut new_evm = Evm::new(
-        Box::from(code),
-        tx_data,
-        block_data,
-        state.clone(),
-        storage.clone(),
-        vec![],
-        vec![],
-        vec![],
-        vec![],
-        Memory::new(),
-        limit,
-        false,
-    );
 
-    let result = new_evm.execute();
+    let inputs = CreateInputs {
+        caller: address,
+        address: contract_address,
+        value,
+        init_code: code.clone(),
+        gas: child_gas,
+        depth: depth + 1,
+    };
 
-    let res = match result {
-        ExecutionResult::Success | ExecutionResult::Halt => {
-            *state = new_evm.state();
-            *storage = new_evm.storage();
+    let (outcome, changes) = match inspect_create(inspector, &inputs) {
+        Some(outcome) => (outcome, None),
+        None => {
+            let mut new_evm = Evm::new(
+                Box::from(code),
+                tx_data,
+                Rc::clone(env),
+                created.clone(),
+                storage.clone(),
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                Memory::new(),
+                limit,
+                false,
+                child_gas,
+            )
+            .with_depth(depth + 1)
+            .with_inspector(inspector.clone());
+
+            let result = new_evm.execute();
+
+            // the code deposit is paid by the constructor: if there isn't enough gas left, the creation fails.
+            let mut child_gas = new_evm.gas();
+            let deposited = result.is_success()
+                && child_gas
+                    .record_cost(code_deposit_gas(&new_evm.return_data()))
+                    .is_ok();
+            if result.is_success() && !deposited {
+                child_gas.burn_remaining();
+            }
 
-            state.save_code(contract_address, new_evm.return_data(), value)?;
-            *last_ret_data = new_evm.return_data();
+            let outcome = CreateOutcome {
+                result,
+                output: new_evm.return_data(),
+                gas: child_gas,
+                address: deposited.then_some(contract_address),
+            };
+            let changes =
+                deposited.then(|| (new_evm.state(), new_evm.storage(), new_evm.emitted_logs()));
+            (outcome, changes)
+        }
+    };
+    let outcome = inspect_create_end(inspector, &inputs, outcome);
+    gas.return_call_gas(&outcome.gas, outcome.address.is_some());
+
+    let res = match outcome.address {
+        Some(contract_address) => {
+            *state = match changes {
+                Some((new_state, new_storage, new_logs)) => {
+                    *storage = new_storage;
+                    logs.extend(new_logs);
+                    new_state
+                }
+                None => created,
+            };
+            state.set_code(contract_address, outcome.output);
+            last_ret_data.clear();
             contract_address
         }
-        ExecutionResult::Revert => 0.into(),
+        None => {
+            // only a reverting constructor returns data, an exceptional halt leaves none.
+            match outcome.result {
+                ExecutionResult::Revert { .. } => *last_ret_data = outcome.output,
+                _ => last_ret_data.clear(),
+            }
+            0.into()
+        }
     };
 
     push(stack, res, limit)?;
     Ok(res)
 }
 
-pub fn calculate_address(sender_address: &[u8], nonce: usize) -> U256 {
-    // no rlp encoding here... in a REAL EVM you should rlp encode [sender_address + nonce] before hashing
-    let result = sha3_hash(&[sender_address, &nonce.to_be_bytes()].concat()).to_vec();
-    let result = result.get(12..).unwrap_or(&[0_u8; 20]);
-    let result = U256::from_big_endian(&result);
+/// Fails a sub call that would go deeper than the call depth limit: 0 is pushed and no gas is
+/// forwarded.
+fn fail_sub_call(
+    stack: &mut Vec<U256>,
+    last_ret_data: &mut Vec<u8>,
+    limit: usize,
+) -> Result<U256, ExecutionError> {
+    last_ret_data.clear();
+    push(stack, U256::zero(), limit)?;
+    Ok(U256::zero())
+}
+
+/// Executes a sub call frame, unless the inspector provides its outcome. Returns the outcome
+/// and, if the frame was executed and succeeded, the state it left and the logs it emitted.
+fn execute_call(
+    mut frame: Evm,
+    inputs: &CallInputs,
+    inspector: &SharedInspector,
+) -> (FrameOutcome, Option<(State, Storage, Vec<Log>)>) {
+    let (outcome, changes) = match inspect_call(inspector, inputs) {
+        Some(outcome) => (outcome, None),
+        None => {
+            let result = frame.execute();
+            let changes = result
+                .is_success()
+                .then(|| (frame.state(), frame.storage(), frame.emitted_logs()));
+            let outcome = FrameOutcome {
+                result,
+                output: frame.return_data(),
+                gas: frame.gas(),
+            };
+            (outcome, changes)
+        }
+    };
+    (inspect_call_end(inspector, inputs, outcome), changes)
+}
 
-    result
+/// Returns the address of the contract created by `sender` with `nonce`,
+/// `keccak256(rlp([sender, nonce]))[12..]`.
+pub fn contract_address(sender: U256, nonce: u64) -> U256 {
+    let mut sender_bytes = [0u8; 32];
+    sender.to_big_endian(&mut sender_bytes);
+    let rlp = Rlp::List(vec![
+        Rlp::Bytes(sender_bytes[12..].to_vec()),
+        Rlp::uint(nonce),
+    ]);
+    U256::from_big_endian(&sha3_hash(&rlp.encode())[12..])
 }
 
 pub fn selfdestruct(
@@ -1064,3 +1332,191 @@ pub fn selfdestruct(
     state.delete_account(src_address);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::contract_address;
+    use crate::{
+        address::Address,
+        assembler::assemble,
+        block_data::{BlockData, BlockHashes},
+        errors::HaltReason,
+        evm::{Evm, ExecutionResult},
+        state_data::AddressData,
+    };
+    use primitive_types::U256;
+
+    #[test]
+    fn calls_stop_at_the_depth_limit() {
+        // counts the frames in slot 0, then CALLs itself until a call fails.
+        let code = hex::decode("60005460010160005560006000600060006000305af100").unwrap();
+        // 1025 frames don't fit in the stack of a test thread in debug builds.
+        let frames = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || {
+                let result = Evm::builder()
+                    .code(code.clone())
+                    .account(
+                        Address::default(),
+                        AddressData {
+                            code,
+                            ..AddressData::default()
+                        },
+                    )
+                    .build()
+                    .run();
+                assert!(result.success);
+                result.storage.load_word(U256::zero(), U256::zero())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(frames, 1025.into());
+    }
+
+    #[test]
+    fn create_derives_the_address_from_the_nonce() {
+        // two empty CREATEs.
+        let result = Evm::builder()
+            .code(hex::decode("5f5f5ff05f5f5ff000").unwrap())
+            .build()
+            .run();
+        assert!(result.success);
+        let creator = U256::zero();
+        assert_eq!(
+            result.stack,
+            vec![contract_address(creator, 1), contract_address(creator, 0)]
+        );
+        assert_eq!(result.state.get_nonce(creator), 2);
+        assert_eq!(result.state.get_nonce(result.stack[0]), 1);
+    }
+
+    #[test]
+    fn create_leaves_return_data_only_on_revert() {
+        let return_data_size = |init_code: &str| {
+            // stores the init code right aligned in the first word, CREATEs it, then checks the
+            // return data.
+            let size = init_code.len() / 2;
+            let code = assemble(&format!(
+                "PUSH{size} 0x{init_code} PUSH1 0 MSTORE
+                 PUSH1 {size} PUSH1 {} PUSH1 0 CREATE POP RETURNDATASIZE STOP",
+                32 - size
+            ))
+            .unwrap();
+            let result = Evm::builder().code(code).build().run();
+            assert!(result.success);
+            result.stack[0]
+        };
+        // returns one byte of runtime code.
+        assert_eq!(return_data_size("60ff60005360016000f3"), U256::zero());
+        // reverts with two bytes.
+        assert_eq!(return_data_size("60026000fd"), 2.into());
+        // runs out of stack.
+        assert_eq!(return_data_size("50"), U256::zero());
+    }
+
+    #[test]
+    fn blockhash_reads_the_block_hash_provider() {
+        let block = BlockData {
+            number: 300.into(),
+            block_hashes: BlockHashes::new(
+                [(44.into(), 0xaa.into()), (43.into(), 0xbb.into())].into(),
+            ),
+            ..BlockData::default()
+        };
+        let result = Evm::builder()
+            .code(assemble("PUSH1 44 BLOCKHASH  PUSH1 43 BLOCKHASH").unwrap())
+            .block(block)
+            .build()
+            .run();
+        assert!(result.success);
+        // 43 is 257 blocks ago.
+        assert_eq!(result.stack, vec![U256::zero(), 0xaa.into()]);
+    }
+
+    /// Runs `code` with a contract at 0xbb returning `0xff` repeated 32 times.
+    fn run_with_callee(code: &str) -> crate::EvmResult {
+        let callee = AddressData {
+            code: assemble("PUSH1 0 NOT PUSH1 0 MSTORE  PUSH1 32 PUSH1 0 RETURN").unwrap(),
+            ..AddressData::default()
+        };
+        Evm::builder()
+            .code(assemble(code).unwrap())
+            .account(Address::from(U256::from(0xbb)), callee)
+            .build()
+            .run()
+    }
+
+    #[test]
+    fn call_return_data_is_cut_to_the_ret_size_window() {
+        // 2 bytes of the 32 returned are copied at 1.
+        let result = run_with_callee(
+            "PUSH1 2 PUSH1 1 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL  PUSH1 0 MLOAD",
+        );
+        assert!(result.success);
+        assert_eq!(result.stack[0], U256::from(0xffff) << 232);
+    }
+
+    #[test]
+    fn failed_calls_expand_the_memory_of_their_ret_window() {
+        let reverting = AddressData {
+            code: assemble("PUSH1 0 PUSH1 0 REVERT").unwrap(),
+            ..AddressData::default()
+        };
+        let result = Evm::builder()
+            .code(
+                assemble("PUSH1 32 PUSH1 64 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL  MSIZE")
+                    .unwrap(),
+            )
+            .account(Address::from(U256::from(0xbb)), reverting)
+            .build()
+            .run();
+        assert!(result.success);
+        assert_eq!(result.stack, vec![96.into(), U256::zero()]);
+    }
+
+    #[test]
+    fn returndatacopy_reads_only_the_return_data() {
+        let call = "PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0 PUSH1 0xbb GAS CALL POP";
+        let result = run_with_callee(&format!(
+            "{call}  PUSH1 2 PUSH1 30 PUSH1 0 RETURNDATACOPY  PUSH1 0 MLOAD"
+        ));
+        assert!(result.success);
+        assert_eq!(result.stack, vec![U256::from(0xffff) << 240]);
+
+        // one byte past the end.
+        let result = run_with_callee(&format!("{call}  PUSH1 2 PUSH1 31 PUSH1 0 RETURNDATACOPY"));
+        assert!(matches!(
+            result.result,
+            ExecutionResult::Halt {
+                reason: HaltReason::ReturnDataOutOfBounds,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn empty_returndatacopy_ignores_the_destination() {
+        let result = run_with_callee(&format!(
+            "PUSH1 0 PUSH1 0 PUSH32 0x{} RETURNDATACOPY  MSIZE",
+            "ff".repeat(32)
+        ));
+        assert!(result.success);
+        assert_eq!(result.stack, vec![U256::zero()]);
+    }
+
+    #[test]
+    fn contract_address_vectors() {
+        let sender = U256::from_str_radix("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0", 16).unwrap();
+        let addresses = [
+            "cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d",
+            "343c43a37d37dff08ae8c4a11544c718abb4fcf8",
+            "f778b86fa74e846c4f0a1fbd1335fe81c00a0c91",
+            "fffd933a0bc612844eaf0c6fe3e5b8e9b6c1d19c",
+        ];
+        for (nonce, address) in addresses.into_iter().enumerate() {
+            let address = U256::from_str_radix(address, 16).unwrap();
+            assert_eq!(contract_address(sender, nonce as u64), address);
+        }
+    }
+}
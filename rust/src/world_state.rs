@@ -0,0 +1,235 @@
+use primitive_types::U256;
+
+use crate::{
+    state_data::{AddressData, State},
+    storage::Storage,
+};
+
+/// A change to the world state, with the value it replaced.
+#[derive(Debug, Clone)]
+enum Change {
+    /// `None` if the account didn't exist.
+    Account {
+        address: U256,
+        previous: Option<AddressData>,
+    },
+    /// `None` if the slot was never written.
+    Slot {
+        address: U256,
+        slot: U256,
+        previous: Option<U256>,
+    },
+}
+
+/// The accounts and their storage, with snapshots to go back to.
+///
+/// A snapshot doesn't copy anything: while some snapshot is taken, every change is recorded in
+/// a journal with the value it replaced, and reverting undoes the changes in reverse order.
+#[derive(Debug, Clone)]
+pub struct WorldState {
+    state: State,
+    storage: Storage,
+    journal: Vec<Change>,
+    /// The id of each snapshot and the length of the journal when it was taken.
+    snapshots: Vec<(usize, usize)>,
+    next_id: usize,
+}
+
+impl WorldState {
+    pub fn new(mut state: State, mut storage: Storage) -> WorldState {
+        // what was written before isn't a change to record.
+        state.take_written();
+        storage.take_written();
+        WorldState {
+            state,
+            storage,
+            journal: vec![],
+            snapshots: vec![],
+            next_id: 0,
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    /// Takes a snapshot of the current state, returning its id.
+    pub fn snapshot(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.snapshots.push((id, self.journal.len()));
+        id
+    }
+
+    /// Goes back to the state of the snapshot `id`. The snapshot and the ones taken after it
+    /// are dropped. Returns false if there's no such snapshot.
+    pub fn revert_to(&mut self, id: usize) -> bool {
+        let Some(index) = self
+            .snapshots
+            .iter()
+            .position(|(snapshot, _)| *snapshot == id)
+        else {
+            return false;
+        };
+        let (_, length) = self.snapshots[index];
+        self.snapshots.truncate(index);
+        for change in self.journal.drain(length..).rev() {
+            match change {
+                Change::Account { address, previous } => {
+                    self.state.restore_account(address, previous)
+                }
+                Change::Slot {
+                    address,
+                    slot,
+                    previous,
+                } => self.storage.restore_word(address, slot, previous),
+            }
+        }
+        if self.snapshots.is_empty() {
+            self.journal.clear();
+        }
+        true
+    }
+
+    /// Replaces the state with the outcome of an execution, recording the accounts and slots
+    /// it wrote.
+    pub fn commit(&mut self, mut state: State, mut storage: Storage) {
+        let accounts = state.take_written();
+        let slots = storage.take_written();
+        if !self.snapshots.is_empty() {
+            for address in accounts {
+                self.record_account(address);
+            }
+            for (address, slot) in slots {
+                self.record_slot(address, slot);
+            }
+        }
+        self.state = state;
+        self.storage = storage;
+    }
+
+    pub fn set_balance(&mut self, address: U256, balance: U256) {
+        self.write_account(address, |state| state.set_balance(address, balance));
+    }
+
+    pub fn set_nonce(&mut self, address: U256, nonce: usize) {
+        self.write_account(address, |state| state.set_nonce(address, nonce));
+    }
+
+    pub fn set_code(&mut self, address: U256, code: Vec<u8>) {
+        self.write_account(address, |state| state.set_code(address, code));
+    }
+
+    pub fn set_word(&mut self, address: U256, slot: U256, value: U256) {
+        if !self.snapshots.is_empty() {
+            self.record_slot(address, slot);
+        }
+        self.storage.set_word(address, slot, value);
+        // recorded already, it mustn't be recorded again by the next commit.
+        self.storage.take_written();
+    }
+
+    fn write_account(&mut self, address: U256, write: impl FnOnce(&mut State)) {
+        if !self.snapshots.is_empty() {
+            self.record_account(address);
+        }
+        write(&mut self.state);
+        self.state.take_written();
+    }
+
+    fn record_account(&mut self, address: U256) {
+        self.journal.push(Change::Account {
+            address,
+            previous: self.state.account(address).cloned(),
+        });
+    }
+
+    fn record_slot(&mut self, address: U256, slot: U256) {
+        self.journal.push(Change::Slot {
+            address,
+            slot,
+            previous: self.storage.slot(address, slot),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WorldState;
+    use crate::{state_data::State, storage::Storage};
+    use primitive_types::U256;
+
+    #[test]
+    fn revert_undoes_the_writes_of_a_commit() {
+        let (alice, bob) = (U256::from(1), U256::from(2));
+        let mut world = WorldState::new(State::default(), Storage::new_empty());
+        world.set_balance(alice, 100.into());
+        world.set_word(alice, 0.into(), 7.into());
+
+        let id = world.snapshot();
+        let mut state = world.state().clone();
+        let mut storage = world.storage().clone();
+        state.set_balance(alice, 50.into());
+        state.set_nonce(bob, 1);
+        storage.set_word(alice, 0.into(), 8.into());
+        storage.set_word(bob, 1.into(), 9.into());
+        world.commit(state, storage);
+        // only the 2 accounts and 2 slots written are journaled.
+        assert_eq!(world.journal.len(), 4);
+
+        assert!(world.revert_to(id));
+        assert_eq!(world.state().get_balance(alice), 100.into());
+        assert!(!world.state().exists(bob));
+        assert_eq!(world.storage().load_word(alice, 0.into()), 7.into());
+        assert_eq!(world.storage().load_word(bob, 1.into()), U256::zero());
+    }
+
+    #[test]
+    fn nested_snapshots_revert_one_at_a_time() {
+        let alice = U256::from(1);
+        let mut world = WorldState::new(State::default(), Storage::new_empty());
+        world.set_balance(alice, 1.into());
+        let first = world.snapshot();
+        world.set_balance(alice, 2.into());
+        world.set_word(alice, 0.into(), 2.into());
+        let second = world.snapshot();
+        world.set_balance(alice, 3.into());
+        world.set_word(alice, 0.into(), 3.into());
+
+        assert!(world.revert_to(second));
+        assert_eq!(world.state().get_balance(alice), 2.into());
+        assert_eq!(world.storage().load_word(alice, 0.into()), 2.into());
+        assert!(world.revert_to(first));
+        assert_eq!(world.state().get_balance(alice), 1.into());
+        // the slot was never written before the first snapshot: it's removed, not zeroed.
+        assert_eq!(world.storage().slots(alice).count(), 0);
+        assert!(world.journal.is_empty());
+    }
+
+    #[test]
+    fn reverting_drops_the_newer_snapshots() {
+        let alice = U256::from(1);
+        let mut world = WorldState::new(State::default(), Storage::new_empty());
+        world.set_word(alice, 0.into(), 1.into());
+        let first = world.snapshot();
+        world.set_word(alice, 0.into(), 2.into());
+        let second = world.snapshot();
+        world.set_word(alice, 0.into(), 3.into());
+
+        assert!(world.revert_to(first));
+        assert_eq!(world.storage().load_word(alice, 0.into()), 1.into());
+        assert!(!world.revert_to(second));
+        assert!(!world.revert_to(first));
+
+        // the ids of the dropped snapshots aren't given again.
+        let third = world.snapshot();
+        assert!(third > second);
+        world.set_word(alice, 0.into(), 4.into());
+        assert!(world.revert_to(third));
+        assert_eq!(world.storage().load_word(alice, 0.into()), 1.into());
+    }
+}